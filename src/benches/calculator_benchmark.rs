@@ -16,6 +16,7 @@ fn bench_calculator(c: &mut Criterion) {
                 token_out: address!("abcdef1234567890abcdef1234567890abcdef12"),
                 protocol: PoolType::UniswapV2,
                 fee: 3000,
+                split: Vec::new(),
             },
         ],
         hash: 0,
@@ -31,5 +32,26 @@ fn bench_calculator(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_calculator);
+fn bench_maverick_tick_sweep(c: &mut Criterion) {
+    let pool = address!("5b0f1b2c3d4e5f60718293a4b5c6d7e8f9a0b1c2");
+    let amount = U256::from(1_000_000_000_000_000_000u128);
+
+    let market_state = Arc::new(MarketState::mock());
+    let calculator = Calculator::new(market_state);
+
+    let mut group = c.benchmark_group("maverick_tick_sweep");
+    group.bench_function("serial", |b| {
+        b.iter(|| {
+            Calculator::maverick_tick_candidates(true)
+                .into_iter()
+                .max_by_key(|tick| calculator.maverick_v1_out(amount, pool, true, *tick))
+        })
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| calculator.optimize_tick_limit_maverick(pool, amount, true, false))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_calculator, bench_maverick_tick_sweep);
 criterion_main!(benches);
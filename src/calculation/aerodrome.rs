@@ -1,5 +1,6 @@
 
 use crate::calculation::Calculator; // Fix: Import Calculator struct
+use crate::state_db::BlockStateDB;
 
 use alloy::network::Network;
 use alloy::primitives::Address;
@@ -17,6 +18,24 @@ pub static WETH: Lazy<Address> =
 pub static USDC: Lazy<Address> =
     Lazy::new(|| Address::from_str("0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913").unwrap());
 
+/// Newton's method settings for the stable-swap `_get_y` solver
+#[derive(Debug, Clone, Copy)]
+pub struct NewtonConfig {
+    /// Iterations before giving up and reporting non-convergence
+    pub max_iterations: u32,
+    /// Step size (in 1e18-scaled units) below which the solver switches to unit steps around the target
+    pub epsilon: U256,
+}
+
+impl Default for NewtonConfig {
+    fn default() -> Self {
+        Self {
+            max_iterations: 255,
+            epsilon: U256::from(1),
+        }
+    }
+}
+
 // --- Aerodrome V2State contract
 sol! {
     #[sol(rpc)]
@@ -39,20 +58,29 @@ where
     /// Calculates Aerodrome swap output amount.
     pub fn aerodrome_out(&self, amount_in: U256, token_in: Address, pool_address: Address) -> U256 {
         // Access the database via market_state field on Calculator
-        let db = self.market_state.db.read().expect("DB read poisoned");
+        let db = self.db_read();
+        self.aerodrome_out_with_db(&db, amount_in, token_in, pool_address)
+    }
 
+    /// `aerodrome_out` against an already locked DB
+    pub fn aerodrome_out_with_db(
+        &self,
+        db: &BlockStateDB<N, P>,
+        amount_in: U256,
+        token_in: Address,
+        pool_address: Address,
+    ) -> U256 {
         // Assuming these methods exist on your DB type within MarketState
         let (reserve0, reserve1) = db.get_reserves(&pool_address);
         let (dec0, dec1) = db.get_decimals(&pool_address);
-        let fee = db.get_fee(&pool_address); // Assuming fee is u32 or similar, needs compatible math below
+        let fee = db.get_fee(&pool_address); // Basis points, resolved from the factory at insertion
         let stable = db.get_stable(&pool_address);
         let token0 = db.get_token0(pool_address);
 
         let mut res0 = U256::from(reserve0);
         let mut res1 = U256::from(reserve1);
 
-        // Apply fee - Ensure fee is represented correctly (e.g., basis points)
-        // If fee is 1 = 0.01%, then divide by 10_000. Adjust if fee represents something else.
+        // Apply fee (basis points, 1 = 0.01%)
         let fee_amount = (amount_in * U256::from(fee) / U256::from(10_000));
         let amount_after_fee = amount_in.saturating_sub(fee_amount);
 
@@ -94,7 +122,10 @@ where
 
             let xy = Self::_k(scaled_res0, scaled_res1); // Use scaled reserves
             let y_in = scaled_res_a.saturating_add(scaled_amount_in);
-            let new_y = Self::_get_y(y_in, xy, scaled_res_b);
+            let Some(new_y) = Self::_get_y(y_in, xy, scaled_res_b, &self.newton_config) else {
+                warn!(?pool_address, %amount_in, "Aerodrome stable swap did not converge");
+                return U256::ZERO;
+            };
             let scaled_y = scaled_res_b.saturating_sub(new_y);
 
             // Scale output back to original token decimals
@@ -115,6 +146,73 @@ where
         }
     }
 
+    /// Inverse of `aerodrome_out`: the `amount_in` needed to receive `desired_out` of `token_out`.
+    /// Returns U256::MAX if the pool cannot provide `desired_out`.
+    pub fn aerodrome_in(&self, desired_out: U256, token_out: Address, pool_address: Address) -> U256 {
+        if desired_out.is_zero() {
+            return U256::ZERO;
+        }
+
+        let db = self.db_read();
+
+        let (reserve0, reserve1) = db.get_reserves(&pool_address);
+        let (dec0, dec1) = db.get_decimals(&pool_address);
+        let fee = db.get_fee(&pool_address);
+        let stable = db.get_stable(&pool_address);
+        let token0 = db.get_token0(pool_address);
+
+        // token_out == token0 means token1 is the input side
+        let out_is_token0 = token_out == token0;
+        let (res_in, res_out) = if out_is_token0 {
+            (U256::from(reserve1), U256::from(reserve0))
+        } else {
+            (U256::from(reserve0), U256::from(reserve1))
+        };
+
+        if desired_out >= res_out {
+            warn!(?pool_address, %desired_out, "Aerodrome desired output exceeds reserves");
+            return U256::MAX;
+        }
+
+        let amount_in_after_fee = if stable {
+            let scale_factor = U256::from(10).pow(U256::from(18));
+            let token0_decimals = U256::from(10).pow(U256::from(dec0));
+            let token1_decimals = U256::from(10).pow(U256::from(dec1));
+            let (in_decimals, out_decimals) = if out_is_token0 {
+                (token1_decimals, token0_decimals)
+            } else {
+                (token0_decimals, token1_decimals)
+            };
+
+            let scaled_res_in = res_in.saturating_mul(scale_factor) / in_decimals;
+            let scaled_res_out = res_out.saturating_mul(scale_factor) / out_decimals;
+            let scaled_out = desired_out.saturating_mul(scale_factor) / out_decimals;
+
+            // The invariant is symmetric in x and y, so _get_y also solves for the new input reserve
+            let xy = Self::_k(scaled_res_in, scaled_res_out);
+            let new_y = scaled_res_out.saturating_sub(scaled_out);
+            let Some(new_x) = Self::_get_y(new_y, xy, scaled_res_in, &self.newton_config) else {
+                warn!(?pool_address, %desired_out, "Aerodrome stable inverse did not converge");
+                return U256::MAX;
+            };
+            let scaled_in = new_x.saturating_sub(scaled_res_in);
+
+            // Round up when scaling back so the input always covers the target output
+            (scaled_in.saturating_mul(in_decimals) + scale_factor - U256::from(1)) / scale_factor
+        } else {
+            // Inverse of dy = (dx * R_out) / (R_in + dx), rounded up
+            (res_in * desired_out) / (res_out - desired_out) + U256::from(1)
+        };
+
+        // Gross up for the fee taken from the input
+        let fee = U256::from(fee);
+        let fee_scale = U256::from(10_000);
+        if fee >= fee_scale {
+            return U256::MAX;
+        }
+        (amount_in_after_fee * fee_scale + fee_scale - fee - U256::from(1)) / (fee_scale - fee)
+    }
+
     // Helper for stable k calculation (assumes inputs are scaled to 18 decimals)
     fn _k(x: U256, y: U256) -> U256 {
         let scale_factor = U256::from(10).pow(U256::from(18));
@@ -128,40 +226,41 @@ where
     }
 
     // Helper for stable get_y (Newton's method, assumes inputs scaled to 18 decimals)
-    fn _get_y(x0: U256, xy_k: U256, mut y: U256) -> U256 {
+    // Returns None if the iteration does not converge within `config.max_iterations`
+    fn _get_y(x0: U256, xy_k: U256, mut y: U256, config: &NewtonConfig) -> Option<U256> {
         let scale_factor = U256::from(10).pow(U256::from(18));
-        if scale_factor.is_zero() { return U256::ZERO; }
+        if scale_factor.is_zero() { return None; }
         let precision_one = U256::from(1);
 
-        for i in 0..255 {
+        for i in 0..config.max_iterations {
             let k_current = Self::_f(x0, y); // Current k based on x0 and y
             let d_val = Self::_d(x0, y);      // Derivative dK/dy
 
             if d_val.is_zero() {
                 // Should not happen with positive reserves
                 warn!(iteration = i, x0 = %x0, y = %y, "Aerodrome _get_y derivative is zero");
-                return y; // Return current y as best estimate
+                return None;
             }
 
             let diff = if k_current > xy_k { k_current.saturating_sub(xy_k) } else { xy_k.saturating_sub(k_current) };
             let dy = (diff.saturating_mul(scale_factor)) / d_val; // Calculate change in y
 
             // If dy is zero, check boundaries or return current y
-            if dy < precision_one {
+            if dy < config.epsilon {
                 // Check if further iteration might cross the target k
                 let next_y = if k_current < xy_k { y.saturating_add(precision_one) } else { y.saturating_sub(precision_one) };
                 if next_y.is_zero() && k_current >= xy_k { // Prevent underflow if already at target or above
-                    return y;
+                    return Some(y);
                 }
                 let k_next = Self::_f(x0, next_y);
                 if k_current < xy_k {
-                    if k_next >= xy_k { return next_y; } // Crossed target
+                    if k_next >= xy_k { return Some(next_y); } // Crossed target
                 } else {
-                    if k_next <= xy_k { return y; } // Crossed target or exactly hit
+                    if k_next <= xy_k { return Some(y); } // Crossed target or exactly hit
                 }
                 // If not crossed, dy=1 is the smallest step
                 if dy == U256::ZERO {
-                    if k_current == xy_k { return y; } // Already converged
+                    if k_current == xy_k { return Some(y); } // Already converged
                     // If not converged, make minimum step in the right direction
                     if k_current < xy_k { y = y.saturating_add(precision_one); } else { y = y.saturating_sub(precision_one); }
                 } else {
@@ -176,13 +275,12 @@ where
             if y.is_zero() && k_current < xy_k {
                 // Should not happen if reserve y > 0 initially unless amount_in is huge
                 warn!(iteration = i, x0 = %x0, "Aerodrome _get_y resulted in zero y prematurely");
-                return U256::ZERO; // Indicate pool drain or error
+                return None; // Indicate pool drain or error
             }
         }
         
-        warn!("Aerodrome _get_y did not converge after 255 iterations");
-        // Return the best estimate or potentially signal an error.
-        y
+        warn!(max_iterations = config.max_iterations, "Aerodrome _get_y did not converge");
+        None
     }
 
     // Helper for stable f(x, y) = xy(x^2+y^2) (assumes inputs scaled to 18 decimals)
@@ -282,3 +380,55 @@ pub fn ample_best_route<N: Network, P: Provider<N>>( // Renamed to avoid conflic
 //         println!("No route found!");
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::test_utils::{aerodrome_pool, insert_aerodrome_offline, market_state, offline_db};
+
+    const ONE: u128 = 1_000_000_000_000_000_000;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn aerodrome_in_inverts_aerodrome_out() {
+        let (token0, token1) = (Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let (volatile, stable) = (Address::repeat_byte(0x11), Address::repeat_byte(0x12));
+        let mut db = offline_db();
+        insert_aerodrome_offline(&mut db, aerodrome_pool(volatile, token0, token1, 1_000 * ONE, 2_000 * ONE, false), 30);
+        insert_aerodrome_offline(&mut db, aerodrome_pool(stable, token0, token1, 1_000 * ONE, 1_200 * ONE, true), 5);
+        let calculator = Calculator::new(market_state(db));
+
+        for pool in [volatile, stable] {
+            for (token_in, token_out) in [(token0, token1), (token1, token0)] {
+                let amount_in = U256::from(3 * ONE);
+                let out = calculator.aerodrome_out(amount_in, token_in, pool);
+                assert!(!out.is_zero());
+                let back = calculator.aerodrome_in(out, token_out, pool);
+                // Both directions round in the pool's favor, within a basis point of the original input
+                assert!(
+                    back.abs_diff(amount_in) <= amount_in / U256::from(10_000),
+                    "pool {pool}: {amount_in} in, {out} out, {back} back"
+                );
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn non_converging_stable_swaps_quote_zero() {
+        type Calc = Calculator<alloy::network::Ethereum, alloy::providers::RootProvider>;
+        let one = U256::from(ONE);
+        let (reserve_in, reserve_out) = (U256::from(1_000) * one, U256::from(1_000) * one);
+        let k = Calc::_k(reserve_in, reserve_out);
+        // A trade larger than the pool moves y far from its starting guess, one step can't land on k
+        let x0 = reserve_in + U256::from(5_000) * one;
+        let single_step = NewtonConfig { max_iterations: 1, ..NewtonConfig::default() };
+        assert_eq!(Calc::_get_y(x0, k, reserve_out, &single_step), None);
+        assert!(Calc::_get_y(x0, k, reserve_out, &NewtonConfig::default()).is_some());
+
+        let (pool, token0, token1) = (Address::repeat_byte(0x12), Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let mut db = offline_db();
+        insert_aerodrome_offline(&mut db, aerodrome_pool(pool, token0, token1, 1_000 * ONE, 1_000 * ONE, true), 5);
+        let mut calculator = Calculator::new(market_state(db));
+        calculator.newton_config = single_step;
+        assert_eq!(calculator.aerodrome_out(U256::from(5_000) * one, token0, pool), U256::ZERO);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::calculation::Calculator; // Fix: Import Calculator
+use crate::state_db::BlockStateDB;
+use crate::state_db::balancer_db::{downscale, upscale};
 
 use alloy::network::Network;
 use alloy::primitives::{Address, U256};
@@ -21,14 +23,25 @@ where
         pool_address: Address,
     ) -> U256 {
         // Access the database via market_state field on Calculator
-        let db = self.market_state.db.read().expect("DB read poisoned");
+        let db = self.db_read();
+        Self::balancer_v2_out_with_db(&db, amount_in, token_in, token_out, pool_address)
+    }
 
+    /// `balancer_v2_out` against an already locked DB
+    pub fn balancer_v2_out_with_db(
+        db: &BlockStateDB<N, P>,
+        amount_in: U256,
+        token_in: Address,
+        token_out: Address,
+        pool_address: Address,
+    ) -> U256 {
         // Fetch Balancer pool details from the DB
         // NOTE: Replace these with your actual DB methods for Balancer pools
         let balances = db.get_balancer_balances(&pool_address); // e.g., returns Vec<U256>
         let weights = db.get_balancer_weights(&pool_address);   // e.g., returns Vec<U256> (scaled)
         let swap_fee = db.get_balancer_fee(&pool_address);     // e.g., returns U256 (scaled, e.g., 1e15 for 0.1%)
         let tokens = db.get_balancer_tokens(&pool_address);     // e.g., returns Vec<Address>
+        let decimals = db.get_balancer_decimals(&pool_address);
 
         // Find indices for token_in and token_out
         let token_in_index = tokens.iter().position(|&t| t == token_in).expect("Token in not found in Balancer pool");
@@ -38,6 +51,12 @@ where
         // https://docs.balancer.fi/concepts/math/weighted-math.html#swap-calculation
         // amountOut = balanceOut * (1 - (balanceIn / (balanceIn + amountIn)) ^ (weightIn / weightOut))
 
+        // Balances are stored upscaled to 18 decimals, the input has to match before it meets them
+        let (Some(&decimals_in), Some(&decimals_out)) = (decimals.get(token_in_index), decimals.get(token_out_index)) else {
+            return U256::ZERO;
+        };
+        let amount_in = upscale(amount_in, decimals_in);
+
         let balance_in = balances[token_in_index];
         let balance_out = balances[token_out_index];
         // Weights are typically normalized (sum to 1) or need scaling factor (e.g., 1e18)
@@ -71,7 +90,7 @@ where
         let factor = Self::complement_balancer(power); // complement assumes scaling
         let amount_out = Self::mul_down_balancer(balance_out, factor); // mul_down assumes scaling
 
-        amount_out
+        downscale(amount_out, decimals_out)
     }
 
     // ---------- Math Helpers ----------
@@ -130,4 +149,42 @@ where
         let one = U256::from(1_000_000_000_000_000_000u64);
         if x < one { one - x } else { U256::ZERO }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_db::balancer_db::BalancerPoolState;
+    use crate::utile::test_utils::{market_state, offline_db};
+
+    // 50/50 USDC/WETH at 2000 USDC per WETH, fee-free so the output has a closed form:
+    // balance_out * (1 - balance_in / (balance_in + amount_in))
+    #[tokio::test(flavor = "multi_thread")]
+    async fn six_and_eighteen_decimal_tokens_quote_in_their_own_units() {
+        let (pool, usdc, weth) = (Address::repeat_byte(0x61), Address::repeat_byte(0x06), Address::repeat_byte(0x18));
+        let one = U256::from(10u64).pow(U256::from(18));
+        let half = one / U256::from(2);
+        let mut db = offline_db();
+        db.balancer_pools.insert(pool, BalancerPoolState {
+            tokens: vec![usdc, weth],
+            decimals: vec![6, 18],
+            balances: vec![U256::from(2_000_000) * one, U256::from(1_000) * one],
+            weights: vec![half, half],
+            ..Default::default()
+        });
+        let calculator = Calculator::new(market_state(db));
+        let within = |actual: U256, expected: f64| {
+            let actual = actual.to::<u128>() as f64;
+            (actual - expected).abs() / expected < 1e-6
+        };
+
+        // 1000 USDC buys just under 0.5 WETH
+        let weth_out = calculator.balancer_v2_out(U256::from(1_000_000_000u64), usdc, weth, pool);
+        let expected = 1_000.0 * (1.0 - 2_000_000.0 / 2_001_000.0) * 1e18;
+        assert!(within(weth_out, expected), "{weth_out} WETH wei, expected {expected}");
+
+        // 0.5 WETH buys just under 1000 USDC, in 6 decimals
+        let usdc_out = calculator.balancer_v2_out(half, weth, usdc, pool);
+        let expected = 2_000_000.0 * (1.0 - 1_000.0 / 1_000.5) * 1e6;
+        assert!(within(usdc_out, expected), "{usdc_out} USDC units, expected {expected}");
+    }
+}
@@ -1,15 +1,35 @@
 // These imports pull in the modules where the respective impl blocks are defined.
-use crate::calculation::aerodrome;
+use crate::calculation::aerodrome::{self, NewtonConfig};
+use crate::calculation::curve::CurveSimConfig;
 use crate::calculation::balancer;
-use crate::calculation::uniswap;
-use crate::utile::{AMOUNT, Cache, MarketState, SwapPath}; // Assuming SwapPath is defined here
+use crate::calculation::registry::{self, CalcContext, CalculatorRegistry, PoolCalculator};
+use crate::state_db::BlockStateDB;
+use crate::utile::swap::SwapStep;
+use crate::utile::{Cache, MarketState, SwapPath, TickCache, amount_for}; // Assuming SwapPath is defined here
+use crate::utile::market_state::WarmUpConfig;
+use crate::utile::quoter::Quoter;
+use crate::utile::rgen::FlashQuoter;
 
 use alloy::network::Network;
 use alloy::primitives::{Address, U256};
 use alloy::providers::Provider;
 use pool_sync::PoolType; // Assuming PoolType comes from here
-use std::collections::HashSet;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLockReadGuard};
+
+// Pools with less effective liquidity than this are treated as drained. Off by default, a fixed floor in raw
+// units means different things for 6 and 18 decimal tokens; opt in with `with_min_liquidity`.
+// V2 pools permanently lock 1000 units so a fully drained pool sits right at that level
+pub const DEFAULT_MIN_LIQUIDITY: u64 = 0;
+
+// Divergence between the off-chain and EVM quote of a path, in basis points, above which `validate_path` warns
+pub const DEFAULT_VALIDATION_THRESHOLD_BPS: u64 = 50;
+
+// Quote cache inputs are exact unless QUOTE_CACHE_STEP sets a bucket width in raw token units
+const DEFAULT_QUOTE_CACHE_STEP: u64 = 0;
+
+// Number of equal chunks `split_route` hands out across parallel pools
+const SPLIT_CHUNKS: u64 = 100;
 
 /// The main struct for performing swap calculations across different DEX protocols.
 pub struct Calculator<N, P>
@@ -21,6 +41,43 @@ where
     pub market_state: Arc<MarketState<N, P>>,
     /// Cache for potentially expensive calculations (e.g., Uniswap V3 ticks).
     pub cache: Arc<Cache>,
+    /// Memoized V3 tick bitmaps and liquidity_net values, invalidated alongside `cache`.
+    pub tick_cache: Arc<TickCache>,
+    /// Iteration limits for the Aerodrome stable-swap solver.
+    pub newton_config: NewtonConfig,
+    /// Caller and gas limit used for Curve quote simulations.
+    pub curve_sim_config: CurveSimConfig,
+    /// Per-token `balances` slots discovered when funding exchange simulations.
+    pub balance_slots: Mutex<WarmUpConfig>,
+    /// Profitability gate for `find_best_route`.
+    pub route_config: RouteConfig,
+    /// Effective liquidity below which `compute_amount_out` quotes zero.
+    pub min_liquidity: U256,
+    /// Per-protocol quoting logic `compute_amount_out` dispatches through.
+    pub pool_calculators: CalculatorRegistry<N, P>,
+    /// Divergence in basis points above which `validate_path` warns.
+    pub validation_threshold_bps: u64,
+    /// Pool types already reported as lacking a calculator.
+    unregistered_warned: Mutex<HashSet<PoolType>>,
+}
+
+/// Off-chain and FlashQuoter outputs of the same path for the same input
+#[derive(Debug, Clone)]
+pub struct PathValidation {
+    pub input: U256,
+    /// Output of `compute_path_output`
+    pub calculated: U256,
+    /// Output of the FlashQuoter simulation, None if it failed
+    pub simulated: Option<U256>,
+    /// |calculated - simulated| relative to simulated, in basis points. None without a simulated output
+    pub divergence_bps: Option<u64>,
+}
+
+/// Settings for `find_best_route`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RouteConfig {
+    /// Margin over the input amount, in basis points, a route must exceed to be returned
+    pub min_margin_bps: u32,
 }
 
 // Core implementation block for Calculator
@@ -31,16 +88,73 @@ where
 {
     /// Creates a new Calculator instance.
     pub fn new(market_state: Arc<MarketState<N, P>>) -> Self {
+        let curve_sim_config = CurveSimConfig::default();
+        let balance_slots = Mutex::new(WarmUpConfig {
+            account: curve_sim_config.caller,
+            sim_config: market_state.sim_config,
+            ..Default::default()
+        });
+        let cache_step = std::env::var("QUOTE_CACHE_STEP")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(U256::from(DEFAULT_QUOTE_CACHE_STEP));
         Self {
             market_state,
-            cache: Arc::new(Cache::new(500)), // Default cache size
+            cache: Arc::new(Cache::new(500).with_quantization(cache_step)), // Default cache size
+            tick_cache: Arc::new(TickCache::new()),
+            newton_config: NewtonConfig::default(),
+            curve_sim_config,
+            balance_slots,
+            route_config: RouteConfig::default(),
+            min_liquidity: U256::from(DEFAULT_MIN_LIQUIDITY),
+            pool_calculators: registry::default_registry(),
+            validation_threshold_bps: DEFAULT_VALIDATION_THRESHOLD_BPS,
+            unregistered_warned: Mutex::new(HashSet::new()),
         }
     }
 
+    /// Registers `calculator` for `pool_type`, replacing any built-in one.
+    pub fn register_calculator(&mut self, pool_type: PoolType, calculator: Box<dyn PoolCalculator<N, P>>) {
+        self.pool_calculators.insert(pool_type, calculator);
+    }
+
+    /// Builder form of `register_calculator`.
+    pub fn with_calculator(mut self, pool_type: PoolType, calculator: Box<dyn PoolCalculator<N, P>>) -> Self {
+        self.register_calculator(pool_type, calculator);
+        self
+    }
+
+    /// Sets the liquidity floor: sqrt(reserve0 * reserve1) for V2-style pools,
+    /// `liquidity` for V3 pools, and the smallest token balance for Balancer pools.
+    pub fn with_min_liquidity(mut self, min_liquidity: U256) -> Self {
+        self.min_liquidity = min_liquidity;
+        self
+    }
+
+    /// Replaces the quote cache with one bucketing inputs to multiples of `step`, see `Cache::with_quantization`.
+    pub fn with_cache_quantization(mut self, step: U256) -> Self {
+        self.cache = Arc::new(Cache::new(500).with_quantization(step));
+        self
+    }
+
+    /// Sets the divergence `validate_path` tolerates before warning.
+    pub fn with_validation_threshold(mut self, threshold_bps: u64) -> Self {
+        self.validation_threshold_bps = threshold_bps;
+        self
+    }
+
+    /// Read lock on the state DB, recovering the guard if a writer panicked.
+    /// The DB is only ever appended to or overwritten slot by slot, so a poisoned lock still holds usable state.
+    /// Take one guard per call and pass `&BlockStateDB` down, see `MarketState::db` for the lock ordering.
+    pub fn db_read(&self) -> RwLockReadGuard<'_, BlockStateDB<N, P>> {
+        self.market_state.db_read()
+    }
+
     /// Invalidates cache entries for specific pool addresses.
     pub fn invalidate_cache(&self, pools: &HashSet<Address>) {
+        self.cache.invalidate_many(pools);
         for pool in pools {
-            self.cache.invalidate(*pool);
+            self.tick_cache.invalidate(*pool);
         }
     }
 
@@ -60,18 +174,16 @@ where
 
     /// Traces the amount changes along a multi-step swap path for debugging.
     pub fn debug_calculation(&self, path: &SwapPath) -> Vec<U256> {
-        // Assuming AMOUNT is a global or configured initial amount for debugging
-        let mut amount = *AMOUNT.read().unwrap();
+        // Start from the configured input size for the path's first token
+        let Some(first_step) = path.steps.first() else {
+            return Vec::new();
+        };
+        let mut amount = amount_for(first_step.token_in);
         let mut path_trace = vec![amount];
 
+        let db = self.db_read();
         for swap_step in &path.steps {
-            let output_amount = self.compute_amount_out(
-                amount,
-                swap_step.pool_address,
-                swap_step.token_in,
-                swap_step.protocol,
-                swap_step.fee,
-            );
+            let output_amount = self.compute_step_output_with_db(&db, amount, swap_step);
             path_trace.push(output_amount);
             amount = output_amount; // Update amount for the next step
             if amount.is_zero() { // Stop early if amount becomes zero
@@ -82,6 +194,78 @@ where
         path_trace
     }
 
+    /// Final output of swapping `input` along every step of `path`, reading all pools under one DB lock.
+    /// Returns zero as soon as any step does.
+    pub fn compute_path_output(&self, path: &SwapPath, input: U256) -> U256 {
+        let db = self.db_read();
+        let mut amount = input;
+        for swap_step in &path.steps {
+            amount = self.compute_step_output_with_db(&db, amount, swap_step);
+            if amount.is_zero() {
+                break;
+            }
+        }
+        amount
+    }
+
+    /// Output of one path step, unlike `compute_amount_out_with_db` the calculator also sees the step's `token_out`.
+    /// Multi-coin pools (Curve) need it to pick the output coin.
+    pub fn compute_step_output_with_db(&self, db: &BlockStateDB<N, P>, input: U256, step: &SwapStep) -> U256 {
+        self.quote_with_db(
+            db,
+            input,
+            step.pool_address,
+            step.token_in,
+            Some(step.token_out),
+            step.protocol,
+            step.fee,
+        )
+    }
+
+    /// Quotes `path` with both the off-chain math and the FlashQuoter bytecode at the configured input size.
+    /// A divergence above `validation_threshold_bps` usually means a fee or direction bug in one of the path's pool types.
+    pub fn validate_path(&self, path: &SwapPath) -> PathValidation {
+        let input = path
+            .steps
+            .first()
+            .map(|step| amount_for(step.token_in))
+            .unwrap_or_default();
+        let calculated = self.compute_path_output(path, input);
+
+        let quoted = FlashQuoter::SwapParams::try_from(path.clone())
+            .and_then(|params| Quoter::quote_path(params, Arc::clone(&self.market_state)));
+        let simulated = match quoted {
+            Ok(amounts) => amounts.last().copied(),
+            Err(e) => {
+                tracing::warn!("Quoter failed while validating path {}: {:?}", path.hash, e);
+                None
+            }
+        };
+
+        let divergence_bps = simulated.map(|simulated| {
+            let diff = calculated.abs_diff(simulated);
+            if simulated.is_zero() {
+                return if diff.is_zero() { 0 } else { u64::MAX };
+            }
+            (diff * U256::from(10_000) / simulated).saturating_to::<u64>()
+        });
+
+        if let Some(divergence) = divergence_bps.filter(|bps| *bps > self.validation_threshold_bps) {
+            let protocols: Vec<PoolType> = path.steps.iter().map(|step| step.protocol).collect();
+            tracing::warn!(
+                "Path {} diverges by {} bps: calculated {}, simulated {:?} for input {} over {:?}",
+                path.hash, divergence, calculated, simulated, input, protocols
+            );
+        }
+
+        PathValidation {
+            input,
+            calculated,
+            simulated,
+            divergence_bps,
+        }
+    }
+
     /// The core dispatch function that calculates swap output based on pool type.
     pub fn compute_amount_out(
         &self,
@@ -91,91 +275,200 @@ where
         pool_type: PoolType,
         fee: u32, // Represents V3 fee tier or is ignored by V2/other types
     ) -> U256 {
-        // Use cached result if available and valid
-        // TODO: Implement caching logic using self.cache if needed
+        let db = self.db_read();
+        self.compute_amount_out_with_db(&db, input_amount, pool_address, token_in, pool_type, fee)
+    }
+
+    /// `compute_amount_out` against an already locked DB, so a caller can quote many steps under one lock.
+    pub fn compute_amount_out_with_db(
+        &self,
+        db: &BlockStateDB<N, P>,
+        input_amount: U256,
+        pool_address: Address,
+        token_in: Address,
+        pool_type: PoolType,
+        fee: u32,
+    ) -> U256 {
+        self.quote_with_db(db, input_amount, pool_address, token_in, None, pool_type, fee)
+    }
+
+    fn quote_with_db(
+        &self,
+        db: &BlockStateDB<N, P>,
+        input_amount: U256,
+        pool_address: Address,
+        token_in: Address,
+        token_out: Option<Address>,
+        pool_type: PoolType,
+        fee: u32,
+    ) -> U256 {
+        // Entries of a pool are dropped through `invalidate_cache` whenever its state changes
+        self.cache.get_or_compute(input_amount, pool_address, token_in, token_out, || {
+            self.quote_uncached_with_db(db, input_amount, pool_address, token_in, token_out, pool_type, fee)
+        })
+    }
+
+    fn quote_uncached_with_db(
+        &self,
+        db: &BlockStateDB<N, P>,
+        input_amount: U256,
+        pool_address: Address,
+        token_in: Address,
+        token_out: Option<Address>,
+        pool_type: PoolType,
+        fee: u32,
+    ) -> U256 {
+        // Drained pools quote dust, don't let them produce phantom paths
+        if self.below_min_liquidity(db, pool_address, pool_type) {
+            return U256::ZERO;
+        }
+
+        let Some(pool_calculator) = self.pool_calculators.get(&pool_type) else {
+            // Quoted on every hop through such a pool, reported once per type
+            if self.unregistered_warned.lock().unwrap_or_else(|p| p.into_inner()).insert(pool_type) {
+                tracing::warn!(?pool_address, ?pool_type, "No calculator registered for pool type, quoting zero");
+            }
+            tracing::debug!(?pool_address, ?pool_type, "No calculator registered for pool type");
+            return U256::ZERO;
+        };
+        let ctx = CalcContext {
+            calculator: self,
+            db,
+            pool_address,
+            pool_type,
+            token_out,
+        };
+        pool_calculator.amount_out(&ctx, input_amount, token_in, fee)
+    }
+
+    /// Whether the pool's effective liquidity is under `min_liquidity`.
+    /// Pool types without a cheap liquidity measure are never gated.
+    fn below_min_liquidity(&self, db: &BlockStateDB<N, P>, pool_address: Address, pool_type: PoolType) -> bool {
+        if self.min_liquidity.is_zero() {
+            return false;
+        }
 
         match pool_type {
-            // --- Uniswap V2 & Clones ---
-            PoolType::UniswapV2 | PoolType::SushiSwapV2 | PoolType::SwapBasedV2 => {
-                // V2 fee is typically fixed (0.3% -> 9970 multiplier)
-                self.uniswap_v2_out(
-                    input_amount,
-                    &pool_address,
-                    &token_in,
-                    U256::from(9970), // Represents 1 - 0.0030
-                )
+            PoolType::UniswapV2
+            | PoolType::SushiSwapV2
+            | PoolType::SwapBasedV2
+            | PoolType::PancakeSwapV2
+            | PoolType::BaseSwapV2
+            | PoolType::DackieSwapV2
+            | PoolType::AlienBaseV2
+            | PoolType::Aerodrome => {
+                let (reserve0, reserve1) = db.get_reserves(&pool_address);
+                U256::from(reserve0).saturating_mul(U256::from(reserve1)).root(2) < self.min_liquidity
             }
-            PoolType::PancakeSwapV2 | PoolType::BaseSwapV2 | PoolType::DackieSwapV2 => {
-                 // Pancake etc. often use 0.25% -> 9975 multiplier
-                self.uniswap_v2_out(
-                    input_amount,
-                    &pool_address,
-                    &token_in,
-                    U256::from(9975), // Represents 1 - 0.0025
-                )
+
+            PoolType::UniswapV3
+            | PoolType::SushiSwapV3
+            | PoolType::BaseSwapV3
+            | PoolType::Slipstream
+            | PoolType::PancakeSwapV3
+            | PoolType::AlienBaseV3
+            | PoolType::SwapBasedV3
+            | PoolType::DackieSwapV3 => db
+                .liquidity(pool_address)
+                .is_ok_and(|liquidity| U256::from(liquidity) < self.min_liquidity),
+
+            PoolType::BalancerV2 => db
+                .get_balancer_balances(&pool_address)
+                .iter()
+                .any(|balance| *balance < self.min_liquidity),
+
+            _ => false,
+        }
+    }
+
+    /// Marginal price of `token_in` in units of the other token before any trade, ignoring fees.
+    /// Returns 0.0 for pool types without a closed-form price.
+    pub fn spot_price(
+        &self,
+        pool_address: Address,
+        token_in: Address,
+        pool_type: PoolType,
+        _fee: u32,
+    ) -> f64 {
+        let db_read = self.db_read();
+        let Some(zero_to_one) = db_read.zero_to_one(&pool_address, token_in) else {
+            return 0.0;
+        };
+
+        match pool_type {
+            PoolType::UniswapV2
+            | PoolType::SushiSwapV2
+            | PoolType::SwapBasedV2
+            | PoolType::PancakeSwapV2
+            | PoolType::BaseSwapV2
+            | PoolType::DackieSwapV2
+            | PoolType::AlienBaseV2 => {
+                let (reserve0, reserve1) = db_read.get_reserves(&pool_address);
+                reserve_ratio(reserve0, reserve1, zero_to_one)
             }
-             PoolType::AlienBaseV2 => {
-                 // Alien Base 0.16%? -> 9984 multiplier
-                self.uniswap_v2_out(
-                    input_amount,
-                    &pool_address,
-                    &token_in,
-                    U256::from(9984), // Represents 1 - 0.0016
-                )
-             }
-
-            // --- Uniswap V3 & Clones ---
+
             PoolType::UniswapV3
             | PoolType::SushiSwapV3
             | PoolType::BaseSwapV3
-            | PoolType::Slipstream // Assuming Slipstream behaves like V3 for quotes
+            | PoolType::Slipstream
             | PoolType::PancakeSwapV3
             | PoolType::AlienBaseV3
             | PoolType::SwapBasedV3
             | PoolType::DackieSwapV3 => {
-                // V3 fee is passed directly (e.g., 500, 3000, 10000)
-                // The uniswap_v3_out method should handle potential errors internally or return Result
-                self.uniswap_v3_out(input_amount, &pool_address, &token_in, fee)
-                    .unwrap_or(U256::ZERO) // Handle potential error from V3 calc
+                let Ok(slot0) = db_read.slot0(pool_address) else {
+                    return 0.0;
+                };
+                sqrt_price_ratio(U256::from(slot0.sqrt_price_x96), zero_to_one)
             }
 
-            // --- Aerodrome (Velodrome Fork) ---
             PoolType::Aerodrome => {
-                // Fee is fetched internally in aerodrome_out based on pool properties
-                self.aerodrome_out(input_amount, token_in, pool_address)
-            }
+                let (reserve0, reserve1) = db_read.get_reserves(&pool_address);
+                if !db_read.get_stable(&pool_address) {
+                    return reserve_ratio(reserve0, reserve1, zero_to_one);
+                }
 
-            // --- Balancer V2 ---
-            PoolType::BalancerV2 => {
-                 // Requires token_out to find weights/balances. Need to fetch it.
-                 // This assumes a simple 2-token pool for now. Multi-token needs more info.
-                 let db_read = self.market_state.db.read().unwrap();
-                 let token0 = db_read.get_token0(pool_address); // Assuming method exists
-                 let token1 = db_read.get_token1(pool_address); // Assuming method exists
-                 let token_out = if token_in == token0 { token1 } else { token0 };
-                 self.balancer_v2_out(input_amount, token_in, token_out, pool_address)
+                // Stable invariant x^3y + y^3x, marginal price in decimal-normalized units
+                let (dec0, dec1) = db_read.get_decimals(&pool_address);
+                let x = u256_to_f64(reserve0) / 10f64.powi(dec0 as i32);
+                let y = u256_to_f64(reserve1) / 10f64.powi(dec1 as i32);
+                let (x, y, dec_in, dec_out) = if zero_to_one {
+                    (x, y, dec0, dec1)
+                } else {
+                    (y, x, dec1, dec0)
+                };
+                let denominator = x * x * x + 3.0 * y * y * x;
+                if denominator == 0.0 {
+                    return 0.0;
+                }
+                let normalized = (3.0 * x * x * y + y * y * y) / denominator;
+                normalized * 10f64.powi(dec_out as i32 - dec_in as i32)
             }
 
-            // --- Maverick ---
-            // TODO: Implement Maverick logic if needed
-            PoolType::MaverickV1 | PoolType::MaverickV2 => {
-                tracing::warn!(?pool_address, "Maverick pool logic not implemented in compute_amount_out");
-                U256::ZERO // Placeholder
-                // self.maverick_v2_out(amount_in, pool_address, zero_for_one, tick_limit) ?
-                // Needs more parameters (zero_for_one, tick_limit) - requires path context.
+            _ => {
+                tracing::warn!(?pool_address, ?pool_type, "Spot price not supported for pool type");
+                0.0
             }
+        }
+    }
 
-            // --- Curve ---
-             // TODO: Implement Curve logic if needed
-            PoolType::CurveTwoCrypto | PoolType::CurveTriCrypto => {
-                tracing::warn!(?pool_address, "Curve pool logic not implemented in compute_amount_out");
-                U256::ZERO // Placeholder
-                 // self.curve_out(index_in, index_out, amount_in, pool) ?
-                 // Needs more parameters (indices) - requires path context.
-            }
-            // Add other pool types if necessary
+    /// Fraction of value lost versus the spot price when trading `input_amount`,
+    /// i.e. `1 - (output / input) / spot_price`. Includes the pool fee.
+    pub fn price_impact(
+        &self,
+        input_amount: U256,
+        pool_address: Address,
+        token_in: Address,
+        pool_type: PoolType,
+        fee: u32,
+    ) -> f64 {
+        let spot = self.spot_price(pool_address, token_in, pool_type, fee);
+        if spot <= 0.0 || input_amount.is_zero() {
+            return 0.0;
         }
-        // TODO: Store result in cache if implemented
+
+        let output = self.compute_amount_out(input_amount, pool_address, token_in, pool_type, fee);
+        let execution_price = u256_to_f64(output) / u256_to_f64(input_amount);
+        1.0 - execution_price / spot
     }
 
     /// Simulates the profit/loss of executing a sequence of trades (e.g., a bundle).
@@ -195,7 +488,7 @@ where
         for trade in bundle {
             // Need token_out for the current trade step.
             // This requires knowing the pool's other token.
-            let db_read = self.market_state.db.read().unwrap();
+            let db_read = self.db_read();
             let token0 = db_read.get_token0(trade.pool_address); // Assuming method exists
             let token1 = db_read.get_token1(trade.pool_address); // Assuming method exists
             let step_token_out = if current_token == token0 { token1 } else { token0 };
@@ -246,77 +539,101 @@ where
         token_out: Address, // Target token
         max_hops: u8,
     ) -> Option<(Vec<Trade>, U256)> { // Return path and amount_out
-        // Basic BFS state: (current_token, current_amount, path_so_far)
-        let mut queue = std::collections::VecDeque::new();
-        queue.push_back((token_in, initial_amt, Vec::<Trade>::new()));
-
-        let mut best_route = Vec::new();
-        let mut best_amount_out = U256::ZERO; // Track the best amount achieved *at the target token*
-
-        let mut visited_states = HashSet::new(); // Prevent cycles (token, hop_count)
-
-        while let Some((current_token, current_amount, current_path)) = queue.pop_front() {
-            let current_hop = current_path.len() as u8;
-
-            // Pruning: Check if we already found a better path to this state
-             if visited_states.contains(&(current_token, current_hop)) { // Basic cycle/redundancy check
-                 continue;
-             }
-             visited_states.insert((current_token, current_hop));
-
-
-            // Check if we reached the target token
-            if current_token == token_out {
-                if current_amount > best_amount_out {
-                    best_amount_out = current_amount;
-                    best_route = current_path.clone();
-                    // Continue searching, maybe a longer path yields more?
-                }
-            }
-
-            // If max hops reached, don't explore further from here
-            if current_hop >= max_hops {
-                continue;
-            }
-
-            // Explore next possible swaps
-            // Need a way to get potential pools/tokens reachable from current_token
-            // Assuming get_pools finds pools involving current_token
-            let potential_pools = self.get_pools_for_token(current_token); // Needs implementation
-
-            for pool in potential_pools {
-                 // Determine the token_out for this specific pool hop
-                let next_token = if pool.token0 == current_token { pool.token1 } else { pool.token0 };
-
-                // Simulate this hop
-                let output_amount = self.simulate_trade(
-                    current_amount,
-                    current_token,
-                    next_token,
-                    pool.address,
-                    pool.pool_type,
-                    U256::from(pool.fee), // Assuming Pool struct now has fee
-                );
-
-                if output_amount > U256::ZERO { // Only proceed if swap is possible
-                    let mut next_path = current_path.clone();
-                    next_path.push(Trade {
+        let (best_route, best_amount_out) = best_route_search(initial_amt, token_in, token_out, max_hops, |token, amount| {
+            self.get_pools_for_token(token)
+                .into_iter()
+                .map(|pool| {
+                    // Determine the token_out for this specific pool hop
+                    let next_token = if pool.token0 == token { pool.token1 } else { pool.token0 };
+                    let output_amount = self.simulate_trade(
+                        amount,
+                        token,
+                        next_token,
+                        pool.address,
+                        pool.pool_type,
+                        U256::from(pool.fee), // Assuming Pool struct now has fee
+                    );
+                    let trade = Trade {
                         pool_address: pool.address,
                         pool_type: pool.pool_type,
                         // Include token_in/out/fee if needed for later execution
-                    });
-                    queue.push_back((next_token, output_amount, next_path));
-                }
-            }
-        }
-
-         if best_amount_out > initial_amt { // Only return profitable routes
+                    };
+                    (next_token, trade, output_amount)
+                })
+                .collect()
+        });
+
+         // Only return routes that clear the configured margin over the input
+         let min_out = initial_amt
+             + initial_amt * U256::from(self.route_config.min_margin_bps) / U256::from(10_000);
+         if best_amount_out > min_out {
             Some((best_route, best_amount_out))
          } else {
             None
          }
     }
 
+    /// Allocates `input` of `token_in` across parallel pools for the same pair to maximize total output.
+    /// Water-fills in equal chunks, each going to the pool with the highest marginal output, which
+    /// converges on equal marginal output across pools for concave output curves.
+    /// Returns the per-pool allocation, pools that received nothing are omitted.
+    pub fn split_route(&self, input: U256, token_in: Address, pools: &[Pool]) -> Vec<(Address, U256)> {
+        if pools.is_empty() || input.is_zero() {
+            return Vec::new();
+        }
+
+        let chunk = input / U256::from(SPLIT_CHUNKS);
+        if chunk.is_zero() || pools.len() == 1 {
+            // Too small to split meaningfully, quote each pool with everything and keep the best
+            let db = self.db_read();
+            let best = pools.iter().max_by_key(|pool| {
+                self.compute_amount_out_with_db(&db, input, pool.address, token_in, pool.pool_type, pool.fee)
+            });
+            return best.map(|pool| vec![(pool.address, input)]).unwrap_or_default();
+        }
+
+        let db = self.db_read();
+        let quote = |pool: &Pool, amount: U256| {
+            self.compute_amount_out_with_db(&db, amount, pool.address, token_in, pool.pool_type, pool.fee)
+        };
+
+        let mut allocations = vec![U256::ZERO; pools.len()];
+        let mut outputs = vec![U256::ZERO; pools.len()];
+        let mut last_filled = None;
+        for _ in 0..SPLIT_CHUNKS {
+            let best = pools
+                .iter()
+                .enumerate()
+                .map(|(i, pool)| {
+                    let out = quote(pool, allocations[i] + chunk);
+                    (i, out, out.saturating_sub(outputs[i]))
+                })
+                .max_by_key(|(_, _, gain)| *gain);
+
+            let Some((i, out, gain)) = best else { break };
+            if gain.is_zero() {
+                break;
+            }
+            allocations[i] += chunk;
+            outputs[i] = out;
+            last_filled = Some(i);
+        }
+
+        // Rounding dust, and any chunks no pool had output left for, go to the pool that took the last chunk
+        let Some(last) = last_filled else {
+            return Vec::new();
+        };
+        let allocated = allocations.iter().fold(U256::ZERO, |acc, amount| acc + *amount);
+        allocations[last] += input.saturating_sub(allocated);
+
+        pools
+            .iter()
+            .zip(allocations)
+            .filter(|(_, amount)| !amount.is_zero())
+            .map(|(pool, amount)| (pool.address, amount))
+            .collect()
+    }
+
     /// Helper to get potential pools involving a specific token.
     /// Needs access to pool data (e.g., from MarketState or PoolSync).
     fn get_pools_for_token(&self, token: Address) -> Vec<Pool> {
@@ -366,6 +683,103 @@ where
     }
 }
 
+/// Lossy conversion used for price math
+fn u256_to_f64(value: U256) -> f64 {
+    value
+        .as_limbs()
+        .iter()
+        .enumerate()
+        .map(|(i, limb)| *limb as f64 * 2f64.powi(64 * i as i32))
+        .sum()
+}
+
+/// Price of token_in in token_out for concentrated liquidity pools, token0 in token1 being `(sqrtPriceX96 / 2^96)^2`
+fn sqrt_price_ratio(sqrt_price_x96: U256, zero_to_one: bool) -> f64 {
+    let sqrt_price = u256_to_f64(sqrt_price_x96) / 2f64.powi(96);
+    let price = sqrt_price * sqrt_price;
+    if zero_to_one {
+        price
+    } else if price > 0.0 {
+        1.0 / price
+    } else {
+        0.0
+    }
+}
+
+/// reserve_out / reserve_in for constant-product pools
+fn reserve_ratio(reserve0: U256, reserve1: U256, zero_to_one: bool) -> f64 {
+    let (reserve_in, reserve_out) = if zero_to_one {
+        (reserve0, reserve1)
+    } else {
+        (reserve1, reserve0)
+    };
+    if reserve_in.is_zero() {
+        return 0.0;
+    }
+    u256_to_f64(reserve_out) / u256_to_f64(reserve_in)
+}
+
+/// BFS behind `Calculator::find_best_route`, `next_hops(token, amount)` listing the `(next_token, trade, output)`
+/// reachable from `token` holding `amount`. Returns the best route into `token_out` and its output, empty if none
+fn best_route_search(
+    initial_amt: U256,
+    token_in: Address,
+    token_out: Address,
+    max_hops: u8,
+    next_hops: impl Fn(Address, U256) -> Vec<(Address, Trade, U256)>,
+) -> (Vec<Trade>, U256) {
+    // Basic BFS state: (current_token, current_amount, path_so_far)
+    let mut queue = std::collections::VecDeque::new();
+    queue.push_back((token_in, initial_amt, Vec::<Trade>::new()));
+
+    let mut best_route = Vec::new();
+    let mut best_amount_out = U256::ZERO; // Track the best amount achieved *at the target token*
+
+    // Best amount reached at each (token, hop_count), only strictly better arrivals are explored
+    let mut best_at_state: HashMap<(Address, u8), U256> = HashMap::new();
+    best_at_state.insert((token_in, 0), initial_amt);
+
+    while let Some((current_token, current_amount, current_path)) = queue.pop_front() {
+        let current_hop = current_path.len() as u8;
+
+        // Pruning: a better path to this state was queued after this one
+        if best_at_state
+            .get(&(current_token, current_hop))
+            .is_some_and(|best| current_amount < *best)
+        {
+            continue;
+        }
+
+        // Check if we reached the target token
+        if current_token == token_out && current_amount > best_amount_out {
+            best_amount_out = current_amount;
+            best_route = current_path.clone();
+            // Continue searching, maybe a longer path yields more?
+        }
+
+        // If max hops reached, don't explore further from here
+        if current_hop >= max_hops {
+            continue;
+        }
+
+        for (next_token, trade, output_amount) in next_hops(current_token, current_amount) {
+            let next_state = (next_token, current_hop + 1);
+            let improves = best_at_state
+                .get(&next_state)
+                .is_none_or(|best| output_amount > *best);
+
+            if output_amount > U256::ZERO && improves { // Only proceed if swap is possible and beats what we have
+                best_at_state.insert(next_state, output_amount);
+                let mut next_path = current_path.clone();
+                next_path.push(trade);
+                queue.push_back((next_token, output_amount, next_path));
+            }
+        }
+    }
+
+    (best_route, best_amount_out)
+}
+
 // --- Supporting Structs ---
 
 /// Represents a single swap step in a potential MEV path.
@@ -385,4 +799,113 @@ pub struct Pool {
     pub token0: Address, // Added token info
     pub token1: Address, // Added token info
     pub fee: u32,        // Added fee (e.g., V3 tier or basis points for V2)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::test_utils::{insert_v2_offline, market_state, offline_db, v2_pool};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn v2_spot_price_is_the_reserve_ratio_and_impact_grows_with_size() {
+        let (pool, token0, token1) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let mut db = offline_db();
+        insert_v2_offline(&mut db, v2_pool(pool, token0, token1, 1_000_000, 2_000_000));
+        let calculator = Calculator::new(market_state(db));
+
+        assert_eq!(calculator.spot_price(pool, token0, PoolType::UniswapV2, 0), 2.0);
+        assert_eq!(calculator.spot_price(pool, token1, PoolType::UniswapV2, 0), 0.5);
+
+        // A trade 0.1% of the reserve loses about the 0.3% fee plus 0.1% slippage
+        let small = calculator.price_impact(U256::from(1_000), pool, token0, PoolType::UniswapV2, 0);
+        assert!((small - 0.004).abs() < 0.0005, "small impact {small}");
+        // 10% of the reserve: 1 - 0.997 / 1.0997
+        let large = calculator.price_impact(U256::from(100_000), pool, token0, PoolType::UniswapV2, 0);
+        assert!((large - 0.0934).abs() < 0.001, "large impact {large}");
+    }
+
+    // A -> B through the worse pool is listed first. Pruning on the first arrival at (B, 1) would
+    // settle for the direct A -> C pool, the better arrival at B leads further through B -> C
+    #[test]
+    fn a_better_later_arrival_at_a_state_is_still_explored() {
+        let (a, b, c) = (Address::repeat_byte(0x0a), Address::repeat_byte(0x0b), Address::repeat_byte(0x0c));
+        let (worse, better, onward, direct) = (
+            Address::repeat_byte(0x01),
+            Address::repeat_byte(0x02),
+            Address::repeat_byte(0x03),
+            Address::repeat_byte(0x04),
+        );
+        let hop = |pool: Address, to: Address, out: U256| {
+            (to, Trade { pool_address: pool, pool_type: PoolType::UniswapV2 }, out)
+        };
+        let next_hops = |token: Address, amount: U256| {
+            if token == a {
+                vec![
+                    hop(worse, b, amount),
+                    hop(better, b, amount * U256::from(2)),
+                    hop(direct, c, amount * U256::from(3)),
+                ]
+            } else if token == b {
+                vec![hop(onward, c, amount * U256::from(2))]
+            } else {
+                Vec::new()
+            }
+        };
+
+        let (route, out) = best_route_search(U256::from(100), a, c, 3, next_hops);
+        let pools: Vec<Address> = route.iter().map(|trade| trade.pool_address).collect();
+        assert_eq!(pools, vec![better, onward]);
+        assert_eq!(out, U256::from(400));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn splitting_across_asymmetric_pools_beats_the_deeper_one_alone() {
+        let (token0, token1) = (Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let (deep, shallow) = (Address::repeat_byte(0x21), Address::repeat_byte(0x22));
+        let mut db = offline_db();
+        insert_v2_offline(&mut db, v2_pool(deep, token0, token1, 10_000_000, 10_000_000));
+        insert_v2_offline(&mut db, v2_pool(shallow, token0, token1, 4_000_000, 4_000_000));
+        let calculator = Calculator::new(market_state(db));
+        let pools: Vec<Pool> = [deep, shallow]
+            .into_iter()
+            .map(|address| Pool { address, pool_type: PoolType::UniswapV2, token0, token1, fee: 0 })
+            .collect();
+
+        let input = U256::from(3_000_000);
+        let split = calculator.split_route(input, token0, &pools);
+        assert_eq!(split.len(), 2, "both pools should take part: {split:?}");
+        assert_eq!(split.iter().fold(U256::ZERO, |acc, (_, amount)| acc + *amount), input);
+
+        let split_out = split.iter().fold(U256::ZERO, |acc, (pool, amount)| {
+            acc + calculator.compute_amount_out(*amount, *pool, token0, PoolType::UniswapV2, 0)
+        });
+        let deep_only = calculator.compute_amount_out(input, deep, token0, PoolType::UniswapV2, 0);
+        assert!(split_out > deep_only, "split {split_out} vs deep pool alone {deep_only}");
+    }
+
+    #[test]
+    fn v3_spot_price_squares_sqrt_price_x96() {
+        let q96 = U256::from(1) << 96;
+        assert_eq!(sqrt_price_ratio(q96, true), 1.0);
+        assert_eq!(sqrt_price_ratio(q96 * U256::from(2), true), 4.0);
+        assert_eq!(sqrt_price_ratio(q96 * U256::from(2), false), 0.25);
+        assert_eq!(sqrt_price_ratio(q96 / U256::from(4), true), 0.0625);
+        assert_eq!(sqrt_price_ratio(U256::ZERO, false), 0.0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn min_liquidity_gates_near_drained_v2_pool_only_when_set() {
+        let (pool, token0, token1) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let mut db = offline_db();
+        // Just above the 1000 units V2 locks forever
+        insert_v2_offline(&mut db, v2_pool(pool, token0, token1, 2_000, 2_000));
+        let state = market_state(db);
+
+        let calculator = Calculator::new(Arc::clone(&state));
+        let quote = calculator.compute_amount_out(U256::from(100), pool, token0, PoolType::UniswapV2, 0);
+        assert!(!quote.is_zero());
+
+        let calculator = Calculator::new(state).with_min_liquidity(U256::from(10_000));
+        let quote = calculator.compute_amount_out(U256::from(100), pool, token0, PoolType::UniswapV2, 0);
+        assert!(quote.is_zero());
+    }
+}
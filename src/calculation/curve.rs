@@ -1,20 +1,42 @@
 use crate::calculation::Calculator;
+use crate::utile::market_state::SimConfig;
+use crate::utile::revert::describe_revert;
+use crate::utile::rgen::ERC20Token;
 // Import necessary types from state_db module
 use crate::state_db::{BlockStateDB, blockstate_db::AccountInfo}; // Adjust path/name if needed
 
 use alloy::network::Network;
-use alloy::primitives::{address, Address, Bytes, U256}; // Fix: Import Bytes struct
+use alloy::primitives::{address, keccak256, Address, Bytes, U256}; // Fix: Import Bytes struct
 use alloy::providers::Provider;
 use alloy::sol;
 use alloy::sol_types::{SolCall, SolValue}; // SolValue needed for <U256>::abi_decode
 
 // Correct imports for revm v22.0.1
-use revm::primitives::{ExecutionResult, Output, TransactTo, Env};
+use revm::db::{CacheDB, WrapDatabaseRef};
+use revm::primitives::{BlockEnv, ExecutionResult, Output, TransactTo, Env};
 use revm::{Database, Evm}; // Use top-level Evm and Database trait
 
 use std::collections::HashMap;
 use tracing::{debug, info, warn};
 
+/// Transaction settings for Curve quote simulations
+#[derive(Debug, Clone, Copy)]
+pub struct CurveSimConfig {
+    /// `msg.sender` of the view call, some pools gate quotes on the caller
+    pub caller: Address,
+    /// Gas limit of the view call
+    pub gas_limit: u64,
+}
+
+impl Default for CurveSimConfig {
+    fn default() -> Self {
+        Self {
+            caller: address!("0000000000000000000000000000000000000001"),
+            gas_limit: 1_000_000,
+        }
+    }
+}
+
 sol! {
     #[sol(rpc)]
     contract CurveOut {
@@ -22,6 +44,10 @@ sol! {
         // j: index of token out
         // dx: amount of token in
         function get_dy(uint256 i, uint256 j, uint256 dx) external view returns (uint256);
+        // Metapools: i/j index the combined [meta coin, base pool coins...] list
+        function get_dy_underlying(int128 i, int128 j, uint256 dx) external view returns (uint256);
+        function coins(uint256 i) external view returns (address);
+        function exchange(uint256 i, uint256 j, uint256 dx, uint256 min_dy) external payable returns (uint256);
     }
 }
 
@@ -30,6 +56,34 @@ where
     N: Network,
     P: Provider<N>, // Fix: Add correct Provider trait bound
 {
+    /// Quotes a Curve swap, routing metapools (flagged at insertion) through `get_dy_underlying`.
+    pub fn curve_quote(
+        &self,
+        index_in: U256,
+        index_out: U256,
+        amount_in: U256,
+        pool: Address,
+    ) -> U256 {
+        let db = self.db_read();
+        self.curve_quote_with_db(&db, index_in, index_out, amount_in, pool)
+    }
+
+    /// `curve_quote` against an already locked DB
+    pub fn curve_quote_with_db(
+        &self,
+        db: &BlockStateDB<N, P>,
+        index_in: U256,
+        index_out: U256,
+        amount_in: U256,
+        pool: Address,
+    ) -> U256 {
+        if db.is_curve_metapool(&pool) {
+            self.curve_out_underlying_with_db(db, index_in, index_out, amount_in, pool)
+        } else {
+            self.curve_out_with_db(db, index_in, index_out, amount_in, pool)
+        }
+    }
+
     /// Simulates Curve's `get_dy` offchain using revm.
     /// Assumes the `pool` address is the Curve pool contract.
     pub fn curve_out(
@@ -38,6 +92,19 @@ where
         index_out: U256,
         amount_in: U256,
         pool: Address,
+    ) -> U256 {
+        let db = self.db_read();
+        self.curve_out_with_db(&db, index_in, index_out, amount_in, pool)
+    }
+
+    /// `curve_out` against an already locked DB
+    pub fn curve_out_with_db(
+        &self,
+        db: &BlockStateDB<N, P>,
+        index_in: U256,
+        index_out: U256,
+        amount_in: U256,
+        pool: Address,
     ) -> U256 {
         // Prepare calldata for the get_dy view call
         let calldata = CurveOut::get_dyCall {
@@ -47,24 +114,66 @@ where
         }
         .abi_encode(); // Returns Vec<u8>
 
-        // Get write access to the database via market_state
-        let mut db_guard = self.market_state.db.write().expect("Failed to acquire DB write lock");
-        let db = &mut *db_guard; // Get mutable reference to the DB
+        self.simulate_curve_view(db, calldata, amount_in, pool)
+    }
+
+    /// Simulates a metapool's `get_dy_underlying` offchain using revm.
+    /// Indices refer to the underlying coin list (meta coin first, then the base pool coins).
+    pub fn curve_out_underlying(
+        &self,
+        index_in: U256,
+        index_out: U256,
+        amount_in: U256,
+        pool: Address,
+    ) -> U256 {
+        let db = self.db_read();
+        self.curve_out_underlying_with_db(&db, index_in, index_out, amount_in, pool)
+    }
+
+    /// `curve_out_underlying` against an already locked DB
+    pub fn curve_out_underlying_with_db(
+        &self,
+        db: &BlockStateDB<N, P>,
+        index_in: U256,
+        index_out: U256,
+        amount_in: U256,
+        pool: Address,
+    ) -> U256 {
+        let (Ok(i), Ok(j)) = (i128::try_from(index_in), i128::try_from(index_out)) else {
+            warn!(?pool, %index_in, %index_out, "Curve underlying index out of range");
+            return U256::ZERO;
+        };
+
+        let calldata = CurveOut::get_dy_underlyingCall { i, j, dx: amount_in }.abi_encode();
+
+        self.simulate_curve_view(db, calldata, amount_in, pool)
+    }
+
+    /// Runs a Curve quote view call against the pool and decodes the single uint256 it returns.
+    fn simulate_curve_view(&self, db: &BlockStateDB<N, P>, calldata: Vec<u8>, amount_in: U256, pool: Address) -> U256 {
+        // Pools ramping A read block.timestamp, so quote against the current block
+        let block = self.market_state.block_env_read().clone();
+
+        // Accounts the call loads land in the overlay, the caller's read guard covers the live DB
+        let mut overlay = CacheDB::new(WrapDatabaseRef(db));
 
         // Create a default Env and modify it
         let mut env = Env::default();
-        env.tx.caller = address!("0000000000000000000000000000000000000001"); // Arbitrary caller
+        env.tx.caller = self.curve_sim_config.caller;
         env.tx.transact_to = TransactTo::Call(pool); // Target Curve pool contract
         env.tx.data = Bytes::from(calldata); // Convert Vec<u8> to revm::primitives::Bytes
         env.tx.value = U256::ZERO;
-        env.tx.gas_limit = 1_000_000; // Set a reasonable gas limit for the view call
+        env.tx.gas_limit = self.curve_sim_config.gas_limit;
         env.tx.gas_price = U256::ZERO; // For view calls, gas price isn't strictly needed
-        // Configure env.block, env.cfg as needed if necessary
+        env.block = block;
+        let sim_config = self.market_state.sim_config;
+        sim_config.apply_cfg(&mut env.cfg);
 
         // Setup EVM for simulation
         let mut evm = Evm::builder()
             .with_env(Box::new(env))
-            .with_db(db) // Provide the database implementation
+            .with_db(&mut overlay)
+            .with_spec_id(sim_config.spec_id)
             .build();
 
         // --- Optional: Snapshot before execution ---
@@ -105,8 +214,7 @@ where
                 }
             }
             ExecutionResult::Revert { output, gas_used } => {
-                // Try to decode revert reason?
-                warn!(?pool, %amount_in, %gas_used, "CurveOut simulation reverted: {:?}", output);
+                warn!(?pool, %amount_in, %gas_used, "CurveOut simulation reverted: {}", describe_revert(&output));
                 U256::ZERO
             }
             ExecutionResult::Halt { reason, gas_used } => {
@@ -116,6 +224,118 @@ where
         }
     }
 
+    /// Simulates the state-changing `exchange(i, j, dx, 0)` and returns the caller's token-out balance delta.
+    /// Unlike `get_dy` this includes admin-fee accrual and the pool's transfer rounding, so it is the executable amount.
+    /// Runs on a `CacheDB` overlay, funding, approval and the swap itself never reach `BlockStateDB`.
+    pub fn curve_exchange_sim(
+        &self,
+        index_in: U256,
+        index_out: U256,
+        amount_in: U256,
+        pool: Address,
+    ) -> U256 {
+        let block = self.market_state.block_env_read().clone();
+        let db_guard = self.db_read();
+        let mut overlay = CacheDB::new(WrapDatabaseRef(&*db_guard));
+        let caller = self.curve_sim_config.caller;
+        let call = |overlay: &mut CacheDB<_>, to: Address, data: Vec<u8>| {
+            Self::overlay_call(overlay, &self.curve_sim_config, &self.market_state.sim_config, &block, to, data)
+        };
+
+        let coin = |out: Option<Bytes>| out.and_then(|out| Address::abi_decode(&out, false).ok());
+        let Some(token_in) = coin(call(&mut overlay, pool, CurveOut::coinsCall { i: index_in }.abi_encode())) else {
+            return U256::ZERO;
+        };
+        let Some(token_out) = coin(call(&mut overlay, pool, CurveOut::coinsCall { i: index_out }.abi_encode())) else {
+            return U256::ZERO;
+        };
+        let balance_call = ERC20Token::balanceOfCall { account: caller }.abi_encode();
+        let balance = |out: Option<Bytes>| out.and_then(|out| U256::abi_decode(&out, false).ok());
+
+        // Fund the caller straight into the overlay through token_in's own `balances` slot, then approve the pool
+        let slot = self
+            .balance_slots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .balance_slot(&*db_guard, token_in);
+        let Some(slot) = slot else {
+            warn!(?pool, %token_in, "No balance slot found, can't fund Curve exchange simulation");
+            return U256::ZERO;
+        };
+        let balance_slot: U256 = keccak256((caller, slot).abi_encode()).into();
+        let approve = ERC20Token::approveCall { spender: pool, amount: amount_in }.abi_encode();
+        let exchange = CurveOut::exchangeCall {
+            i: index_in,
+            j: index_out,
+            dx: amount_in,
+            min_dy: U256::ZERO,
+        }
+        .abi_encode();
+
+        if let Err(e) = overlay.insert_account_storage(token_in, balance_slot, amount_in) {
+            warn!(?pool, "Failed to fund Curve exchange simulation: {:?}", e);
+            return U256::ZERO;
+        }
+        if call(&mut overlay, token_in, approve).is_none() {
+            return U256::ZERO;
+        }
+
+        let Some(before) = balance(call(&mut overlay, token_out, balance_call.clone())) else {
+            return U256::ZERO;
+        };
+        if call(&mut overlay, pool, exchange).is_none() {
+            return U256::ZERO;
+        }
+        let Some(after) = balance(call(&mut overlay, token_out, balance_call)) else {
+            return U256::ZERO;
+        };
+
+        let amount_out = after.saturating_sub(before);
+        debug!(?pool, %amount_in, %amount_out, "Curve exchange simulation success.");
+        amount_out
+    }
+
+    /// Executes and commits a call against the simulation overlay, returning its output on success
+    fn overlay_call(
+        overlay: &mut CacheDB<WrapDatabaseRef<&BlockStateDB<N, P>>>,
+        config: &CurveSimConfig,
+        sim_config: &SimConfig,
+        block: &BlockEnv,
+        to: Address,
+        data: Vec<u8>,
+    ) -> Option<Bytes> {
+        let mut env = Env::default();
+        env.tx.caller = config.caller;
+        env.tx.transact_to = TransactTo::Call(to);
+        env.tx.data = Bytes::from(data);
+        env.tx.value = U256::ZERO;
+        env.tx.gas_limit = config.gas_limit;
+        env.tx.gas_price = U256::ZERO;
+        env.block = block.clone();
+        sim_config.apply_cfg(&mut env.cfg);
+
+        let mut evm = Evm::builder()
+            .with_env(Box::new(env))
+            .with_db(overlay)
+            .with_spec_id(sim_config.spec_id)
+            .build();
+        match evm.transact_commit() {
+            Ok(ExecutionResult::Success { output: Output::Call(bytes), .. }) => Some(bytes),
+            Ok(ExecutionResult::Revert { output, .. }) => {
+                warn!(%to, "Curve exchange simulation reverted: {}", describe_revert(&output));
+                None
+            }
+            Ok(result) => {
+                warn!(%to, "Curve exchange simulation call failed: {:?}", result);
+                None
+            }
+            Err(err) => {
+                warn!(%to, "Curve exchange simulation EVM error: {:?}", err);
+                None
+            }
+        }
+    }
+
     /// Checks if a Curve swap results in zero output (potential edge case).
     pub fn is_curve_edge_case_zero(
         &self,
@@ -201,3 +421,93 @@ where
         // else: Account didn't exist before or after, no changes related to it.
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::MarketState;
+    use crate::utile::test_utils::full_node_provider;
+    use std::sync::Arc;
+
+    // Compares both simulations against the pool's own get_dy at the latest block. Run with
+    // FULL=<base rpc> CURVE_TEST_POOL=<2-coin pool> cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn curve_quotes_match_on_chain_get_dy() {
+        let pool: Address = std::env::var("CURVE_TEST_POOL")
+            .expect("CURVE_TEST_POOL must name a Curve pool")
+            .parse()
+            .unwrap();
+        let provider = full_node_provider();
+        let (i, j) = (U256::ZERO, U256::from(1));
+
+        let coin = CurveOut::new(pool, &provider).coins(i).call().await.unwrap();
+        let amount_in = crate::utile::amount_for(coin);
+        let on_chain = CurveOut::new(pool, &provider).get_dy(i, j, amount_in).call().await.unwrap();
+
+        let market_state = MarketState::standalone(Vec::new(), provider).await.unwrap();
+        let calculator = Calculator::new(market_state);
+        assert_eq!(calculator.curve_out(i, j, amount_in, pool), on_chain);
+
+        // exchange pays out what get_dy promises, give or take the pool's transfer rounding
+        let exchanged = calculator.curve_exchange_sim(i, j, amount_in, pool);
+        assert!(!exchanged.is_zero());
+        assert!(exchanged.abs_diff(on_chain) <= on_chain / U256::from(10_000));
+    }
+
+    // Quotes the meta coin into the first base pool coin. Run with
+    // FULL=<base rpc> CURVE_TEST_METAPOOL=<metapool> cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn metapool_quotes_match_on_chain_get_dy_underlying() {
+        let pool: Address = std::env::var("CURVE_TEST_METAPOOL")
+            .expect("CURVE_TEST_METAPOOL must name a Curve metapool")
+            .parse()
+            .unwrap();
+        let provider = full_node_provider();
+        let (i, j) = (0i128, 1i128);
+
+        let coin = CurveOut::new(pool, &provider).coins(U256::ZERO).call().await.unwrap();
+        let amount_in = crate::utile::amount_for(coin);
+        let on_chain = CurveOut::new(pool, &provider)
+            .get_dy_underlying(i, j, amount_in)
+            .call()
+            .await
+            .unwrap();
+
+        let market_state = MarketState::standalone(Vec::new(), provider).await.unwrap();
+        let calculator = Calculator::new(market_state);
+        assert_eq!(calculator.curve_out_underlying(U256::ZERO, U256::from(1), amount_in, pool), on_chain);
+    }
+
+    // Stands in for a pool ramping A: its "get_dy" returns block.timestamp, so the quote follows the block env
+    #[tokio::test(flavor = "multi_thread")]
+    async fn quotes_read_the_current_block_timestamp() {
+        use crate::state_db::InsertionType;
+        use crate::utile::test_utils::{market_state, offline_db};
+        use revm::primitives::{AccountInfo, Bytecode};
+
+        // TIMESTAMP PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        let code = Bytes::from_static(&[0x42, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3]);
+        let pool = Address::repeat_byte(0x33);
+        let mut db = offline_db();
+        db.insert_account_info(
+            pool,
+            AccountInfo {
+                code_hash: keccak256(&code),
+                code: Some(Bytecode::new_raw(code)),
+                ..Default::default()
+            },
+            InsertionType::Custom,
+        );
+        let state = market_state(db);
+        let calculator = Calculator::new(Arc::clone(&state));
+        let (i, j, dx) = (U256::ZERO, U256::from(1), U256::from(1_000));
+
+        state.block_env.write().unwrap().timestamp = U256::from(1_700_000_000u64);
+        assert_eq!(calculator.curve_out(i, j, dx, pool), U256::from(1_700_000_000u64));
+
+        state.block_env.write().unwrap().timestamp = U256::from(1_700_000_002u64);
+        assert_eq!(calculator.curve_out(i, j, dx, pool), U256::from(1_700_000_002u64));
+    }
+}
@@ -4,5 +4,8 @@ pub mod balancer;
 pub mod calculator;
 pub mod curve;
 pub mod maverick;
+pub mod quote;
+pub mod registry;
 pub mod uniswap;
 pub use calculator::Calculator;
+pub use quote::{quote_single, supports_single_quote};
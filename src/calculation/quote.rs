@@ -0,0 +1,107 @@
+use crate::calculation::Calculator;
+use crate::utile::MarketState;
+
+use alloy::network::Network;
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use anyhow::{Result, bail};
+use pool_sync::{Pool, PoolInfo, PoolType};
+
+/// Whether `quote_single` can quote pools of `pool_type`:
+/// - every Uniswap V2 fork (UniswapV2, SushiSwapV2, PancakeSwapV2, BaseSwapV2, SwapBasedV2, DackieSwapV2, AlienBaseV2)
+/// - every Uniswap V3 fork including Slipstream
+/// - Aerodrome, volatile and stable
+/// - BalancerV2 weighted pools
+/// - 2-coin Curve pools, the output is the other coin
+///
+/// Curve tri-crypto needs the output coin, Maverick V1 a tick limit and Maverick V2 a live block context,
+/// so they are only quoted in the bot
+pub fn supports_single_quote(pool_type: PoolType) -> bool {
+    !matches!(
+        pool_type,
+        PoolType::CurveTriCrypto | PoolType::MaverickV1 | PoolType::MaverickV2
+    )
+}
+
+/// Output of swapping `amount_in` of `token_in` through `pool` at the latest block, without the bot's workers.
+/// Builds a `MarketState` holding only `pool` and runs `Calculator::compute_amount_out` against it,
+/// so the result matches what the searcher would quote. Needs a multi-threaded tokio runtime.
+/// See `supports_single_quote` for the pool types it accepts
+pub async fn quote_single<N, P>(provider: P, pool: Pool, token_in: Address, amount_in: U256) -> Result<U256>
+where
+    N: Network,
+    P: Provider<N> + Clone + Send + Sync + 'static,
+{
+    let pool_type = pool.pool_type();
+    if !supports_single_quote(pool_type) {
+        bail!("{:?} pools can't be quoted on their own", pool_type);
+    }
+    let pool_address = pool.address();
+    let tokens = match &pool {
+        Pool::BalancerV2(balancer) => balancer.get_tokens(),
+        _ => vec![pool.token0_address(), pool.token1_address()],
+    };
+    if !tokens.contains(&token_in) {
+        bail!("Token {} is not traded by pool {}", token_in, pool_address);
+    }
+    let fee = pool.fee();
+
+    let market_state = MarketState::standalone(vec![pool], provider).await?;
+    let calculator = Calculator::new(market_state);
+    Ok(calculator.compute_amount_out(amount_in, pool_address, token_in, pool_type, fee))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::test_utils::{full_node_provider, v2_pool};
+    use alloy::primitives::address;
+    use alloy::sol;
+
+    sol! {
+        #[sol(rpc)]
+        contract V2Pair {
+            function token0() external view returns (address);
+            function token1() external view returns (address);
+            function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
+        }
+
+        #[sol(rpc)]
+        contract V2Router {
+            function getAmountsOut(uint amountIn, address[] memory path) external view returns (uint[] memory amounts);
+        }
+    }
+
+    /// Uniswap V2 router on Base
+    const UNISWAP_V2_ROUTER: Address = address!("4752ba5DBc23f44D87826276BF6Fd6b1C372aD24");
+
+    // The pair's reserves and the router quote are read at the same block. Run with
+    // FULL=<base rpc> V2_TEST_PAIR=<uniswap v2 pair> cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn v2_quotes_match_the_router() {
+        let pair: Address = std::env::var("V2_TEST_PAIR")
+            .expect("V2_TEST_PAIR must name a Uniswap V2 pair")
+            .parse()
+            .unwrap();
+        let provider = full_node_provider();
+        let block = provider.get_block_number().await.unwrap();
+        let contract = V2Pair::new(pair, &provider);
+        let token0 = contract.token0().call().await.unwrap();
+        let token1 = contract.token1().call().await.unwrap();
+        let reserves = contract.getReserves().block(block.into()).call().await.unwrap();
+        let amount_in = crate::utile::amount_for(token0);
+
+        let amounts = V2Router::new(UNISWAP_V2_ROUTER, &provider)
+            .getAmountsOut(amount_in, vec![token0, token1])
+            .block(block.into())
+            .call()
+            .await
+            .unwrap();
+
+        let pool = v2_pool(pair, token0, token1, reserves.reserve0.to(), reserves.reserve1.to());
+        let quoted = quote_single(provider, pool, token0, amount_in).await.unwrap();
+        assert!(quoted > U256::ZERO);
+        assert_eq!(quoted, amounts[1]);
+    }
+}
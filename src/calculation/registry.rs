@@ -0,0 +1,193 @@
+use crate::calculation::Calculator;
+use crate::calculation::uniswap;
+use crate::state_db::BlockStateDB;
+
+use alloy::network::Network;
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use pool_sync::PoolType;
+use std::collections::HashMap;
+
+const V2_POOL_TYPES: [PoolType; 7] = [
+    PoolType::UniswapV2,
+    PoolType::SushiSwapV2,
+    PoolType::SwapBasedV2,
+    PoolType::PancakeSwapV2,
+    PoolType::BaseSwapV2,
+    PoolType::DackieSwapV2,
+    PoolType::AlienBaseV2,
+];
+
+const V3_POOL_TYPES: [PoolType; 8] = [
+    PoolType::UniswapV3,
+    PoolType::SushiSwapV3,
+    PoolType::BaseSwapV3,
+    PoolType::Slipstream,
+    PoolType::PancakeSwapV3,
+    PoolType::AlienBaseV3,
+    PoolType::SwapBasedV3,
+    PoolType::DackieSwapV3,
+];
+
+/// Everything a pool calculator can read while quoting a single step.
+/// The DB is already read-locked by the caller, calculators must not lock it again.
+pub struct CalcContext<'a, N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    pub calculator: &'a Calculator<N, P>,
+    pub db: &'a BlockStateDB<N, P>,
+    pub pool_address: Address,
+    pub pool_type: PoolType,
+    /// Output token when quoting a path step, None for a bare `compute_amount_out`
+    pub token_out: Option<Address>,
+}
+
+/// Quotes swaps for one family of pools, registered on `Calculator` per `PoolType`
+pub trait PoolCalculator<N, P>: Send + Sync
+where
+    N: Network,
+    P: Provider<N>,
+{
+    /// Output for swapping `input` of `token_in`, zero when the swap isn't possible
+    fn amount_out(&self, ctx: &CalcContext<'_, N, P>, input: U256, token_in: Address, fee: u32) -> U256;
+}
+
+pub type CalculatorRegistry<N, P> = HashMap<PoolType, Box<dyn PoolCalculator<N, P>>>;
+
+/// Built-in calculators for every protocol with in-process quoting.
+/// Maverick V1 needs a tick limit from the path and stays unregistered.
+pub fn default_registry<N, P>() -> CalculatorRegistry<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let mut registry: CalculatorRegistry<N, P> = HashMap::new();
+    for pool_type in V2_POOL_TYPES {
+        registry.insert(pool_type, Box::new(UniswapV2Calculator));
+    }
+    for pool_type in V3_POOL_TYPES {
+        registry.insert(pool_type, Box::new(UniswapV3Calculator));
+    }
+    registry.insert(PoolType::Aerodrome, Box::new(AerodromeCalculator));
+    registry.insert(PoolType::BalancerV2, Box::new(BalancerV2Calculator));
+    registry.insert(PoolType::MaverickV2, Box::new(MaverickV2Calculator));
+    registry.insert(PoolType::CurveTwoCrypto, Box::new(CurveCalculator));
+    registry.insert(PoolType::CurveTriCrypto, Box::new(CurveCalculator));
+    registry
+}
+
+pub struct UniswapV2Calculator;
+
+impl<N, P> PoolCalculator<N, P> for UniswapV2Calculator
+where
+    N: Network,
+    P: Provider<N>,
+{
+    fn amount_out(&self, ctx: &CalcContext<'_, N, P>, input: U256, token_in: Address, _fee: u32) -> U256 {
+        // V2 fees are fixed per fork, see `V2FeeTable`
+        let fee = uniswap::v2_fee_or_default(ctx.pool_type);
+        Calculator::uniswap_v2_out_with_db(ctx.db, input, &ctx.pool_address, &token_in, fee)
+    }
+}
+
+pub struct UniswapV3Calculator;
+
+impl<N, P> PoolCalculator<N, P> for UniswapV3Calculator
+where
+    N: Network,
+    P: Provider<N>,
+{
+    fn amount_out(&self, ctx: &CalcContext<'_, N, P>, input: U256, token_in: Address, fee: u32) -> U256 {
+        // V3 fee is the pool's tier (e.g., 500, 3000, 10000)
+        ctx.calculator
+            .uniswap_v3_out_detailed_with_db(ctx.db, input, &ctx.pool_address, &token_in, fee, None)
+            .map(|(amount_out, _, _)| amount_out)
+            .unwrap_or(U256::ZERO)
+    }
+}
+
+pub struct AerodromeCalculator;
+
+impl<N, P> PoolCalculator<N, P> for AerodromeCalculator
+where
+    N: Network,
+    P: Provider<N>,
+{
+    fn amount_out(&self, ctx: &CalcContext<'_, N, P>, input: U256, token_in: Address, _fee: u32) -> U256 {
+        // Fee is resolved from the factory at insertion
+        ctx.calculator
+            .aerodrome_out_with_db(ctx.db, input, token_in, ctx.pool_address)
+    }
+}
+
+pub struct BalancerV2Calculator;
+
+impl<N, P> PoolCalculator<N, P> for BalancerV2Calculator
+where
+    N: Network,
+    P: Provider<N>,
+{
+    fn amount_out(&self, ctx: &CalcContext<'_, N, P>, input: U256, token_in: Address, _fee: u32) -> U256 {
+        // Assumes a two-token pool, the output is the other token
+        let tokens = ctx.db.get_balancer_tokens(&ctx.pool_address);
+        match tokens.into_iter().find(|token| *token != token_in) {
+            Some(token_out) => {
+                Calculator::balancer_v2_out_with_db(ctx.db, input, token_in, token_out, ctx.pool_address)
+            }
+            None => U256::ZERO,
+        }
+    }
+}
+
+pub struct MaverickV2Calculator;
+
+impl<N, P> PoolCalculator<N, P> for MaverickV2Calculator
+where
+    N: Network,
+    P: Provider<N>,
+{
+    fn amount_out(&self, ctx: &CalcContext<'_, N, P>, input: U256, token_in: Address, _fee: u32) -> U256 {
+        // tokenA is the pool's token0
+        let block = ctx.calculator.market_state.block_env_read().clone();
+        match ctx.db.zero_to_one(&ctx.pool_address, token_in) {
+            Some(token_a_in) => Calculator::maverick_v2_out_with_db(
+                ctx.db,
+                &block,
+                &ctx.calculator.market_state.sim_config,
+                ctx.calculator.market_state.deployment.maverick_v2_quoter,
+                input,
+                ctx.pool_address,
+                token_a_in,
+                None,
+            ),
+            None => U256::ZERO,
+        }
+    }
+}
+
+pub struct CurveCalculator;
+
+impl<N, P> PoolCalculator<N, P> for CurveCalculator
+where
+    N: Network,
+    P: Provider<N>,
+{
+    fn amount_out(&self, ctx: &CalcContext<'_, N, P>, input: U256, token_in: Address, _fee: u32) -> U256 {
+        // Without a path step the output is only unambiguous for a 2-coin pool
+        let token_out = ctx.token_out.or_else(|| {
+            let coins = ctx.db.curve_tokens.get(&ctx.pool_address)?;
+            match coins.as_slice() {
+                [a, b] if *a == token_in => Some(*b),
+                [a, b] if *b == token_in => Some(*a),
+                _ => None,
+            }
+        });
+        let Some((i, j)) = token_out.and_then(|token_out| ctx.db.curve_indices(&ctx.pool_address, token_in, token_out))
+        else {
+            return U256::ZERO;
+        };
+        ctx.calculator.curve_quote_with_db(ctx.db, i, j, input, ctx.pool_address)
+    }
+}
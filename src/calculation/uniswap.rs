@@ -1,14 +1,18 @@
 use crate::calculation::Calculator;
+use crate::state_db::BlockStateDB;
 use crate::utile::DbTickDataProvider;
 use uniswap_v3_sdk::prelude::TickMath; 
 use alloy::network::Network;
 use alloy::primitives::{Address, I256, U256};
 use alloy::providers::Provider;
 use anyhow::{Result, anyhow};
-use log::info;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use pool_sync::PoolType;
 use uniswap_v3_math::swap_math;
 use uniswap_v3_math::tick_math::{self, MAX_SQRT_RATIO, MAX_TICK, MIN_SQRT_RATIO, MIN_TICK};
-use uniswap_v3_sdk::prelude::TickDataProvider;
+use std::collections::HashMap;
+use std::sync::RwLock;
 pub const U256_1: U256 = U256::from_limbs([1, 0, 0, 0]);
 
 // Mock DB access interface - not used in calculation functions
@@ -59,7 +63,19 @@ where
         fee: U256,
     ) -> U256 {
         // get read access to db
-        let db_read = self.market_state.db.read().unwrap();
+        let db_read = self.db_read();
+        Self::uniswap_v2_out_with_db(&db_read, amount_in, pool_address, token_in, fee)
+    }
+
+    /// `uniswap_v2_out` against an already locked DB
+    #[inline]
+    pub fn uniswap_v2_out_with_db(
+        db_read: &BlockStateDB<N, P>,
+        amount_in: U256,
+        pool_address: &Address,
+        token_in: &Address,
+        fee: U256,
+    ) -> U256 {
         let zero_to_one = match db_read.zero_to_one(pool_address, *token_in) {
             Ok(zto) => zto,
             Err(e) => {
@@ -69,26 +85,18 @@ where
         };
         let (reserve0, reserve1) = db_read.get_reserves(pool_address);
 
-        let scalar = U256::from(10000);
-
         let (reserve_in, reserve_out) = if zero_to_one {
             (U256::from(reserve0), U256::from(reserve1))
         } else {
             (U256::from(reserve1), U256::from(reserve0))
         };
 
-        let amount_in_with_fee = amount_in * fee;
-        let numerator = amount_in_with_fee * reserve_out;
-        let denominator = reserve_in * scalar + amount_in_with_fee;
-        
-        if denominator.is_zero() {
-            info!("Uniswap V2 division by zero in denominator");
-            return U256::ZERO;
-        }
-        numerator / denominator
+        v2_amount_out(amount_in, reserve_in, reserve_out, fee)
     }
 
-    // calculate the amount out for a uniswapv3 swap using swap_math and full_math for precision
+    // calculate the amount out for a uniswapv3 swap using swap_math and full_math for precision.
+    // With `sqrt_price_limit_x96` set the swap stops at that price and returns the partial output,
+    // like the router's sqrtPriceLimitX96; None walks up to the pool's price bounds.
     #[inline]
     pub fn uniswap_v3_out(
         &self,
@@ -96,36 +104,81 @@ where
         pool_address: &Address,
         token_in: &Address,
         fee: u32,
+        sqrt_price_limit_x96: Option<U256>,
     ) -> Result<U256> {
+        let (amount_out, _ticks_crossed, _final_sqrt_price) =
+            self.uniswap_v3_out_detailed(amount_in, pool_address, token_in, fee, sqrt_price_limit_x96)?;
+        Ok(amount_out)
+    }
+
+    /// Same as `uniswap_v3_out` but also reports how many initialized ticks were crossed
+    /// and the sqrt price the pool ends at. Returns `(amount_out, ticks_crossed, final_sqrt_price)`.
+    pub fn uniswap_v3_out_detailed(
+        &self,
+        amount_in: U256,
+        pool_address: &Address,
+        token_in: &Address,
+        fee: u32,
+        sqrt_price_limit_x96: Option<U256>,
+    ) -> Result<(U256, u32, U256)> {
         if amount_in.is_zero() {
-            return Ok(U256::ZERO);
+            return Ok((U256::ZERO, 0, U256::ZERO));
         }
 
         // acquire db read access and get all our state information
-        let db_read = self.market_state.db.read().unwrap();
+        let db_read = self.db_read();
+        self.uniswap_v3_out_detailed_with_db(&db_read, amount_in, pool_address, token_in, fee, sqrt_price_limit_x96)
+    }
+
+    /// `uniswap_v3_out_detailed` against an already locked DB
+    pub fn uniswap_v3_out_detailed_with_db(
+        &self,
+        db_read: &BlockStateDB<N, P>,
+        amount_in: U256,
+        pool_address: &Address,
+        token_in: &Address,
+        fee: u32,
+        sqrt_price_limit_x96: Option<U256>,
+    ) -> Result<(U256, u32, U256)> {
+        if amount_in.is_zero() {
+            return Ok((U256::ZERO, 0, U256::ZERO));
+        }
+
         let zero_to_one = db_read.zero_to_one(pool_address, *token_in).unwrap();
         let slot0 = db_read.slot0(*pool_address)?;
         let liquidity = db_read.liquidity(*pool_address)?;
-        let tick_spacing = db_read.tick_spacing(pool_address)?;
+        let tick_spacing = db_read.tick_spacing(*pool_address)?;
 
-        // Set sqrt_price_limit_x_96 to the max or min sqrt price in the pool depending on zero_for_one
-        let sqrt_price_limit_x_96 = if zero_to_one {
-            tick_math::MIN_SQRT_RATIO + U256_1
-        } else {
-            tick_math::MAX_SQRT_RATIO - U256_1
+        // Default sqrt_price_limit_x_96 to the max or min sqrt price in the pool depending on zero_for_one
+        let sqrt_price_limit_x_96 = match sqrt_price_limit_x96 {
+            Some(limit) => limit.clamp(tick_math::MIN_SQRT_RATIO + U256_1, tick_math::MAX_SQRT_RATIO - U256_1),
+            None if zero_to_one => tick_math::MIN_SQRT_RATIO + U256_1,
+            None => tick_math::MAX_SQRT_RATIO - U256_1,
         };
 
+        // A limit on the wrong side of the current price allows no trade, the pool would revert with SPL
+        let current_sqrt_price = U256::from(slot0.sqrt_price_x96);
+        if (zero_to_one && sqrt_price_limit_x_96 >= current_sqrt_price)
+            || (!zero_to_one && sqrt_price_limit_x_96 <= current_sqrt_price)
+        {
+            return Ok((U256::ZERO, 0, current_sqrt_price));
+        }
+
         // Initialize a mutable state struct to hold the dynamic simulated state of the pool
         let mut current_state = CurrentState {
-            sqrt_price_x_96: slot0.sqrtPriceX96, //Active price on the pool
+            sqrt_price_x_96: U256::from(slot0.sqrt_price_x96), //Active price on the pool
             amount_calculated: I256::ZERO,       //Amount of token_out that has been calculated
             amount_specified_remaining: I256::from_raw(amount_in), //Amount of token_in that has not been swapped
             tick: slot0.tick,
             liquidity, //Current available liquidity in the tick range
         };
 
-        // Prepare tick data provider
-        let mut tick_data_provider = crate::utile::DbTickDataProvider::new(db_read.clone(), *pool_address, tick_spacing);
+        // Number of initialized ticks crossed, a proxy for the gas cost of the swap
+        let mut ticks_crossed: u32 = 0;
+
+        // Prepare tick data provider, bitmap and liquidity_net reads are memoized until the pool is touched
+        let tick_data_provider = DbTickDataProvider::new(db_read, *pool_address, tick_spacing)
+            .with_cache(&self.tick_cache);
 
         while current_state.amount_specified_remaining > I256::ZERO
             && current_state.sqrt_price_x_96 != sqrt_price_limit_x_96
@@ -178,6 +231,8 @@ where
             if current_state.sqrt_price_x_96 == step.sqrt_price_next_x96 {
                 // If the tick crossed was initialized, adjust the liquidity
                 if step.initialized {
+                    ticks_crossed += 1;
+
                     // Get liquidity net from the tick data provider
                     let liquidity_net = tick_data_provider.get_liquidity_net(step.tick_next)?;
                     
@@ -222,6 +277,173 @@ where
             );
         }
 
-        Ok((-current_state.amount_calculated).into_raw())
+        Ok((
+            (-current_state.amount_calculated).into_raw(),
+            ticks_crossed,
+            current_state.sqrt_price_x_96,
+        ))
+    }
+}
+
+/// Constant-product output for explicit reserves, `fee` is the multiplier out of 10_000 (e.g. 9970)
+pub fn v2_amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256, fee: U256) -> U256 {
+    let scalar = U256::from(10000);
+    let amount_in_with_fee = amount_in * fee;
+    let numerator = amount_in_with_fee * reserve_out;
+    let denominator = reserve_in * scalar + amount_in_with_fee;
+
+    if denominator.is_zero() {
+        info!("Uniswap V2 division by zero in denominator");
+        return U256::ZERO;
+    }
+    numerator / denominator
+}
+
+// Multiplier applied to V2 pools missing from the fee table
+pub const DEFAULT_V2_FEE: u32 = 9970;
+
+lazy_static! {
+    static ref V2_FEES: RwLock<V2FeeTable> = RwLock::new(V2FeeTable::default());
+}
+
+/// Fee multipliers (out of 10_000) of the V2 clones, keyed by protocol
+#[derive(Debug, Clone)]
+pub struct V2FeeTable {
+    fees: HashMap<PoolType, U256>,
+}
+
+impl Default for V2FeeTable {
+    fn default() -> Self {
+        Self::builder()
+            // 0.30%
+            .fee(PoolType::UniswapV2, 9970)
+            .fee(PoolType::SushiSwapV2, 9970)
+            .fee(PoolType::SwapBasedV2, 9970)
+            // 0.25%
+            .fee(PoolType::PancakeSwapV2, 9975)
+            .fee(PoolType::BaseSwapV2, 9975)
+            .fee(PoolType::DackieSwapV2, 9975)
+            // 0.16%
+            .fee(PoolType::AlienBaseV2, 9984)
+            .build()
+    }
+}
+
+impl V2FeeTable {
+    /// Empty builder, start from `V2FeeTable::default().to_builder()` to override the known forks
+    pub fn builder() -> V2FeeTableBuilder {
+        V2FeeTableBuilder {
+            fees: HashMap::new(),
+        }
+    }
+
+    pub fn to_builder(&self) -> V2FeeTableBuilder {
+        V2FeeTableBuilder {
+            fees: self.fees.clone(),
+        }
+    }
+
+    pub fn get(&self, pool_type: PoolType) -> Option<U256> {
+        self.fees.get(&pool_type).copied()
+    }
+}
+
+pub struct V2FeeTableBuilder {
+    fees: HashMap<PoolType, U256>,
+}
+
+impl V2FeeTableBuilder {
+    /// Set the multiplier out of 10_000 for `pool_type`, e.g. 9970 for a 0.30% fee
+    pub fn fee(mut self, pool_type: PoolType, fee: u32) -> Self {
+        self.fees.insert(pool_type, U256::from(fee));
+        self
+    }
+
+    pub fn build(self) -> V2FeeTable {
+        V2FeeTable { fees: self.fees }
+    }
+
+    /// Replace the global table read by `v2_fee`
+    pub fn install(self) {
+        *V2_FEES.write().unwrap() = self.build();
+    }
+}
+
+/// Fee multiplier (out of 10_000) of the V2 clones, None for anything else
+pub fn v2_fee(pool_type: PoolType) -> Option<U256> {
+    V2_FEES.read().unwrap().get(pool_type)
+}
+
+/// Fee multiplier for a V2 pool, falling back to `DEFAULT_V2_FEE` for forks missing from the table
+pub fn v2_fee_or_default(pool_type: PoolType) -> U256 {
+    v2_fee(pool_type).unwrap_or_else(|| {
+        warn!("No V2 fee configured for {:?}, using {}", pool_type, DEFAULT_V2_FEE);
+        U256::from(DEFAULT_V2_FEE)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_forks_use_their_documented_fee() {
+        let table = V2FeeTable::default();
+        let documented = [
+            (PoolType::UniswapV2, 9970),
+            (PoolType::SushiSwapV2, 9970),
+            (PoolType::SwapBasedV2, 9970),
+            (PoolType::PancakeSwapV2, 9975),
+            (PoolType::BaseSwapV2, 9975),
+            (PoolType::DackieSwapV2, 9975),
+            (PoolType::AlienBaseV2, 9984),
+        ];
+        for (pool_type, fee) in documented {
+            assert_eq!(table.get(pool_type), Some(U256::from(fee)), "{pool_type:?}");
+        }
+        assert_eq!(table.get(PoolType::UniswapV3), None);
+
+        let overridden = table.to_builder().fee(PoolType::AlienBaseV2, 9970).build();
+        assert_eq!(overridden.get(PoolType::AlienBaseV2), Some(U256::from(9970)));
+        assert_eq!(overridden.get(PoolType::UniswapV2), Some(U256::from(9970)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_tight_sqrt_price_limit_returns_a_partial_output() {
+        use crate::state_db::BlockStateDBSlot;
+        use crate::state_db::InsertionType;
+        use crate::state_db::v3_db::tick_bitmap_slot;
+        use crate::utile::test_utils::{insert_v3_offline, market_state, offline_db, v3_pool};
+
+        let (pool, token0, token1) = (Address::repeat_byte(0x31), Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let sqrt_price = U256::from(1) << 96;
+        let mut db = offline_db();
+        insert_v3_offline(&mut db, v3_pool(pool, token0, token1, sqrt_price, 0, 10u128.pow(18)));
+        // No initialized ticks near the price, the words the swap scans are empty rather than fetched
+        let account = db.accounts.get_mut(&pool).unwrap();
+        for word in -2..=1 {
+            account.storage.insert(
+                tick_bitmap_slot(word).unwrap(),
+                BlockStateDBSlot { value: U256::ZERO, insertion_type: InsertionType::Custom },
+            );
+        }
+        let calculator = Calculator::new(market_state(db));
+        let amount_in = U256::from(10u64.pow(15));
+
+        let (unbounded, _, _) = calculator
+            .uniswap_v3_out_detailed(amount_in, &pool, &token0, 3000, None)
+            .unwrap();
+        let limit = sqrt_price * U256::from(9_995) / U256::from(10_000);
+        let (limited, _, final_price) = calculator
+            .uniswap_v3_out_detailed(amount_in, &pool, &token0, 3000, Some(limit))
+            .unwrap();
+
+        assert!(!limited.is_zero());
+        assert!(limited < unbounded, "limited {limited} vs unbounded {unbounded}");
+        assert_eq!(final_price, limit);
+
+        // Selling token0 pushes the price down, a limit above it allows no trade
+        let above = sqrt_price + U256::from(1);
+        assert_eq!(calculator.uniswap_v3_out(amount_in, &pool, &token0, 3000, Some(above)).unwrap(), U256::ZERO);
     }
 }
@@ -1,55 +1,37 @@
 use alloy::primitives::U256;
 use anyhow::Result;
 use log::LevelFilter;
-use once_cell::sync::Lazy;
-use pool_sync::{Chain, PoolSync, PoolType};
-use std::{collections::HashMap, sync::RwLock, time::Duration};
+use pool_sync::{Chain, Pool, PoolSync, PoolType};
+use alloy::primitives::Address;
+use alloy::network::Network;
+use alloy::providers::Provider;
 use tracing::info;
 
 mod calculation;
 mod state_db;
 mod utile;
 
-use crate::utile::ignition::start_workers;
+use crate::utile::constant::AMOUNTS;
+use crate::utile::decimals::DECIMALS;
+use crate::utile::ignition::{ChainConfig, start_workers};
 
-pub const AMOUNT: Lazy<RwLock<U256>> =
-    Lazy::new(|| RwLock::new(U256::from(1_000_000_000_000_000_000u128)));
-
-// Token decimals map to convert $100k into base units
-pub static TOKEN_DECIMALS: Lazy<HashMap<&'static str, u8>> = Lazy::new(|| {
-    let mut map = HashMap::new();
-    map.insert("USDC", 6);
-    map.insert("USDT", 6);
-    map.insert("WETH", 18);
-    map.insert("DAI", 18);
-    map
-});
-
-/// Converts a token symbol to an on-chain value in base units
-pub fn amount_for_token(token_symbol: &str) -> U256 {
-    let decimals = TOKEN_DECIMALS.get(token_symbol).copied().unwrap_or(18);
+/// Converts 100k whole units of `token` into base units, resolving its decimals by address
+pub async fn amount_for_token<N: Network, P: Provider<N>>(token: Address, provider: &P) -> Result<U256> {
+    let decimals = DECIMALS.resolve(token, provider).await?;
     let multiplier = U256::from(10).pow(U256::from(decimals as u32));
-    U256::from(100_000) * multiplier // Correct conversion
+    Ok(U256::from(100_000) * multiplier)
 }
 
-/// Updates the global `AMOUNT` based on the token
-pub fn update_amount(token_symbol: &str) {
-    let calculated = amount_for_token(token_symbol);
-    let amount_lock = &AMOUNT;
-    let mut amount = amount_lock.write().unwrap();
-    *amount = calculated;
+/// Overrides the input size for cycles starting at `token`
+pub async fn update_amount<N: Network, P: Provider<N>>(token: Address, provider: &P) -> Result<()> {
+    let calculated = amount_for_token(token, provider).await?;
+    AMOUNTS.write().unwrap().set(token, calculated);
+    Ok(())
 }
 
-/// Entry point: starts the workers and main loop
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Load environment variables and logger
-    dotenv::dotenv().ok();
-    env_logger::Builder::new()
-        .filter_module("BaseBuster", LevelFilter::Info)
-        .init();
-
-    info!("Loading and syncing pools...");
+/// Syncs every supported AMM protocol on `chain`, returning the pools and the block they are synced to
+async fn sync_pools(chain: Chain) -> Result<(Vec<Pool>, u64)> {
+    info!("Loading and syncing pools on {}...", chain);
 
     // Initialize pool sync across all supported AMM protocols
     let pool_sync = PoolSync::builder()
@@ -73,20 +55,66 @@ async fn main() -> Result<()> {
             ]
             .into_iter(),
         )
-        .chain(Chain::Base)
+        .chain(chain)
         .build()?;
 
-    let (pools, last_synced_block) = pool_sync.sync_pools().await?;
+    Ok(pool_sync.sync_pools().await?)
+}
 
-    // Start async workers
-    start_workers(pools, last_synced_block).await;
+/// Chains named in a comma separated list such as `base,ethereum`, duplicates are run once
+fn parse_chains(raw: &str) -> Result<Vec<Chain>> {
+    let mut chains = Vec::new();
+    let mut seen = Vec::new();
+    for name in raw.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let (key, chain) = match name.to_ascii_lowercase().as_str() {
+            "base" => ("base", Chain::Base),
+            "ethereum" | "mainnet" => ("ethereum", Chain::Ethereum),
+            other => anyhow::bail!("Unknown chain {} in CHAINS", other),
+        };
+        if !seen.contains(&key) {
+            seen.push(key);
+            chains.push(chain);
+        }
+    }
+    if chains.is_empty() {
+        anyhow::bail!("CHAINS lists no chains");
+    }
+    Ok(chains)
+}
+
+/// Entry point: starts the workers and main loop
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load environment variables and logger
+    dotenv::dotenv().ok();
+    env_logger::Builder::new()
+        .filter_module("BaseBuster", LevelFilter::Info)
+        .init();
 
-    // Loop to keep main thread alive if workers are spawned independently
-    loop {
-        tokio::time::sleep(Duration::from_secs(1000)).await;
+    // One pipeline per chain listed in CHAINS (comma separated, default base)
+    let chains = parse_chains(&std::env::var("CHAINS").unwrap_or_else(|_| "base".to_string()))?;
+    let mut configs = Vec::new();
+    for chain in chains {
+        let (pools, last_synced_block) = sync_pools(chain).await?;
+        configs.push(ChainConfig::from_env(chain, pools, last_synced_block)?);
     }
 
-    // This is never reached unless loop is broken
-    #[allow(unreachable_code)]
+    // Start async workers, returns once they have shut down
+    start_workers(configs).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chains_are_parsed_once_each() {
+        let chains = parse_chains(" base, Ethereum,mainnet,base ").unwrap();
+        assert_eq!(chains.len(), 2);
+        assert!(matches!(chains[0], Chain::Base));
+        assert!(matches!(chains[1], Chain::Ethereum));
+        assert!(parse_chains("base,optimism").is_err());
+        assert!(parse_chains(" , ").is_err());
+    }
+}
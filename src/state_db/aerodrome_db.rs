@@ -0,0 +1,140 @@
+use alloy::network::Network;
+use alloy::primitives::{Address, U256, address};
+use alloy::providers::Provider;
+use alloy::sol;
+use anyhow::{Result, anyhow};
+use log::{debug, trace};
+
+use crate::state_db::BlockStateDB;
+
+// Aerodrome PoolFactory on Base
+pub const AERODROME_FACTORY: Address = address!("420DD381b31aEf6683db6B902084cB0FFECe40Da");
+
+// Factory defaults, used only when a pool's fee was never resolved
+const DEFAULT_STABLE_FEE_BPS: u32 = 5;
+const DEFAULT_VOLATILE_FEE_BPS: u32 = 30;
+
+sol! {
+    #[sol(rpc)]
+    contract AerodromeFactory {
+        function getFee(address pool, bool _stable) external view returns (uint256);
+    }
+}
+
+impl<N, P> BlockStateDB<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    /// Resolves the pool's swap fee from the factory and caches it in basis points.
+    /// `getFee` already applies custom per-pool fees and the zero-fee indicator.
+    pub fn insert_aerodrome_fee(&mut self, pool: Address, stable: bool) -> Result<()> {
+        let factory = AerodromeFactory::new(AERODROME_FACTORY, &self.provider);
+        let fee = self
+            .runtime
+            .block_on(async { factory.getFee(pool, stable).call().await })
+            .map_err(|e| anyhow!("Failed to fetch Aerodrome fee for {}: {:?}", pool, e))?;
+
+        let fee_bps = normalize_fee_bps(fee)?;
+        trace!("Aerodrome fee for {} (stable={}): {} bps", pool, stable, fee_bps);
+        self.aerodrome_fees.insert(pool, fee_bps);
+        Ok(())
+    }
+
+    /// Swap fee of an Aerodrome pool in basis points
+    pub fn get_fee(&self, pool: &Address) -> u32 {
+        if let Some(fee) = self.aerodrome_fees.get(pool) {
+            return *fee;
+        }
+
+        let stable = self.get_stable(pool);
+        // Hot path, the failed lookup was already reported once at insertion
+        debug!("No resolved fee for Aerodrome pool {}, using factory default", pool);
+        if stable {
+            DEFAULT_STABLE_FEE_BPS
+        } else {
+            DEFAULT_VOLATILE_FEE_BPS
+        }
+    }
+
+    /// Whether an Aerodrome pool uses the stable invariant, from the pool_sync metadata stored at insertion
+    pub fn get_stable(&self, pool: &Address) -> bool {
+        self.pool_info
+            .get(pool)
+            .and_then(|info| info.get_v2())
+            .and_then(|v2| v2.stable)
+            .unwrap_or(false)
+    }
+}
+
+/// Factory fees are denominated in basis points (10_000 = 100%), capped at 3% on-chain
+fn normalize_fee_bps(fee: U256) -> Result<u32> {
+    let fee: u32 = fee.try_into().map_err(|_| anyhow!("Aerodrome fee out of range: {}", fee))?;
+    if fee > 10_000 {
+        return Err(anyhow!("Aerodrome fee above 100%: {} bps", fee));
+    }
+    Ok(fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::test_utils::{aerodrome_pool, insert_aerodrome_offline, insert_v2_offline, offline_db};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn aerodrome_pools_read_back_fee_and_stable_flag() {
+        let (token0, token1) = (Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let (stable, volatile) = (Address::repeat_byte(0x12), Address::repeat_byte(0x11));
+        let mut db = offline_db();
+        insert_aerodrome_offline(&mut db, aerodrome_pool(stable, token0, token1, 1_000, 1_000, true), 2);
+        // Offline the factory lookup fails, leaving the fee unresolved
+        insert_v2_offline(&mut db, aerodrome_pool(volatile, token0, token1, 1_000, 1_000, false));
+
+        assert!(db.get_stable(&stable));
+        assert_eq!(db.get_fee(&stable), 2);
+        assert_eq!(db.get_decimals(&stable), (18, 18));
+
+        assert!(!db.get_stable(&volatile));
+        assert_eq!(db.get_fee(&volatile), DEFAULT_VOLATILE_FEE_BPS);
+    }
+
+    #[test]
+    fn factory_fees_are_bounded_basis_points() {
+        assert_eq!(normalize_fee_bps(U256::from(5)).unwrap(), 5);
+        assert_eq!(normalize_fee_bps(U256::ZERO).unwrap(), 0);
+        assert!(normalize_fee_bps(U256::from(10_001)).is_err());
+        assert!(normalize_fee_bps(U256::MAX).is_err());
+    }
+
+    // Run with FULL=<base rpc> AERODROME_TEST_POOL=<pool> cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn fees_are_resolved_from_the_factory() {
+        use crate::state_db::blockstate_db::DEFAULT_PROVIDER_TIMEOUT;
+        use crate::utile::test_utils::full_node_provider;
+
+        sol! {
+            #[sol(rpc)]
+            contract AerodromePool {
+                function stable() external view returns (bool);
+            }
+        }
+
+        let pool: Address = std::env::var("AERODROME_TEST_POOL")
+            .expect("AERODROME_TEST_POOL must name an Aerodrome pool")
+            .parse()
+            .unwrap();
+        let provider = full_node_provider();
+        let stable = AerodromePool::new(pool, &provider).stable().call().await.unwrap();
+        let factory_fee = AerodromeFactory::new(AERODROME_FACTORY, &provider)
+            .getFee(pool, stable)
+            .call()
+            .await
+            .unwrap();
+
+        let mut db = BlockStateDB::new(provider, DEFAULT_PROVIDER_TIMEOUT).unwrap();
+        db.insert_aerodrome_fee(pool, stable).unwrap();
+        assert_eq!(U256::from(db.get_fee(&pool)), factory_fee);
+        assert_eq!(db.aerodrome_fees.get(&pool).copied(), Some(normalize_fee_bps(factory_fee).unwrap()));
+    }
+}
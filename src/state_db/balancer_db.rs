@@ -0,0 +1,182 @@
+use alloy::network::Network;
+use alloy::primitives::{Address, B256, U256, address};
+use alloy::providers::Provider;
+use alloy::sol;
+use log::{trace, warn};
+use pool_sync::{BalancerV2Pool, Pool};
+
+use crate::state_db::BlockStateDB;
+use crate::utile::decimals::DECIMALS;
+
+// Balancer V2 Vault, holds the balances of every pool
+pub const BALANCER_VAULT: Address = address!("BA12222222228d8Ba445958a75a0704d566BF2C8");
+
+sol! {
+    #[sol(rpc)]
+    contract BalancerPoolId {
+        function getPoolId() external view returns (bytes32);
+    }
+
+    #[sol(rpc)]
+    contract BalancerVault {
+        function getPoolTokens(bytes32 poolId) external view returns (address[] tokens, uint256[] balances, uint256 lastChangeBlock);
+    }
+}
+
+/// Weighted-pool parameters, balances upscaled to 18 decimals as the Vault math expects
+#[derive(Debug, Clone, Default)]
+pub struct BalancerPoolState {
+    /// Vault pool id, zero when it couldn't be resolved and the balances can't be refreshed
+    pub pool_id: B256,
+    pub tokens: Vec<Address>,
+    pub decimals: Vec<u8>,
+    pub balances: Vec<U256>,
+    pub weights: Vec<U256>,
+    pub swap_fee: U256,
+}
+
+impl<N, P> BlockStateDB<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    /// Inserts a Balancer V2 weighted pool, keeping its Vault-side state in `balancer_pools`
+    pub fn insert_balancer(&mut self, pool: BalancerV2Pool) {
+        let address = pool.address;
+        trace!("Balancer DB: inserting pool {}", address);
+
+        let tokens = pool.get_tokens();
+        // Decimals resolved earlier (possibly on-chain) win over the pool's listing
+        let decimals: Vec<u8> = [pool.token0_decimals, pool.token1_decimals]
+            .into_iter()
+            .chain(pool.additional_token_decimals.iter().copied())
+            .zip(&tokens)
+            .map(|(listed, token)| DECIMALS.get_or_record(*token, listed))
+            .collect();
+
+        if tokens.len() != pool.balances.len() || tokens.len() != pool.weights.len() {
+            warn!(
+                "Balancer pool {} has {} tokens but {} balances / {} weights, skipping",
+                address,
+                tokens.len(),
+                pool.balances.len(),
+                pool.weights.len()
+            );
+            return;
+        }
+
+        let balances = pool
+            .balances
+            .iter()
+            .zip(&decimals)
+            .map(|(balance, decimals)| upscale(*balance, *decimals))
+            .collect();
+
+        let contract = BalancerPoolId::new(address, &self.provider);
+        let pool_id = self
+            .runtime
+            .block_on(async { contract.getPoolId().call().await })
+            .unwrap_or_else(|e| {
+                warn!("Failed to fetch Balancer pool id for {}, balances won't refresh: {:?}", address, e);
+                B256::ZERO
+            });
+
+        let state = BalancerPoolState {
+            pool_id,
+            tokens,
+            decimals,
+            balances,
+            weights: pool.weights.clone(),
+            swap_fee: pool.swap_fee,
+        };
+
+        self.add_pool(Pool::BalancerV2(pool));
+        self.balancer_pools.insert(address, state);
+    }
+
+    /// Vault pool ids of the tracked Balancer pools, pools without a resolved id are left out
+    pub fn balancer_pool_ids(&self) -> Vec<(Address, B256)> {
+        self.balancer_pools
+            .iter()
+            .filter(|(_, state)| !state.pool_id.is_zero())
+            .map(|(pool, state)| (*pool, state.pool_id))
+            .collect()
+    }
+
+    /// Replaces a pool's balances with raw Vault balances, returns whether anything changed
+    pub fn update_balancer_balances(&mut self, pool: &Address, balances: &[U256]) -> bool {
+        let Some(state) = self.balancer_pools.get_mut(pool) else {
+            return false;
+        };
+        if balances.len() != state.decimals.len() {
+            warn!(
+                "Balancer pool {} returned {} balances for {} tokens, keeping the old ones",
+                pool,
+                balances.len(),
+                state.decimals.len()
+            );
+            return false;
+        }
+
+        let upscaled: Vec<U256> = balances
+            .iter()
+            .zip(&state.decimals)
+            .map(|(balance, decimals)| upscale(*balance, *decimals))
+            .collect();
+        if upscaled == state.balances {
+            return false;
+        }
+        state.balances = upscaled;
+        true
+    }
+
+    /// Pool tokens in Vault registration order
+    pub fn get_balancer_tokens(&self, pool: &Address) -> Vec<Address> {
+        self.balancer_pools
+            .get(pool)
+            .map(|state| state.tokens.clone())
+            .unwrap_or_default()
+    }
+
+    /// Token decimals in Vault registration order
+    pub fn get_balancer_decimals(&self, pool: &Address) -> Vec<u8> {
+        self.balancer_pools
+            .get(pool)
+            .map(|state| state.decimals.clone())
+            .unwrap_or_default()
+    }
+
+    /// Token balances upscaled to 18 decimals
+    pub fn get_balancer_balances(&self, pool: &Address) -> Vec<U256> {
+        self.balancer_pools
+            .get(pool)
+            .map(|state| state.balances.clone())
+            .unwrap_or_default()
+    }
+
+    /// Normalized weights, 1e18-scaled and summing to 1e18
+    pub fn get_balancer_weights(&self, pool: &Address) -> Vec<U256> {
+        self.balancer_pools
+            .get(pool)
+            .map(|state| state.weights.clone())
+            .unwrap_or_default()
+    }
+
+    /// Swap fee percentage, 1e18-scaled
+    pub fn get_balancer_fee(&self, pool: &Address) -> U256 {
+        self.balancer_pools
+            .get(pool)
+            .map(|state| state.swap_fee)
+            .unwrap_or_default()
+    }
+}
+
+// Vault math runs on 18-decimal fixed point, so lower-decimal balances are scaled up
+pub(crate) fn upscale(balance: U256, decimals: u8) -> U256 {
+    balance * U256::from(10).pow(U256::from(18u8.saturating_sub(decimals)))
+}
+
+// Back from 18-decimal fixed point to the token's own decimals, rounding down like the Vault does for outputs
+pub(crate) fn downscale(amount: U256, decimals: u8) -> U256 {
+    amount / U256::from(10).pow(U256::from(18u8.saturating_sub(decimals)))
+}
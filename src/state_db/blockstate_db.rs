@@ -5,6 +5,7 @@ use alloy::primitives::{Address, BlockNumber, B256, U256};
 use alloy::providers::Provider;
 use alloy::rpc::types::BlockId;
 use alloy::rpc::types::trace::geth::AccountState as GethAccountState;
+use alloy::transports::TransportError;
 use anyhow::Result;
 use pool_sync::{Pool, PoolInfo};
 use revm::{Database, DatabaseRef, Evm};
@@ -12,7 +13,10 @@ use revm::db::AccountState;
 use revm::primitives::{Account, AccountInfo, Bytecode, Log, KECCAK_EMPTY};
 use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
+use std::time::Duration;
 use tokio::runtime::{Handle, Runtime};
+use crate::state_db::balancer_db::BalancerPoolState;
+use crate::state_db::maverick_db::MaverickPoolState;
 
 // Handles either a current thread Handle or a dedicated Runtime 
 #[derive(Debug)]
@@ -31,6 +35,19 @@ impl HandleOrRuntime {
     }
 }
 
+// Provider reads slower than this fail instead of stalling the simulation holding the db lock
+pub const DEFAULT_PROVIDER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Why a provider backed read failed
+#[derive(Debug, thiserror::Error)]
+pub enum DBTransportError {
+    #[error(transparent)]
+    Transport(#[from] TransportError),
+
+    #[error("Provider call timed out after {0:?}")]
+    Timeout(Duration),
+}
+
 #[derive(Debug)]
 pub struct BlockStateDB< N: Network, P: Provider<N>> {
     pub accounts: HashMap<Address, BlockStateDBAccount>,
@@ -39,8 +56,24 @@ pub struct BlockStateDB< N: Network, P: Provider<N>> {
     pub block_hashes: HashMap<BlockNumber, B256>,
     pub pools: HashSet<Address>,
     pub pool_info: HashMap<Address, Pool>,
-    provider: P,
-    runtime: HandleOrRuntime,
+    /// Aerodrome swap fees in basis points, resolved from the factory at insertion
+    pub aerodrome_fees: HashMap<Address, u32>,
+    /// Curve pools quoted through `get_dy_underlying`
+    pub curve_metapools: HashSet<Address>,
+    /// Curve pool coins in on-chain index order, resolves `(i, j)` for a token pair
+    pub curve_tokens: HashMap<Address, Vec<Address>>,
+    /// Balancer V2 pool tokens, balances, weights and fees
+    pub balancer_pools: HashMap<Address, BalancerPoolState>,
+    /// Maverick V1/V2 pool tokens and bin spacing
+    pub maverick_pools: HashMap<Address, MaverickPoolState>,
+    /// Block in which each pool's storage last changed
+    pub pool_last_updated: HashMap<Address, u64>,
+    pub(crate) provider: P,
+    pub(crate) runtime: HandleOrRuntime,
+    /// Upper bound on every provider call made while serving a read
+    provider_timeout: Duration,
+    /// Block uncached accounts and slots are read at
+    read_block: BlockId,
     _marker: PhantomData<fn() -> N>,
 }
 
@@ -49,8 +82,8 @@ where
     N: Network,
     P: Provider<N>,
 {
-    /// Construct a new BlockStateDB with appropriate runtime handle
-    pub fn new(provider: P) -> Option<Self> {
+    /// Construct a new BlockStateDB with appropriate runtime handle, provider reads give up after `provider_timeout`
+    pub fn new(provider: P, provider_timeout: Duration) -> Option<Self> {
         debug!("Creating new BlockStateDB");
 
         let mut contracts = HashMap::new();
@@ -72,12 +105,61 @@ where
             block_hashes: HashMap::new(),
             pools: HashSet::new(),
             pool_info: HashMap::new(),
+            aerodrome_fees: HashMap::new(),
+            curve_metapools: HashSet::new(),
+            curve_tokens: HashMap::new(),
+            balancer_pools: HashMap::new(),
+            maverick_pools: HashMap::new(),
+            pool_last_updated: HashMap::new(),
             provider,
             runtime,
+            provider_timeout,
+            read_block: BlockId::latest(),
             _marker: PhantomData,
         })
     }
 
+    /// Reads accounts and slots missing from the db at `block` instead of the latest block
+    pub fn with_read_block(mut self, block: BlockId) -> Self {
+        self.read_block = block;
+        self
+    }
+
+    /// Throwaway copy of every account, slot and pool record, sharing the provider.
+    /// Writes to the copy never reach this db, None outside a multi-threaded runtime
+    pub fn snapshot(&self) -> Option<Self>
+    where
+        P: Clone,
+    {
+        let mut db = Self::new(self.provider.clone(), self.provider_timeout)?;
+        db.accounts = self.accounts.clone();
+        db.contracts = self.contracts.clone();
+        db.block_hashes = self.block_hashes.clone();
+        db.pools = self.pools.clone();
+        db.pool_info = self.pool_info.clone();
+        db.aerodrome_fees = self.aerodrome_fees.clone();
+        db.curve_metapools = self.curve_metapools.clone();
+        db.curve_tokens = self.curve_tokens.clone();
+        db.balancer_pools = self.balancer_pools.clone();
+        db.maverick_pools = self.maverick_pools.clone();
+        db.pool_last_updated = self.pool_last_updated.clone();
+        db.read_block = self.read_block;
+        Some(db)
+    }
+
+    /// Drives `fut` to completion, failing with `DBTransportError::Timeout` once `provider_timeout` passes
+    fn block_on_timeout<F: std::future::Future + Send>(&self, fut: F) -> Result<F::Output, DBTransportError>
+    where
+        F::Output: Send,
+    {
+        self.runtime
+            .block_on(tokio::time::timeout(self.provider_timeout, fut))
+            .map_err(|_| {
+                warn!("Provider call timed out after {:?}", self.provider_timeout);
+                DBTransportError::Timeout(self.provider_timeout)
+            })
+    }
+
     /// Add a new pool to the DB (fetch on-chain account, store it with type)
     pub fn add_pool(&mut self, pool: Pool) {
         let pool_address = pool.address();
@@ -112,23 +194,42 @@ where
         self.pool_info.get(pool).map(|info| info.token0_address() == token_in)
     }
 
+    /// (token0, token1) decimals recorded from pool_sync at insertion, (0, 0) for unknown pools
+    #[inline]
+    pub fn get_decimals(&self, pool: &Address) -> (u8, u8) {
+        self.pool_info
+            .get(pool)
+            .map(|info| (info.token0_decimals(), info.token1_decimals()))
+            .unwrap_or_else(|| {
+                warn!("No pool info for {}, decimals unknown", pool);
+                (0, 0)
+            })
+    }
+
     /// Update all storage slots for a given account from a block trace
     #[inline]
+    /// Returns the slots whose value actually changed, traces also report slots that were only read
     pub fn update_all_slots(
         &mut self,
         address: Address,
         account_state: GethAccountState,
-    ) -> Result<()> {
+    ) -> Result<Vec<U256>> {
         trace!("Updating storage for address {}", address);
-        for (slot, value) in account_state.storage {
-            if let Some(account) = self.accounts.get_mut(&address) {
-                account.storage.insert(slot.into(), BlockStateDBSlot {
-                    value: value.into(),
+        let mut changed = Vec::new();
+        if let Some(account) = self.accounts.get_mut(&address) {
+            for (slot, value) in account_state.storage {
+                let slot: U256 = slot.into();
+                let value: U256 = value.into();
+                let previous = account.storage.insert(slot, BlockStateDBSlot {
+                    value,
                     insertion_type: InsertionType::Custom,
                 });
+                if previous.map_or(true, |prev| prev.value != value) {
+                    changed.push(slot);
+                }
             }
         }
-        Ok(())
+        Ok(changed)
     }
 
     /// Direct insert of an account into the state DB
@@ -243,12 +344,12 @@ where
 
         // fetch fresh data from provider
         let fut = async {
-            let nonce = self.provider.get_transaction_count(address).block_id(BlockId::latest());
-            let balance = self.provider.get_balance(address).block_id(BlockId::latest());
-            let code = self.provider.get_code_at(address).block_id(BlockId::latest());
+            let nonce = self.provider.get_transaction_count(address).block_id(self.read_block);
+            let balance = self.provider.get_balance(address).block_id(self.read_block);
+            let code = self.provider.get_code_at(address).block_id(self.read_block);
             tokio::join!(nonce, balance, code)
         };
-        let (nonce, balance, code) = self.runtime.block_on(fut);
+        let (nonce, balance, code) = self.block_on_timeout(fut)?;
         match (nonce, balance, code) {
             (Ok(n), Ok(b), Ok(c)) => {
                 let bytecode = Bytecode::new_raw(c.0.into());
@@ -263,12 +364,17 @@ where
         self.contracts
             .get(&code_hash)
             .cloned()
-            .ok_or_else(|| TransportError::Custom("Missing code hash".into()))
+            .ok_or_else(|| TransportError::Custom("Missing code hash".into()).into())
     }
 
     fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
-        let fut = self.provider.get_storage_at(address, index);
-        Ok(self.runtime.block_on(fut.into_future())?)
+        // Simulation overlays read through here, synced slots must win over the provider
+        if let Some(slot) = self.accounts.get(&address).and_then(|acc| acc.storage.get(&index)) {
+            return Ok(slot.value);
+        }
+
+        let fut = self.provider.get_storage_at(address, index).block_id(self.read_block);
+        Ok(self.block_on_timeout(fut.into_future())??)
     }
 
     fn block_hash_ref(&self, number: BlockNumber) -> Result<B256, Self::Error> {
@@ -276,10 +382,10 @@ where
             return Ok(*hash);
         }
 
-        let block = self.runtime.block_on(
+        let block = self.block_on_timeout(
             self.provider
                 .get_block_by_number(number.into(), false.into()),
-        )?;
+        )??;
         Ok(block.map(|b| B256::new(*b.header().hash())).unwrap_or(B256::ZERO))
     }
 }
@@ -338,20 +444,20 @@ where
     }
 }
 
-#[derive(Default, Debug, Eq, PartialEq)]
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub struct BlockStateDBSlot {
     pub value: U256,
     pub insertion_type: InsertionType,
 }
 
-#[derive(Default, Debug, Eq, PartialEq)]
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub enum InsertionType {
     Custom,
     #[default]
     OnChain,
 }
 
-#[derive(Default, Debug, Eq, PartialEq)]
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub struct BlockStateDBAccount {
     pub info: AccountInfo,
     pub state: AccountState,
@@ -369,3 +475,28 @@ impl BlockStateDBAccount {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::providers::RootProvider;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    // A node that accepts the connection but never answers, each read has to give up on its own
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reads_from_a_stalled_provider_time_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let provider = RootProvider::new_http(url.parse().unwrap());
+        let timeout = Duration::from_millis(200);
+        let db = BlockStateDB::new(provider, timeout).unwrap();
+        let account = Address::repeat_byte(0x77);
+
+        let started = Instant::now();
+        assert!(matches!(db.basic_ref(account), Err(DBTransportError::Timeout(t)) if t == timeout));
+        assert!(matches!(db.storage_ref(account, U256::ZERO), Err(DBTransportError::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(listener);
+    }
+}
@@ -0,0 +1,90 @@
+use alloy::network::Network;
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use alloy::sol;
+use anyhow::Result;
+use log::trace;
+use pool_sync::{Pool, PoolInfo};
+
+use crate::state_db::BlockStateDB;
+
+sol! {
+    #[sol(rpc)]
+    contract CurveMetaPool {
+        function base_pool() external view returns (address);
+    }
+}
+
+impl<N, P> BlockStateDB<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    /// Insert a Curve pool, flagging it as a metapool if it exposes a base pool.
+    /// Plain pools don't implement `base_pool()`, so a reverted call just means "not a metapool".
+    pub fn insert_curve(&mut self, pool: Pool) -> Result<()> {
+        let address = pool.address();
+
+        // pool_sync lists coins in the pool's own index order
+        let tokens = match &pool {
+            Pool::CurveTriCrypto(curve_pool) => curve_pool.get_tokens(),
+            _ => vec![pool.token0_address(), pool.token1_address()],
+        };
+        trace!("Curve pool {} coins: {:?}", address, tokens);
+        self.curve_tokens.insert(address, tokens);
+        self.add_pool(pool);
+
+        let meta = CurveMetaPool::new(address, &self.provider);
+        let base_pool = self
+            .runtime
+            .block_on(async { meta.base_pool().call().await })
+            .ok()
+            .filter(|base| *base != Address::ZERO);
+
+        if let Some(base_pool) = base_pool {
+            trace!("Curve pool {} is a metapool over {}", address, base_pool);
+            self.curve_metapools.insert(address);
+        }
+        Ok(())
+    }
+
+    /// Coin indices `(i, j)` of a Curve swap from `token_in` to `token_out`
+    pub fn curve_indices(&self, pool: &Address, token_in: Address, token_out: Address) -> Option<(U256, U256)> {
+        let tokens = self.curve_tokens.get(pool)?;
+        let i = tokens.iter().position(|token| *token == token_in)?;
+        let j = tokens.iter().position(|token| *token == token_out)?;
+        if i == j {
+            return None;
+        }
+        Some((U256::from(i), U256::from(j)))
+    }
+
+    /// Whether `pool` was flagged as a Curve metapool at insertion
+    #[inline]
+    pub fn is_curve_metapool(&self, pool: &Address) -> bool {
+        self.curve_metapools.contains(pool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::test_utils::offline_db;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn every_ordered_pair_of_a_three_coin_pool_maps_to_its_indices() {
+        let pool = Address::repeat_byte(0x55);
+        let coins = [Address::repeat_byte(0x0a), Address::repeat_byte(0x0b), Address::repeat_byte(0x0c)];
+        let mut db = offline_db();
+        db.curve_tokens.insert(pool, coins.to_vec());
+
+        for (i, token_in) in coins.iter().enumerate() {
+            for (j, token_out) in coins.iter().enumerate() {
+                let expected = (i != j).then(|| (U256::from(i), U256::from(j)));
+                assert_eq!(db.curve_indices(&pool, *token_in, *token_out), expected, "{i} -> {j}");
+            }
+        }
+        assert_eq!(db.curve_indices(&pool, coins[0], Address::repeat_byte(0x0d)), None);
+        assert_eq!(db.curve_indices(&Address::repeat_byte(0x56), coins[0], coins[1]), None);
+    }
+}
@@ -0,0 +1,207 @@
+use alloy::network::Network;
+use alloy::primitives::{Address, B256, U256};
+use alloy::providers::Provider;
+use alloy::sol;
+use anyhow::{Result, anyhow};
+use log::{trace, warn};
+use pool_sync::{Pool, PoolInfo, PoolType};
+use revm::DatabaseRef;
+
+use crate::state_db::{BlockStateDB, BlockStateDBAccount, InsertionType};
+
+sol! {
+    #[sol(rpc)]
+    contract MaverickPool {
+        function tickSpacing() external view returns (uint256);
+        function tokenA() external view returns (address);
+        function tokenB() external view returns (address);
+    }
+}
+
+/// Token and bin layout of a Maverick pool, tokenA is the pool's token0
+#[derive(Debug, Clone, Default)]
+pub struct MaverickPoolState {
+    pub token_a: Address,
+    pub token_b: Address,
+    /// Width of a bin in ticks, None when the pool didn't answer `tickSpacing()`
+    pub tick_spacing: Option<U256>,
+}
+
+impl<N, P> BlockStateDB<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    /// Inserts a Maverick V1/V2 pool with its on-chain bytecode, so `calculateSwap` runs against real code.
+    /// V2 pools are quoted through `v2_quoter`, whose code is fetched alongside the first V2 pool
+    pub fn insert_maverick(&mut self, pool: Pool, v2_quoter: Address) -> Result<()> {
+        let address = pool.address();
+        let pool_type = pool.pool_type();
+        let state = MaverickPoolState {
+            token_a: pool.token0_address(),
+            token_b: pool.token1_address(),
+            tick_spacing: None,
+        };
+        self.add_pool(pool);
+        self.record_maverick(address, pool_type, state, v2_quoter)
+    }
+
+    /// Caches the code of an inserted Maverick pool account and records its metadata
+    fn record_maverick(
+        &mut self,
+        address: Address,
+        pool_type: PoolType,
+        state: MaverickPoolState,
+        v2_quoter: Address,
+    ) -> Result<()> {
+        let code_hash = self
+            .cache_code(address)
+            .ok_or_else(|| anyhow!("Maverick pool {} has no bytecode", address))?;
+        trace!("Maverick pool {} code {}", address, code_hash);
+
+        if pool_type == PoolType::MaverickV2 && !self.accounts.contains_key(&v2_quoter) {
+            if let Ok(Some(info)) = <Self as DatabaseRef>::basic_ref(self, v2_quoter) {
+                self.accounts.insert(v2_quoter, BlockStateDBAccount {
+                    info,
+                    insertion_type: InsertionType::OnChain,
+                    ..Default::default()
+                });
+            }
+            if self.cache_code(v2_quoter).is_none() {
+                warn!("Maverick V2 quoter {} has no bytecode, V2 quotes will fail", v2_quoter);
+            }
+        }
+
+        let contract = MaverickPool::new(address, &self.provider);
+        let tick_spacing = self
+            .runtime
+            .block_on(async { contract.tickSpacing().call().await })
+            .ok();
+        if tick_spacing.is_none() {
+            warn!("Failed to fetch tick spacing of Maverick pool {}", address);
+        }
+
+        self.maverick_pools.insert(address, MaverickPoolState { tick_spacing, ..state });
+        Ok(())
+    }
+
+    /// Token and bin metadata recorded at insertion
+    #[inline]
+    pub fn maverick_pool(&self, pool: &Address) -> Option<&MaverickPoolState> {
+        self.maverick_pools.get(pool)
+    }
+
+    /// Registers the bytecode of an already inserted account under its hash, returning the hash if it has code
+    fn cache_code(&mut self, address: Address) -> Option<B256> {
+        let info = &self.accounts.get(&address)?.info;
+        let code = info.code.clone().filter(|code| !code.is_empty())?;
+        let code_hash = info.code_hash;
+        self.contracts.entry(code_hash).or_insert(code);
+        Some(code_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::Calculator;
+    use crate::state_db::blockstate_db::DEFAULT_PROVIDER_TIMEOUT;
+    use crate::utile::MarketState;
+    use crate::utile::constant::{Deployment, MAX_TICK, MIN_TICK};
+    use crate::utile::market_state::SimConfig;
+    use crate::utile::test_utils::{full_node_provider, market_state_with};
+    use alloy::eips::BlockNumberOrTag;
+    use alloy::network::Ethereum;
+    use alloy::providers::RootProvider;
+    use pool_sync::Chain;
+    use std::sync::Arc;
+
+    sol! {
+        #[sol(rpc)]
+        contract MaverickV1Quote {
+            function calculateSwap(uint128 amount, bool tokenAIn, bool exactOutput, int32 tickLimit)
+                external view returns (uint256 amountIn, uint256 amountOut);
+        }
+
+        #[sol(rpc)]
+        contract MaverickV2Quote {
+            function calculateSwap(address pool, uint128 amount, bool tokenAIn, bool exactOutput, int32 tickLimit)
+                external returns (uint256 amountIn, uint256 amountOut, uint256 gasEstimate);
+        }
+    }
+
+    /// Market state at the latest block holding the Maverick pool named by `var`. Its metadata is read
+    /// on-chain and its account goes in the way `add_pool` puts it. Returns the pool, its tokenA and the block
+    async fn inserted_pool(var: &str, pool_type: PoolType) -> (Arc<MarketState<Ethereum, RootProvider>>, Address, Address, u64) {
+        let pool: Address = std::env::var(var)
+            .unwrap_or_else(|_| panic!("{var} must name a {pool_type:?} pool"))
+            .parse()
+            .unwrap();
+        let provider = full_node_provider();
+        let contract = MaverickPool::new(pool, &provider);
+        let state = MaverickPoolState {
+            token_a: contract.tokenA().call().await.unwrap(),
+            token_b: contract.tokenB().call().await.unwrap(),
+            tick_spacing: None,
+        };
+        let token_a = state.token_a;
+        let block = provider.get_block_by_number(BlockNumberOrTag::Latest).await.unwrap().unwrap();
+
+        let quoter = Deployment::simulation_only(Chain::Base).maverick_v2_quoter;
+        let mut db = BlockStateDB::new(provider, DEFAULT_PROVIDER_TIMEOUT).unwrap();
+        let info = <BlockStateDB<_, _> as DatabaseRef>::basic_ref(&db, pool).unwrap().unwrap();
+        db.accounts.insert(pool, BlockStateDBAccount { info, insertion_type: InsertionType::OnChain, ..Default::default() });
+        db.record_maverick(pool, pool_type, state, quoter).unwrap();
+        assert!(db.maverick_pool(&pool).unwrap().tick_spacing.is_some());
+        assert!(db.contracts.contains_key(&db.accounts[&pool].info.code_hash));
+
+        let market_state = market_state_with(db, SimConfig::for_chain(Chain::Base));
+        {
+            let mut block_env = market_state.block_env.write().unwrap();
+            block_env.number = U256::from(block.header.number);
+            block_env.timestamp = U256::from(block.header.timestamp);
+        }
+        (market_state, pool, token_a, block.header.number)
+    }
+
+    // Run with FULL=<base rpc> MAVERICK_TEST_POOL=<v1 pool> cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn inserted_pools_simulate_calculate_swap() {
+        let (market_state, pool, token_a, block) = inserted_pool("MAVERICK_TEST_POOL", PoolType::MaverickV1).await;
+        let amount_in = crate::utile::amount_for(token_a);
+        // No limit in the tokenA -> tokenB direction, as `maverick_tick_candidates` defaults to
+        let tick_limit = MIN_TICK;
+        let on_chain = MaverickV1Quote::new(pool, full_node_provider())
+            .calculateSwap(amount_in.to::<u128>(), true, false, tick_limit)
+            .block(block.into())
+            .call()
+            .await
+            .unwrap()
+            .amountOut;
+
+        let simulated = Calculator::new(market_state).maverick_v1_out(amount_in, pool, true, tick_limit);
+        assert!(simulated > U256::ZERO);
+        assert_eq!(simulated, on_chain);
+    }
+
+    // Quoted through the V2 quoter with no tick limit, which defaults to the protocol bound. Run with
+    // FULL=<base rpc> MAVERICK_V2_TEST_POOL=<v2 pool> cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn inserted_v2_pools_simulate_through_the_quoter() {
+        let (market_state, pool, token_a, block) = inserted_pool("MAVERICK_V2_TEST_POOL", PoolType::MaverickV2).await;
+        let amount_in = crate::utile::amount_for(token_a);
+        let on_chain = MaverickV2Quote::new(market_state.deployment.maverick_v2_quoter, full_node_provider())
+            .calculateSwap(pool, amount_in.to::<u128>(), true, false, MAX_TICK)
+            .block(block.into())
+            .call()
+            .await
+            .unwrap()
+            .amountOut;
+
+        let simulated = Calculator::new(market_state).maverick_v2_out(amount_in, pool, true, None);
+        assert!(simulated > U256::ZERO);
+        assert_eq!(simulated, on_chain);
+    }
+}
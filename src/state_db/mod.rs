@@ -1 +1,9 @@
+pub mod aerodrome_db;
+pub mod balancer_db;
+pub mod blockstate_db;
+pub mod curve_db;
+pub mod maverick_db;
+pub mod v2_db;
+pub mod v3_db;
+
 pub use blockstate_db::{BlockStateDB, BlockStateDBAccount, BlockStateDBSlot, InsertionType};
@@ -1,9 +1,9 @@
 use alloy::network::Network;
 use alloy::primitives::{Address, U256};
 use alloy::providers::Provider;
-use log::trace;
+use log::{trace, warn};
 use lazy_static::lazy_static;
-use pool_sync::{Pool, PoolInfo};
+use pool_sync::{Pool, PoolInfo, PoolType};
 use revm::DatabaseRef;
 
 use crate::state_db::BlockStateDB;
@@ -36,6 +36,13 @@ where
         self.insert_reserves(address, reserve0, reserve1);
         self.insert_token0(address, token0);
         self.insert_token1(address, token1);
+
+        if pool.pool_type() == PoolType::Aerodrome {
+            let stable = v2_info.stable.unwrap_or(false);
+            if let Err(e) = self.insert_aerodrome_fee(address, stable) {
+                warn!("{:?}", e);
+            }
+        }
     }
 
     /// Reads packed V2-style reserves from storage slot 8
@@ -58,10 +65,11 @@ where
         Address::from_word(raw.into())
     }
 
-    /// [Future] Add V2 token fetch logic via full ABI if needed
-    #[allow(dead_code)]
-    pub fn get_tokens(&self, _pool: &Address) -> (Address, Address) {
-        todo!("If needed for ABI resolution or extra asserts")
+    /// (token0, token1) from the pool metadata stored at insertion, avoids the storage reads of `get_token0`/`get_token1`
+    pub fn get_tokens(&self, pool: &Address) -> Option<(Address, Address)> {
+        self.pool_info
+            .get(pool)
+            .map(|info| (info.token0_address(), info.token1_address()))
     }
 
     /// Helper: inserts packed reserve0 + reserve1 into storage slot 8
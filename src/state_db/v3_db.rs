@@ -2,9 +2,10 @@ use alloy::sol;
 use alloy::network::Network;
 use alloy::primitives::{keccak256, Address, I256, U160, U256};
 use alloy::providers::Provider;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use log::trace;
 use pool_sync::{Pool, PoolInfo};
+use revm::DatabaseRef;
 use crate::state_db::BlockStateDB;
 use crate::state_db::blockstate_db::{InsertionType, BlockStateDBSlot};
 
@@ -34,6 +35,70 @@ sol!(
 );
 
  pub use UniswapV3;
+
+// Bit offsets of the packed slot0 fields
+const TICK_OFFSET: usize = 160;
+const OBSERVATION_INDEX_OFFSET: usize = TICK_OFFSET + 24;
+const OBSERVATION_CARDINALITY_OFFSET: usize = OBSERVATION_INDEX_OFFSET + 16;
+const OBSERVATION_CARDINALITY_NEXT_OFFSET: usize = OBSERVATION_CARDINALITY_OFFSET + 16;
+const FEE_PROTOCOL_OFFSET: usize = OBSERVATION_CARDINALITY_NEXT_OFFSET + 16;
+const UNLOCKED_OFFSET: usize = FEE_PROTOCOL_OFFSET + 8;
+
+/// Uniswap V3 slot0, the single source of truth for how it packs into storage slot 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot0 {
+    pub sqrt_price_x96: U160,
+    pub tick: i32,
+    pub observation_index: u16,
+    pub observation_cardinality: u16,
+    pub observation_cardinality_next: u16,
+    pub fee_protocol: u8,
+    pub unlocked: bool,
+}
+
+impl Slot0 {
+    pub fn decode(word: U256) -> Self {
+        let tick_bits = ((word >> TICK_OFFSET) & *BITS24MASK).to::<u32>();
+        // Sign-extend the 24 bit tick
+        let tick = ((tick_bits << 8) as i32) >> 8;
+
+        Self {
+            sqrt_price_x96: U160::from(word & *BITS160MASK),
+            tick,
+            observation_index: ((word >> OBSERVATION_INDEX_OFFSET) & *BITS16MASK).to::<u16>(),
+            observation_cardinality: ((word >> OBSERVATION_CARDINALITY_OFFSET) & *BITS16MASK).to::<u16>(),
+            observation_cardinality_next: ((word >> OBSERVATION_CARDINALITY_NEXT_OFFSET) & *BITS16MASK)
+                .to::<u16>(),
+            fee_protocol: ((word >> FEE_PROTOCOL_OFFSET) & *BITS8MASK).to::<u8>(),
+            unlocked: !((word >> UNLOCKED_OFFSET) & *BITS1MASK).is_zero(),
+        }
+    }
+
+    pub fn encode(&self) -> U256 {
+        U256::from(self.sqrt_price_x96)
+            | ((U256::from(self.tick as u32) & *BITS24MASK) << TICK_OFFSET)
+            | (U256::from(self.observation_index) << OBSERVATION_INDEX_OFFSET)
+            | (U256::from(self.observation_cardinality) << OBSERVATION_CARDINALITY_OFFSET)
+            | (U256::from(self.observation_cardinality_next) << OBSERVATION_CARDINALITY_NEXT_OFFSET)
+            | (U256::from(self.fee_protocol) << FEE_PROTOCOL_OFFSET)
+            | (U256::from(self.unlocked) << UNLOCKED_OFFSET)
+    }
+}
+
+/// Storage slot of `tickBitmap[word_pos]` (mapping at slot 6)
+pub fn tick_bitmap_slot(word_pos: i16) -> Result<U256> {
+    let mut key = I256::try_from(word_pos)?.to_be_bytes::<32>().to_vec();
+    key.extend(U256::from(6).to_be_bytes::<32>());
+    Ok(U256::from_be_bytes(keccak256(&key).into()))
+}
+
+/// Storage slot of `ticks[tick]` (mapping at slot 5), liquidityNet lives in the upper 128 bits
+pub fn tick_info_slot(tick: i32) -> Result<U256> {
+    let mut key = I256::try_from(tick)?.to_be_bytes::<32>().to_vec();
+    key.extend(U256::from(5).to_be_bytes::<32>());
+    Ok(U256::from_be_bytes(keccak256(&key).into()))
+}
+
 // === V3 Pool Insertion Logic ===
 impl<N, P> BlockStateDB<N, P>
 where
@@ -63,12 +128,10 @@ where
 
     fn insert_tick_bitmap(&mut self, pool: Address, tick: i16, bitmap: U256) -> Result<()> {
         trace!("Insert Tick Bitmap: {} @ Tick {}", pool, tick);
-        let mut key = I256::try_from(tick)?.to_be_bytes::<32>().to_vec();
-        key.extend(U256::from(6).to_be_bytes::<32>());
-        let slot = keccak256(&key);
+        let slot = tick_bitmap_slot(tick)?;
 
         let account = self.accounts.get_mut(&pool).expect("Pool not found in DB");
-        account.storage.insert(U256::from_be_bytes(slot.into()), BlockStateDBSlot {
+        account.storage.insert(slot, BlockStateDBSlot {
             value: bitmap,
             insertion_type: InsertionType::Custom,
         });
@@ -79,15 +142,12 @@ where
     fn insert_tick_liquidity_net(&mut self, pool: Address, tick: i32, liquidity_net: i128) -> Result<()> {
         trace!("Insert Tick Liquidity: {} @ Tick {}", pool, tick);
         let unsigned = liquidity_net as u128;
-
-        let mut key = I256::try_from(tick)?.to_be_bytes::<32>().to_vec();
-        key.extend(U256::from(5).to_be_bytes::<32>());
-        let slot = keccak256(&key);
+        let slot = tick_info_slot(tick)?;
 
         let shifted = U256::from(unsigned) << 128;
 
         let account = self.accounts.get_mut(&pool).expect("Pool not found in DB");
-        account.storage.insert(U256::from_be_bytes(slot.into()), BlockStateDBSlot {
+        account.storage.insert(slot, BlockStateDBSlot {
             value: shifted,
             insertion_type: InsertionType::Custom,
         });
@@ -107,13 +167,16 @@ where
 
     fn insert_slot0(&mut self, pool: Address, sqrt_price: U160, tick: i32) -> Result<()> {
         trace!("Insert Slot0: {} | sqrtPriceX96={}, tick={}", pool, sqrt_price, tick);
-        let value = U256::from(sqrt_price)
-            | ((U256::from(tick as u32) & *BITS24MASK) << 160)
-            | (U256::ZERO << (160 + 24))  // observationIndex
-            | (U256::ZERO << (160 + 24 + 16))  // observationCardinality
-            | (U256::ZERO << (160 + 24 + 16 + 16))  // observationCardinalityNext
-            | (U256::ZERO << (160 + 24 + 16 + 16 + 16))  // feeProtocol
-            | (U256::from(1u8) << (160 + 24 + 16 + 16 + 16 + 8)); // unlocked=true
+        let value = Slot0 {
+            sqrt_price_x96: sqrt_price,
+            tick,
+            observation_index: 0,
+            observation_cardinality: 0,
+            observation_cardinality_next: 0,
+            fee_protocol: 0,
+            unlocked: true,
+        }
+        .encode();
 
         let account = self.accounts.get_mut(&pool).expect("Pool not found in DB");
         account.storage.insert(U256::from(0), BlockStateDBSlot {
@@ -133,5 +196,114 @@ where
         });
         Ok(())
     }
+
+    /// Decodes the packed slot0 word written by `insert_slot0`
+    pub fn slot0(&self, pool: Address) -> Result<Slot0> {
+        let value = self.read_v3_slot(pool, U256::from(0))?;
+        Ok(Slot0::decode(value))
+    }
+
+    /// In-range liquidity from slot 4
+    pub fn liquidity(&self, pool: Address) -> Result<u128> {
+        let value = self.read_v3_slot(pool, U256::from(4))?;
+        Ok((value & *BITS128MASK).to::<u128>())
+    }
+
+    /// Tick spacing, stored in slot 14 by `insert_tick_spacing`
+    pub fn tick_spacing(&self, pool: Address) -> Result<i32> {
+        let value = self.read_v3_slot(pool, U256::from(14))?;
+        i32::try_from(value).map_err(|_| anyhow!("Invalid tick spacing for {}: {}", pool, value))
+    }
+
+    /// Bitmap word at `word_pos` of the pool's tick bitmap
+    pub fn get_tick_bitmap_word(&self, pool: Address, word_pos: i16) -> Result<U256> {
+        self.read_v3_slot(pool, tick_bitmap_slot(word_pos)?)
+    }
+
+    /// liquidityNet of an initialized tick, the upper 128 bits of `ticks[tick]`
+    pub fn get_liquidity_net(&self, pool: Address, tick: i32) -> Result<i128> {
+        let info = self.read_v3_slot(pool, tick_info_slot(tick)?)?;
+        Ok((info >> 128).to::<u128>() as i128)
+    }
+
+    fn read_v3_slot(&self, pool: Address, slot: U256) -> Result<U256> {
+        self.storage_ref(pool, slot)
+            .map_err(|e| anyhow!("Failed to read slot {} of {}: {:?}", slot, pool, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_db::BlockStateDBAccount;
+    use crate::utile::constant::{MAX_SQRT_RATIO, MAX_TICK, MIN_SQRT_RATIO, MIN_TICK};
+    use crate::utile::test_utils::offline_db;
+    use proptest::prelude::*;
+
+    /// Sqrt prices over `[MIN_SQRT_RATIO, MAX_SQRT_RATIO)`, both ends included as explicit cases
+    fn sqrt_prices() -> impl Strategy<Value = U160> {
+        let min = U256::from(MIN_SQRT_RATIO);
+        let span = *MAX_SQRT_RATIO - min;
+        prop_oneof![
+            Just(U160::from(min)),
+            Just(U160::from(*MAX_SQRT_RATIO - U256::from(1))),
+            any::<[u8; 20]>().prop_map(move |bytes| U160::from(min + U256::from(U160::from_be_bytes(bytes)) % span)),
+        ]
+    }
+
+    /// Ticks over the full range, the sign boundary and both extremes included as explicit cases
+    fn ticks() -> impl Strategy<Value = i32> {
+        prop_oneof![Just(MIN_TICK), Just(MAX_TICK), Just(-1), Just(0), MIN_TICK..=MAX_TICK]
+    }
+
+    proptest! {
+        #[test]
+        fn slot0_round_trips_through_its_packed_word(
+            sqrt_price_x96 in sqrt_prices(),
+            tick in ticks(),
+            observation_index in any::<u16>(),
+            observation_cardinality in any::<u16>(),
+            observation_cardinality_next in any::<u16>(),
+            fee_protocol in any::<u8>(),
+            unlocked in any::<bool>(),
+        ) {
+            let slot0 = Slot0 {
+                sqrt_price_x96,
+                tick,
+                observation_index,
+                observation_cardinality,
+                observation_cardinality_next,
+                fee_protocol,
+                unlocked,
+            };
+            prop_assert_eq!(Slot0::decode(slot0.encode()), slot0);
+            // Fields never bleed into the unused top bits
+            prop_assert!((slot0.encode() >> (UNLOCKED_OFFSET + 1)).is_zero());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn v3_reads_return_what_was_inserted() {
+        let pool = Address::repeat_byte(0x44);
+        let mut db = offline_db();
+        db.accounts.insert(pool, BlockStateDBAccount::default());
+
+        let sqrt_price = U160::from(79_228_162_514_264_337_593_543_950_336u128);
+        db.insert_slot0(pool, sqrt_price, -887_220).unwrap();
+        db.insert_liquidity(pool, u128::MAX - 1).unwrap();
+        db.insert_tick_spacing(pool, 60).unwrap();
+        db.insert_tick_bitmap(pool, -3, U256::from(0b1010)).unwrap();
+        db.insert_tick_liquidity_net(pool, -120, -5_000_000).unwrap();
+        db.insert_tick_liquidity_net(pool, 120, 5_000_000).unwrap();
+
+        let slot0 = db.slot0(pool).unwrap();
+        assert_eq!(slot0.sqrt_price_x96, sqrt_price);
+        assert_eq!(slot0.tick, -887_220);
+        assert!(slot0.unlocked);
+        assert_eq!(db.liquidity(pool).unwrap(), u128::MAX - 1);
+        assert_eq!(db.tick_spacing(pool).unwrap(), 60);
+        assert_eq!(db.get_tick_bitmap_word(pool, -3).unwrap(), U256::from(0b1010));
+        assert_eq!(db.get_liquidity_net(pool, -120).unwrap(), -5_000_000);
+        assert_eq!(db.get_liquidity_net(pool, 120).unwrap(), 5_000_000);
+    }
 }
-</create_file>
@@ -0,0 +1,114 @@
+use crate::utile::Cache;
+use crate::utile::market_state::{MarketState, PoolSnapshot};
+use crate::utile::opportunities::{OpportunityRecord, RecentOpportunities};
+use alloy::network::Network;
+use alloy::providers::Provider;
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// Shared state the read-only endpoints serve from
+pub struct ApiState<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    pub market_state: Arc<MarketState<N, P>>,
+    pub caught_up: Arc<AtomicBool>,
+    pub cache: Arc<Cache>,
+    pub opportunities: RecentOpportunities,
+}
+
+#[derive(Serialize)]
+struct StatusResponse {
+    caught_up: bool,
+    last_block: u64,
+    pool_count: usize,
+}
+
+#[derive(Deserialize)]
+struct OpportunitiesQuery {
+    /// Newest records returned, everything retained when unset
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct CacheStatsResponse {
+    entries: usize,
+}
+
+/// Serves `/status`, `/opportunities`, `/pools` and `/cache/stats` on `addr` until the process exits
+pub async fn serve<N, P>(addr: SocketAddr, state: ApiState<N, P>)
+where
+    N: Network,
+    P: Provider<N> + Send + Sync + 'static,
+{
+    let app = Router::new()
+        .route("/status", get(status::<N, P>))
+        .route("/opportunities", get(opportunities::<N, P>))
+        .route("/pools", get(pools::<N, P>))
+        .route("/cache/stats", get(cache_stats::<N, P>))
+        .with_state(Arc::new(state));
+
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind API on {}: {:?}", addr, e);
+            return;
+        }
+    };
+
+    info!("API listening on {}", addr);
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("API server failed: {:?}", e);
+    }
+}
+
+async fn status<N, P>(State(state): State<Arc<ApiState<N, P>>>) -> Json<StatusResponse>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let last_block = state.market_state.block_env_read().number.saturating_to::<u64>();
+    let pool_count = state.market_state.db_read().pools.len();
+    Json(StatusResponse {
+        caught_up: state.caught_up.load(Ordering::Relaxed),
+        last_block,
+        pool_count,
+    })
+}
+
+async fn opportunities<N, P>(
+    State(state): State<Arc<ApiState<N, P>>>,
+    Query(query): Query<OpportunitiesQuery>,
+) -> Json<Vec<OpportunityRecord>>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    let recent = state.opportunities.lock().unwrap();
+    Json(recent.recent(query.limit.unwrap_or(usize::MAX)))
+}
+
+async fn pools<N, P>(State(state): State<Arc<ApiState<N, P>>>) -> Json<Vec<PoolSnapshot>>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    Json(state.market_state.all_pool_snapshots())
+}
+
+async fn cache_stats<N, P>(State(state): State<Arc<ApiState<N, P>>>) -> Json<CacheStatsResponse>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    Json(CacheStatsResponse {
+        entries: state.cache.len(),
+    })
+}
@@ -0,0 +1,114 @@
+use crate::utile::swap::SwapPath;
+use alloy::primitives::{Address, address};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+/// Tokens excluded regardless of configuration
+const BUILTIN_BLACKLIST: &[Address] = &[address!("be5614875952b1683cb0a2c20e6509be46d353a4")];
+
+/// Blacklisted tokens, split by source so a file reload never drops runtime additions
+#[derive(Debug, Default)]
+pub struct Blacklist {
+    path: Option<PathBuf>,
+    from_file: HashSet<Address>,
+    runtime: HashSet<Address>,
+}
+
+impl Blacklist {
+    /// Builds the blacklist from `BLACKLIST_FILE`, a JSON array of addresses, if set
+    pub fn from_env() -> Self {
+        let mut blacklist = Self {
+            path: std::env::var("BLACKLIST_FILE").ok().map(PathBuf::from),
+            ..Default::default()
+        };
+        if let Err(e) = blacklist.reload() {
+            warn!("Failed to load blacklist: {:?}", e);
+        }
+        blacklist
+    }
+
+    #[inline]
+    pub fn contains(&self, token: &Address) -> bool {
+        BUILTIN_BLACKLIST.contains(token) || self.from_file.contains(token) || self.runtime.contains(token)
+    }
+
+    pub fn insert(&mut self, token: Address) {
+        self.runtime.insert(token);
+    }
+
+    /// Re-reads the configured file, a no-op when no file is configured
+    pub fn reload(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        self.from_file = read_blacklist_file(path)?;
+        info!("Loaded {} blacklisted tokens from {}", self.from_file.len(), path.display());
+        Ok(())
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+/// Global blacklist consulted by pool filtering
+pub static BLACKLIST: Lazy<RwLock<Blacklist>> = Lazy::new(|| RwLock::new(Blacklist::from_env()));
+
+#[inline]
+pub fn is_blacklisted(token: &Address) -> bool {
+    BLACKLIST.read().unwrap().contains(token)
+}
+
+/// Whether any hop of `path` trades a blacklisted token, checked at search time so reloads and
+/// runtime additions apply to cycles generated before them
+pub fn path_is_blacklisted(path: &SwapPath) -> bool {
+    let blacklist = BLACKLIST.read().unwrap();
+    path.steps
+        .iter()
+        .any(|step| blacklist.contains(&step.token_in) || blacklist.contains(&step.token_out))
+}
+
+/// Blacklists a token at runtime, e.g. after detecting a transfer tax
+pub fn add_to_blacklist(token: Address) {
+    info!("Blacklisting token {}", token);
+    BLACKLIST.write().unwrap().insert(token);
+}
+
+/// Polls the blacklist file every `interval` and reloads it when its mtime changes
+pub async fn watch_blacklist(interval: Duration) {
+    let Some(path) = BLACKLIST.read().unwrap().path().map(Path::to_path_buf) else {
+        return;
+    };
+
+    let mut last_modified = modified_at(&path);
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let modified = modified_at(&path);
+        if modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        if let Err(e) = BLACKLIST.write().unwrap().reload() {
+            warn!("Failed to reload blacklist from {}: {:?}", path.display(), e);
+        }
+    }
+}
+
+fn read_blacklist_file(path: &Path) -> Result<HashSet<Address>> {
+    let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let tokens: Vec<Address> =
+        serde_json::from_str(&contents).with_context(|| format!("Invalid blacklist file {}", path.display()))?;
+    Ok(tokens.into_iter().collect())
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -1,6 +1,7 @@
 use alloy::primitives::{Address, U256};
 use dashmap::DashMap;
 use fxhash::FxHasher;
+use std::collections::HashSet;
 use std::hash::{BuildHasherDefault, Hash, Hasher};
 
 /// Custom hasher based on `FxHasher` (fast non-cryptographic hashing)
@@ -19,10 +20,13 @@ impl Hasher for CacheHasher {
     }
 }
 
-/// Composite key to cache a specific pool's quote with an exact input amount
+/// Composite key to cache a specific pool's quote in one direction with an input amount, exact unless the cache quantizes.
+/// `token_out` is only set when the caller named it, multi-coin pools quote differently per output coin
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
 struct CacheKey {
     pub pool_address: Address,
+    pub token_in: Address,
+    pub token_out: Option<Address>,
     pub amount_in: U256,
 }
 
@@ -35,6 +39,8 @@ struct CacheEntry {
 /// A concurrent, fast read/write cache for pool simulations and estimations
 pub struct Cache {
     entries: DashMap<CacheKey, CacheEntry, BuildHasherDefault<CacheHasher>>,
+    // Inputs are rounded to the nearest multiple of this before keying
+    quantization: Option<U256>,
 }
 
 impl Cache {
@@ -46,16 +52,39 @@ impl Cache {
                 num_pools * 100,
                 BuildHasherDefault::default(),
             ),
+            quantization: None,
         }
     }
 
-    /// Retrieves a cached output amount for a given pool + input amount.
+    /// Buckets inputs to the nearest multiple of `step` raw units before keying, so near-identical inputs
+    /// share an entry. A bucket returns the output of whichever of its inputs was quoted first, unscaled:
+    /// there is no interpolation and no bound on the error beyond how much output `step` of input buys.
+    /// The step applies to every token alike, size it for the smallest-decimals token quoted.
+    /// A zero `step` disables bucketing
+    pub fn with_quantization(mut self, step: U256) -> Self {
+        self.quantization = (!step.is_zero()).then_some(step);
+        self
+    }
+
+    /// Key for swapping `amount_in` of `token_in` on `pool_address`, bucketed when quantization is on
     #[inline]
-    pub fn get(&self, amount_in: U256, pool_address: Address) -> Option<U256> {
-        let key = CacheKey {
+    fn key(&self, amount_in: U256, pool_address: Address, token_in: Address, token_out: Option<Address>) -> CacheKey {
+        let amount_in = match self.quantization {
+            Some(step) => amount_in.saturating_add(step / U256::from(2)) / step * step,
+            None => amount_in,
+        };
+        CacheKey {
             pool_address,
+            token_in,
+            token_out,
             amount_in,
-        };
+        }
+    }
+
+    /// Retrieves a cached output amount for a given pool, direction and input amount.
+    #[inline]
+    pub fn get(&self, amount_in: U256, pool_address: Address, token_in: Address, token_out: Option<Address>) -> Option<U256> {
+        let key = self.key(amount_in, pool_address, token_in, token_out);
         match self.entries.get(&key) {
             Some(entry) => Some(entry.output_amount),
             None => None,
@@ -64,14 +93,52 @@ impl Cache {
 
     /// Stores a new output amount in the cache
     #[inline]
-    pub fn insert(&self, amount_in: U256, pool_address: Address, output_amount: U256) {
-        let key = CacheKey {
-            pool_address,
-            amount_in,
-        };
+    pub fn insert(
+        &self,
+        amount_in: U256,
+        pool_address: Address,
+        token_in: Address,
+        token_out: Option<Address>,
+        output_amount: U256,
+    ) {
+        let key = self.key(amount_in, pool_address, token_in, token_out);
         self.entries.insert(key, CacheEntry { output_amount });
     }
 
+    /// Returns the cached output, or computes and stores it.
+    /// `f` runs without any shard lock held, so it may use the cache itself; callers racing on a missing
+    /// key may each compute it, the first stored output wins and is what every caller gets back.
+    #[inline]
+    pub fn get_or_compute(
+        &self,
+        amount_in: U256,
+        pool_address: Address,
+        token_in: Address,
+        token_out: Option<Address>,
+        f: impl FnOnce() -> U256,
+    ) -> U256 {
+        let key = self.key(amount_in, pool_address, token_in, token_out);
+        if let Some(entry) = self.entries.get(&key) {
+            return entry.output_amount;
+        }
+
+        let output_amount = f();
+        self.entries
+            .entry(key)
+            .or_insert(CacheEntry { output_amount })
+            .output_amount
+    }
+
+    /// Invalidate all cache entries for every pool in `pools` in a single pass
+    #[inline]
+    pub fn invalidate_many(&self, pools: &HashSet<Address>) {
+        if pools.is_empty() {
+            return;
+        }
+        self.entries
+            .retain(|key, _| !pools.contains(&key.pool_address));
+    }
+
     /// Invalidate all cache entries for a given pool
     #[inline]
     pub fn invalidate(&self, pool_address: Address) {
@@ -95,3 +162,68 @@ impl Cache {
         self.entries.is_empty()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn get_or_compute_from_many_threads() {
+        let cache = Arc::new(Cache::new(16));
+        let computed = Arc::new(AtomicUsize::new(0));
+        let token_in = Address::repeat_byte(0xaa);
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = Arc::clone(&cache);
+                let computed = Arc::clone(&computed);
+                std::thread::spawn(move || {
+                    for i in 0..1_000u64 {
+                        let pool = Address::with_last_byte((i % 16) as u8);
+                        let amount = U256::from(i % 4);
+                        let output = cache.get_or_compute(amount, pool, token_in, None, || {
+                            computed.fetch_add(1, Ordering::Relaxed);
+                            // Re-entering the cache from the closure must not deadlock
+                            let _ = cache.get(amount, pool, token_in, None);
+                            amount * U256::from(2)
+                        });
+                        assert_eq!(output, amount * U256::from(2));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(cache.len(), 16);
+        // Races may compute a key more than once, never once per call
+        assert!(computed.load(Ordering::Relaxed) < 8 * 1_000);
+    }
+
+    #[test]
+    fn inputs_in_one_bucket_share_an_entry() {
+        let cache = Cache::new(1).with_quantization(U256::from(100));
+        let (pool, token_in) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02));
+
+        cache.insert(U256::from(1_020), pool, token_in, None, U256::from(7));
+        // 980..1049 round to 1000
+        assert_eq!(cache.get(U256::from(990), pool, token_in, None), Some(U256::from(7)));
+        assert_eq!(cache.get_or_compute(U256::from(1_049), pool, token_in, None, || U256::from(8)), U256::from(7));
+        assert_eq!(cache.get(U256::from(1_050), pool, token_in, None), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn directions_are_cached_separately() {
+        let cache = Cache::new(1);
+        let pool = Address::repeat_byte(0x01);
+        let (token0, token1) = (Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+
+        cache.insert(U256::from(100), pool, token0, None, U256::from(90));
+        assert_eq!(cache.get(U256::from(100), pool, token1, None), None);
+        assert_eq!(cache.get(U256::from(100), pool, token0, None), Some(U256::from(90)));
+    }
+}
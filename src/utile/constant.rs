@@ -1,13 +1,350 @@
 use std::str::FromStr;
-use alloy::primitives::U256;
+use crate::utile::errors::WorkerError;
+use alloy::primitives::{Address, U256, address};
+use reqwest::Url;
+use pool_sync::Chain;
 use once_cell::sync::Lazy;
+use std::collections::HashMap;
 use std::sync::RwLock;
+use log::warn;
+
+/// Input size used for tokens without an explicit entry (1e18)
+pub const DEFAULT_AMOUNT: U256 = U256::from_limbs([1_000_000_000_000_000_000, 0, 0, 0]);
+
+/// Input sizes per base token, so WETH- and USDC-rooted cycles can be sized independently
+#[derive(Debug, Clone)]
+pub struct AmountConfig {
+    default: U256,
+    overrides: HashMap<Address, U256>,
+}
+
+impl AmountConfig {
+    pub fn new(default: U256) -> Self {
+        Self {
+            default,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Input size for cycles starting at `token`
+    #[inline]
+    pub fn amount_for(&self, token: Address) -> U256 {
+        self.overrides.get(&token).copied().unwrap_or(self.default)
+    }
+
+    /// Overrides the input size for a single token
+    pub fn set(&mut self, token: Address, amount: U256) {
+        self.overrides.insert(token, amount);
+    }
+
+    /// Updates the size used for tokens without an override
+    pub fn set_default(&mut self, amount: U256) {
+        self.default = amount;
+    }
+
+    pub fn default_amount(&self) -> U256 {
+        self.default
+    }
+}
+
+/// Global input sizing used across modules
+pub static AMOUNTS: Lazy<RwLock<AmountConfig>> =
+    Lazy::new(|| RwLock::new(AmountConfig::new(DEFAULT_AMOUNT)));
+
+/// Input size for cycles starting at `token`
+#[inline]
+pub fn amount_for(token: Address) -> U256 {
+    AMOUNTS.read().unwrap().amount_for(token)
+}
+/// Aave V3 flash loan premium in basis points
+pub const FLASH_LOAN_FEE_BPS: u32 = 9;
+
+/// Where the executor borrows the input of a path from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashLoanProvider {
+    Aave,
+    Balancer,
+}
+
+impl FlashLoanProvider {
+    /// Premium the provider charges, in basis points
+    pub fn default_fee_bps(&self) -> u32 {
+        match self {
+            Self::Aave => FLASH_LOAN_FEE_BPS,
+            Self::Balancer => 0,
+        }
+    }
+
+    /// FlashSwap only implements Aave's `executeOperation` callback, other providers need the
+    /// contract to implement their callback (`receiveFlashLoan` for the Balancer Vault) first
+    pub fn supported_by_executor(&self) -> bool {
+        matches!(self, Self::Aave)
+    }
+}
+
+impl FromStr for FlashLoanProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "aave" => Ok(Self::Aave),
+            "balancer" => Ok(Self::Balancer),
+            other => Err(format!("Unknown flash loan provider {}", other)),
+        }
+    }
+}
+
+/// Flash loan source and the premium it charges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashLoanConfig {
+    pub fee_bps: u32,
+    pub provider: FlashLoanProvider,
+    /// Largest amount the provider is trusted to lend for one path, in whole tokens of the borrowed token.
+    /// Tokens without an entry are uncapped
+    pub max_loans: HashMap<Address, u64>,
+}
+
+impl Default for FlashLoanConfig {
+    fn default() -> Self {
+        Self {
+            fee_bps: FLASH_LOAN_FEE_BPS,
+            provider: FlashLoanProvider::Aave,
+            max_loans: HashMap::new(),
+        }
+    }
+}
+
+impl FlashLoanConfig {
+    /// Reads FLASH_LOAN_PROVIDER (default aave), FLASH_LOAN_FEE_BPS (default: the provider's premium)
+    /// and FLASH_LOAN_MAX_IN, comma separated `token:whole_tokens` caps such as `0x4200..0006:50` (default uncapped)
+    pub fn from_env() -> Self {
+        let provider = std::env::var("FLASH_LOAN_PROVIDER")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(FlashLoanProvider::Aave);
+        let fee_bps = std::env::var("FLASH_LOAN_FEE_BPS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| provider.default_fee_bps());
+        let max_loans = std::env::var("FLASH_LOAN_MAX_IN")
+            .map(|raw| parse_max_loans(&raw))
+            .unwrap_or_default();
+        Self { fee_bps, provider, max_loans }
+    }
+
+    /// Cap on a loan of `token` in its raw units, None when `token` is uncapped
+    pub fn max_loan_for(&self, token: Address, decimals: u8) -> Option<U256> {
+        let whole = self.max_loans.get(&token)?;
+        Some(U256::from(*whole).saturating_mul(U256::from(10u64).pow(U256::from(decimals))))
+    }
+
+    /// Refuses live sending when FlashSwap can't borrow from the configured provider. Paths are sized against
+    /// this provider's premium, sending them through an executor that borrows elsewhere pays a fee the search
+    /// never accounted for. Dry runs are allowed and only warned about
+    pub fn ensure_executable(&self, dry_run: bool) -> Result<(), WorkerError> {
+        if self.provider.supported_by_executor() {
+            return Ok(());
+        }
+        if !dry_run {
+            return Err(WorkerError::ConfigMissing(format!(
+                "FlashSwap can't borrow from {:?}, set DRY_RUN=true or FLASH_LOAN_PROVIDER=aave",
+                self.provider
+            )));
+        }
+        warn!("FlashSwap can't borrow from {:?}, dry-run results assume its {} bps fee", self.provider, self.fee_bps);
+        Ok(())
+    }
+
+    /// Premium owed on top of a flash loan of `amount`
+    #[inline]
+    pub fn fee(&self, amount: U256) -> U256 {
+        amount * U256::from(self.fee_bps) / U256::from(10_000)
+    }
+}
+
+/// `token:whole_tokens` pairs, malformed entries are skipped with a warning
+fn parse_max_loans(raw: &str) -> HashMap<Address, u64> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once(':')
+                .and_then(|(token, cap)| Some((token.trim().parse().ok()?, cap.trim().parse().ok()?)));
+            if parsed.is_none() {
+                warn!("Ignoring FLASH_LOAN_MAX_IN entry {}, expected token:whole_tokens", entry);
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// Address the FlashQuoter bytecode is injected at in simulated state
+pub const DEFAULT_QUOTER_ADDRESS: Address = address!("0000000000000000000000000000000000001000");
+/// Funded caller of warm-up and quote simulations
+pub const DEFAULT_SIM_ACCOUNT: Address = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+/// Broadcast endpoint on Base when RELAYS is not set
+pub const BASE_SEQUENCER_URL: &str = "https://mainnet-sequencer.base.org";
+
+/// Well-known contracts and endpoints of one chain, the defaults a `Deployment` starts from
+#[derive(Debug, Clone, Copy)]
+pub struct ChainDefaults {
+    pub weth: Address,
+    /// Uniswap V2 Router02, pending swaps through it are decoded by the mempool stream
+    pub v2_router: Address,
+    /// MaverickV2Quoter, V2 pools have no view quote on the pool itself
+    pub maverick_v2_quoter: Address,
+    /// Broadcast endpoint when RELAYS is not set, None where relays must be configured
+    pub relay: Option<&'static str>,
+}
+
+impl ChainDefaults {
+    pub fn for_chain(chain: Chain) -> Self {
+        match chain {
+            Chain::Base => Self {
+                weth: address!("4200000000000000000000000000000000000006"),
+                v2_router: address!("4752ba5DBc23f44D87826276BF6Fd6b1C372aD24"),
+                maverick_v2_quoter: address!("b40AfdB85a07f37aE217E7D6462e609900dD8D7A"),
+                relay: Some(BASE_SEQUENCER_URL),
+            },
+            // Maverick deploys V2 at the same addresses on every EVM chain
+            Chain::Ethereum => Self {
+                weth: address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                v2_router: address!("7a250d5630B4cF539739dF2C5dAcb4c659F2488D"),
+                maverick_v2_quoter: address!("b40AfdB85a07f37aE217E7D6462e609900dD8D7A"),
+                relay: None,
+            },
+        }
+    }
+}
+
+/// Chain with `chain_id`, None for chains pools can't be synced on
+pub fn chain_from_id(chain_id: u64) -> Option<Chain> {
+    match chain_id {
+        1 => Some(Chain::Ethereum),
+        8453 => Some(Chain::Base),
+        _ => None,
+    }
+}
+
+/// Contract addresses and endpoints of one deployment, loaded once per chain.
+/// Warm-up and quoting both read `quoter` and `sim_account` from here, so they always agree
+#[derive(Debug, Clone)]
+pub struct Deployment {
+    /// Where the FlashQuoter bytecode lives in the simulated state
+    pub quoter: Address,
+    /// FlashSwap executor transactions are sent to
+    pub flash_swap: Address,
+    /// Account funded on warm-up and used as the quote caller
+    pub sim_account: Address,
+    /// Endpoints signed transactions are broadcast to
+    pub relays: Vec<Url>,
+    pub weth: Address,
+    /// Router whose pending swaps are sandwiched
+    pub v2_router: Address,
+    /// Quoter Maverick V2 pools are simulated through
+    pub maverick_v2_quoter: Address,
+    /// Whether `flash_swap` implements `executeSplitArbitrage`, split paths are never sent without it
+    pub split_arbitrage: bool,
+    /// Whether `flash_swap`'s SwapParams carries `amountOutMin`, without it trades have no on-chain output floor
+    pub min_out_arbitrage: bool,
+}
+
+impl Deployment {
+    /// Default quoter and simulation account with no executor or relays, for quoting without ever sending
+    pub fn simulation_only(chain: Chain) -> Self {
+        let defaults = ChainDefaults::for_chain(chain);
+        Self {
+            quoter: DEFAULT_QUOTER_ADDRESS,
+            flash_swap: Address::ZERO,
+            sim_account: DEFAULT_SIM_ACCOUNT,
+            relays: Vec::new(),
+            weth: defaults.weth,
+            v2_router: defaults.v2_router,
+            maverick_v2_quoter: defaults.maverick_v2_quoter,
+            split_arbitrage: false,
+            min_out_arbitrage: false,
+        }
+    }
+
+    /// Reads SWAP_CONTRACT (required), QUOTER_ADDRESS, SIM_ACCOUNT, RELAYS (comma separated), WETH, V2_ROUTER,
+    /// MAVERICK_V2_QUOTER, SPLIT_ARBITRAGE and MIN_OUT_ARBITRAGE (both default false) through `var`, which returns
+    /// the value for a key or the error to report when it is missing. Unset addresses and relays fall back to `chain`'s defaults
+    pub fn from_vars(chain: Chain, var: impl Fn(&str) -> Result<String, WorkerError>) -> Result<Self, WorkerError> {
+        let defaults = ChainDefaults::for_chain(chain);
+        let address = |key: &str, default: Option<Address>| -> Result<Address, WorkerError> {
+            match (var(key), default) {
+                (Ok(value), _) => value
+                    .parse()
+                    .map_err(|e| WorkerError::ConfigMissing(format!("Invalid {} address {}: {:?}", key, value, e))),
+                (Err(_), Some(default)) => Ok(default),
+                (Err(e), None) => Err(e),
+            }
+        };
+
+        let raw_relays = match (var("RELAYS"), defaults.relay) {
+            (Ok(relays), _) => relays,
+            (Err(_), Some(relay)) => relay.to_string(),
+            (Err(e), None) => return Err(e),
+        };
+        let mut relays: Vec<Url> = Vec::new();
+        for entry in raw_relays.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let url = Url::parse(entry)
+                .map_err(|e| WorkerError::ConfigMissing(format!("Invalid relay URL {}: {:?}", entry, e)))?;
+            if !relays.contains(&url) {
+                relays.push(url);
+            }
+        }
+        if relays.is_empty() {
+            return Err(WorkerError::ConfigMissing("RELAYS lists no endpoints".to_string()));
+        }
+
+        Ok(Self {
+            quoter: address("QUOTER_ADDRESS", Some(DEFAULT_QUOTER_ADDRESS))?,
+            flash_swap: address("SWAP_CONTRACT", None)?,
+            sim_account: address("SIM_ACCOUNT", Some(DEFAULT_SIM_ACCOUNT))?,
+            relays,
+            weth: address("WETH", Some(defaults.weth))?,
+            v2_router: address("V2_ROUTER", Some(defaults.v2_router))?,
+            maverick_v2_quoter: address("MAVERICK_V2_QUOTER", Some(defaults.maverick_v2_quoter))?,
+            split_arbitrage: var("SPLIT_ARBITRAGE").ok().and_then(|v| v.parse().ok()).unwrap_or(false),
+            min_out_arbitrage: var("MIN_OUT_ARBITRAGE").ok().and_then(|v| v.parse().ok()).unwrap_or(false),
+        })
+    }
+}
 
-/// Global amount used across modules
-pub static AMOUNT: Lazy<RwLock<U256>> =
-    Lazy::new(|| RwLock::new(U256::from(1_000_000_000_000_000_000u128)));
 pub static U256_ONE: Lazy<U256> = Lazy::new(|| U256::from(1u64));
 pub const MIN_SQRT_RATIO: u128 = 4295128739;
 pub static MAX_SQRT_RATIO: Lazy<U256> = Lazy::new(|| U256::from_str("1461446703485210103287273052203988822378723970342").expect("Invalid MAX_SQRT_RATIO string"));
 pub const MIN_TICK: i32 = -887272;
 pub const MAX_TICK: i32 = 887272;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_flash_loan_provider_only_runs_dry() {
+        let balancer = FlashLoanConfig {
+            fee_bps: 0,
+            provider: FlashLoanProvider::Balancer,
+            max_loans: HashMap::new(),
+        };
+        assert!(matches!(balancer.ensure_executable(false), Err(WorkerError::ConfigMissing(_))));
+        assert!(balancer.ensure_executable(true).is_ok());
+        assert!(FlashLoanConfig::default().ensure_executable(false).is_ok());
+    }
+
+    #[test]
+    fn loan_caps_are_per_token_in_whole_units() {
+        let (weth, usdc) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02));
+        let config = FlashLoanConfig {
+            max_loans: parse_max_loans(&format!("{}:50, {}:150000, garbage", weth, usdc)),
+            ..Default::default()
+        };
+        assert_eq!(config.max_loans.len(), 2);
+        assert_eq!(config.max_loan_for(weth, 18), Some(U256::from(50u64) * U256::from(10u64).pow(U256::from(18))));
+        assert_eq!(config.max_loan_for(usdc, 6), Some(U256::from(150_000_000_000u64)));
+        assert_eq!(config.max_loan_for(Address::repeat_byte(0x03), 18), None);
+    }
+}
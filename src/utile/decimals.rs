@@ -0,0 +1,124 @@
+use crate::utile::rgen::ERC20Token;
+use alloy::network::Network;
+use alloy::primitives::Address;
+use alloy::providers::Provider;
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use pool_sync::{Pool, PoolInfo};
+
+/// Token decimals shared across modules, seeded from pool metadata and filled on-chain on a miss
+pub static DECIMALS: Lazy<DecimalsProvider> = Lazy::new(DecimalsProvider::new);
+
+/// Decimals of a token if already known
+#[inline]
+pub fn decimals_of(token: Address) -> Option<u8> {
+    DECIMALS.get(token)
+}
+
+/// Resolves token decimals by address.
+/// pool_sync metadata is the primary source, tokens it never listed fall back to an on-chain `decimals()` call,
+/// every answer is cached so a token costs at most one call per process
+#[derive(Debug, Default)]
+pub struct DecimalsProvider {
+    decimals: DashMap<Address, u8>,
+}
+
+impl DecimalsProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached decimals of `token`, never touches the chain
+    pub fn get(&self, token: Address) -> Option<u8> {
+        self.decimals.get(&token).map(|d| *d)
+    }
+
+    /// Cached decimals of `token`, recording `listed` (from pool metadata) if nothing was known yet
+    pub fn get_or_record(&self, token: Address, listed: u8) -> u8 {
+        *self.decimals.entry(token).or_insert(listed)
+    }
+
+    /// Records the decimals pool_sync listed for every token of `pool`
+    pub fn record_pool(&self, pool: &Pool) {
+        if let Pool::BalancerV2(balancer) = pool {
+            let decimals = [balancer.token0_decimals, balancer.token1_decimals]
+                .into_iter()
+                .chain(balancer.additional_token_decimals.iter().copied());
+            for (token, decimals) in balancer.get_tokens().into_iter().zip(decimals) {
+                self.get_or_record(token, decimals);
+            }
+            return;
+        }
+        self.get_or_record(pool.token0_address(), pool.token0_decimals());
+        self.get_or_record(pool.token1_address(), pool.token1_decimals());
+    }
+
+    /// Records the metadata of every pool in `pools`
+    pub fn record_pools(&self, pools: &[Pool]) {
+        for pool in pools {
+            self.record_pool(pool);
+        }
+        debug!("Decimals known for {} tokens", self.decimals.len());
+    }
+
+    /// Decimals of `token`, calling its `decimals()` on-chain when no pool listed it
+    pub async fn resolve<N: Network, P: Provider<N>>(&self, token: Address, provider: &P) -> Result<u8> {
+        if let Some(decimals) = self.get(token) {
+            return Ok(decimals);
+        }
+
+        let decimals = ERC20Token::new(token, provider)
+            .decimals()
+            .call()
+            .await
+            .with_context(|| format!("decimals() call failed for {}", token))?;
+        if decimals > 36 {
+            warn!("Token {} reports {} decimals", token, decimals);
+        }
+        self.decimals.insert(token, decimals);
+        Ok(decimals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::test_utils::full_node_provider;
+    use alloy::primitives::{U128, address};
+    use pool_sync::UniswapV2Pool;
+
+    const BASE_USDC: Address = address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913");
+
+    #[test]
+    fn six_decimal_tokens_resolve_to_six_from_pool_metadata() {
+        let (usdc, weth) = (Address::repeat_byte(0x06), Address::repeat_byte(0x18));
+        let provider = DecimalsProvider::new();
+        provider.record_pool(&Pool::UniswapV2(UniswapV2Pool {
+            address: Address::repeat_byte(0x21),
+            token0: usdc,
+            token1: weth,
+            token0_decimals: 6,
+            token1_decimals: 18,
+            token0_reserves: U128::from(1),
+            token1_reserves: U128::from(1),
+            ..Default::default()
+        }));
+
+        assert_eq!(provider.get(usdc), Some(6));
+        assert_eq!(provider.get(weth), Some(18));
+        // The first answer sticks, a later listing can't flip it back to the 18 decimal default
+        assert_eq!(provider.get_or_record(usdc, 18), 6);
+        assert_eq!(provider.get(Address::repeat_byte(0x99)), None);
+    }
+
+    // Tokens no pool listed are resolved through decimals(). Run with FULL=<base rpc> cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn unlisted_tokens_resolve_on_chain() {
+        let provider = DecimalsProvider::new();
+        assert_eq!(provider.resolve(BASE_USDC, &full_node_provider()).await.unwrap(), 6);
+        assert_eq!(provider.get(BASE_USDC), Some(6));
+    }
+}
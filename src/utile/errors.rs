@@ -0,0 +1,40 @@
+use thiserror::Error;
+use tokio::sync::mpsc::error::SendError;
+
+/// Why a pipeline worker stopped
+#[derive(Error, Debug)]
+pub enum WorkerError {
+    /// The other end of a pipeline channel is gone, the pipeline can't make progress
+    #[error("Channel closed: {0}")]
+    ChannelClosed(String),
+
+    #[error("Provider error: {0}")]
+    Provider(String),
+
+    #[error("Decode error: {0}")]
+    Decode(String),
+
+    #[error("Missing or invalid config: {0}")]
+    ConfigMissing(String),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl WorkerError {
+    /// Fatal errors bring the bot down, the rest only stop the failing worker
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::ChannelClosed(_) | Self::ConfigMissing(_))
+    }
+}
+
+impl<T> From<SendError<T>> for WorkerError {
+    fn from(_: SendError<T>) -> Self {
+        Self::ChannelClosed("receiver dropped".to_string())
+    }
+}
+
+/// Reads a required env var
+pub fn env_var(key: &str) -> Result<String, WorkerError> {
+    std::env::var(key).map_err(|_| WorkerError::ConfigMissing(format!("{} env var not set", key)))
+}
@@ -1,11 +1,13 @@
 use crate::calculation::calculator;
-use crate::utile::constant::AMOUNT;
+use crate::utile::constant::amount_for;
+use crate::utile::decimals::{DECIMALS, decimals_of};
 use crate::utile::market_state::MarketState;
 use crate::utile::rgen::FlashQuoter;
 use crate::utile::swap::SwapPath;
 use alloy::network::Network;
 use alloy::primitives::{Address, U256};
 use alloy::providers::Provider;
+use log::warn;
 use once_cell::sync::Lazy;
 use pool_sync::{Pool, PoolInfo};
 use std::collections::{HashMap, HashSet};
@@ -28,7 +30,8 @@ where
     market_state: Arc<MarketState<N, P>>,
     calculator: calculator::Calculator<N, P>,
     aggregated_weth_rate: HashMap<Address, U256>,
-    token_decimals: HashMap<Address, u32>,
+    // Token every cycle starts and ends in
+    weth: Address,
 }
 
 impl<N, P> Estimator<N, P>
@@ -36,18 +39,20 @@ where
     N: Network,
     P: Provider<N>,
 {
-    pub fn new(market_state: Arc<MarketState<N, P>>) -> Self {
+    pub fn new(market_state: Arc<MarketState<N, P>>, weth: Address) -> Self {
         Self {
             rates: HashMap::new(),
             weth_based: HashMap::new(),
             market_state: Arc::clone(&market_state),
             calculator: calculator::Calculator::new(market_state),
             aggregated_weth_rate: HashMap::new(),
-            token_decimals: HashMap::new(),
+            weth,
         }
     }
 
     pub fn update_rates(&mut self, pool_addrs: &HashSet<Address>) {
+        // Quotes of the changed pools are stale before any rate is recomputed
+        self.calculator.invalidate_cache(pool_addrs);
         let db = self.market_state.db.read().unwrap();
         let pools: Vec<Pool> = pool_addrs
             .iter()
@@ -59,7 +64,10 @@ where
     }
 
     pub fn estimate_output_amount(&self, path: &SwapPath) -> U256 {
-        path.steps.iter().fold(*AMOUNT, |amount, step| {
+        let Some(first_step) = path.steps.first() else {
+            return U256::ZERO;
+        };
+        path.steps.iter().fold(amount_for(first_step.token_in), |amount, step| {
             self.rates
                 .get(&step.pool_address)
                 .and_then(|m| m.get(&step.token_in))
@@ -69,6 +77,17 @@ where
         })
     }
 
+    /// `wei` of native token (gas cost) in base units of `token`, priced at the averaged WETH rate.
+    /// None while `token` has no WETH rate or unknown decimals
+    pub fn wei_to_token(&self, wei: U256, token: Address) -> Option<U256> {
+        if token == self.weth {
+            return Some(wei);
+        }
+        let rate = *self.aggregated_weth_rate.get(&token)?;
+        let decimals = decimals_of(token)?;
+        Some(weth_to_token(wei, rate, decimals as u32))
+    }
+
     pub fn is_profitable(&self, path: &SwapPath, min_profit_ratio: U256) -> bool {
         let final_rate = path.steps.iter().fold(*RATE_SCALE_VALUE, |rate, step| {
             self.rates
@@ -106,15 +125,27 @@ where
     }
 
     pub fn process_pools(&mut self, pools: Vec<Pool>) {
-        let weth: Address = std::env::var("WETH").unwrap().parse().unwrap();
+        let weth = self.weth;
+        let weth_amount = amount_for(weth);
         let mut alt_tokens: HashSet<Address> = HashSet::new();
         let mut weth_alt_cnt: HashMap<Address, u32> = HashMap::new();
+        DECIMALS.record_pools(&pools);
+
+        // Averages are rebuilt from this batch's pools, summing onto the previous average would inflate it
+        for pool in &pools {
+            let (token0, token1) = (pool.token0_address(), pool.token1_address());
+            if token0 == weth {
+                self.aggregated_weth_rate.remove(&token1);
+            } else if token1 == weth {
+                self.aggregated_weth_rate.remove(&token0);
+            }
+        }
 
         for pool in &pools {
             let has_weth = pool.token0_address() == weth || pool.token1_address() == weth;
             if has_weth {
                 self.weth_based.insert(pool.address(), true);
-                self.process_eth_pool(pool, weth, *AMOUNT, &mut alt_tokens, &mut weth_alt_cnt);
+                self.process_eth_pool(pool, weth, weth_amount, &mut alt_tokens, &mut weth_alt_cnt);
             }
         }
 
@@ -128,7 +159,7 @@ where
 
         for pool in &pools {
             if pool.token0_address() != weth && pool.token1_address() != weth {
-                self.process_nonweth_pool(pool, *AMOUNT);
+                self.process_nonweth_pool(pool, weth_amount);
             }
         }
     }
@@ -142,11 +173,6 @@ where
         cnt_map: &mut HashMap<Address, u32>,
     ) {
         let (token0, token1) = (pool.token0_address(), pool.token1_address());
-        self.token_decimals
-            .insert(token0, pool.token0_decimals().into());
-        self.token_decimals
-            .insert(token1, pool.token1_decimals().into());
-
         let (eth_token, alt_token) = if token0 == weth {
             (token0, token1)
         } else {
@@ -170,8 +196,12 @@ where
             output,
         );
 
-        let in_dec = *self.token_decimals.get(&eth_token).unwrap_or(&18);
-        let out_dec = *self.token_decimals.get(&alt_token).unwrap_or(&18);
+        // A rate scaled with guessed decimals is off by orders of magnitude, better to have none
+        let (Some(in_dec), Some(out_dec)) = (decimals_of(eth_token), decimals_of(alt_token)) else {
+            warn!("Unknown decimals for pool {} tokens, skipping rate", pool.address());
+            return;
+        };
+        let (in_dec, out_dec) = (in_dec as u32, out_dec as u32);
 
         let rate_eth_to_alt = self.calculate_rate(input, output, in_dec, out_dec);
         let rate_alt_to_eth = self.calculate_rate(output, back_output, out_dec, in_dec);
@@ -194,8 +224,11 @@ where
 
     fn process_nonweth_pool(&mut self, pool: &Pool, input: U256) {
         let (token0, token1) = (pool.token0_address(), pool.token1_address());
-        let decimals0 = *self.token_decimals.get(&token0).unwrap_or(&18);
-        let decimals1 = *self.token_decimals.get(&token1).unwrap_or(&18);
+        let (Some(decimals0), Some(decimals1)) = (decimals_of(token0), decimals_of(token1)) else {
+            warn!("Unknown decimals for pool {} tokens, skipping rate", pool.address());
+            return;
+        };
+        let (decimals0, decimals1) = (decimals0 as u32, decimals1 as u32);
 
         if let Some(&input_rate) = self.aggregated_weth_rate.get(&token0) {
             let output = self.calculator.compute_pool_output(
@@ -227,3 +260,31 @@ where
         }
     }
 }
+
+/// `amount` of WETH (18 decimals) in base units of a token with `decimals`,
+/// `rate` being the token's 1e18-scaled, decimal-normalized price per WETH
+fn weth_to_token(amount: U256, rate: U256, decimals: u32) -> U256 {
+    let normalized = amount.saturating_mul(rate) / *RATE_SCALE_VALUE;
+    let base = U256::from(10u64);
+    if decimals <= RATE_SCALE {
+        normalized / base.pow(U256::from(RATE_SCALE - decimals))
+    } else {
+        normalized.saturating_mul(base.pow(U256::from(decimals - RATE_SCALE)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gas_wei_converts_into_token_units() {
+        // 3000 USDC per WETH, normalized to 18 decimals and scaled by 1e18
+        let rate = U256::from(3000u64) * *RATE_SCALE_VALUE;
+        let gas_wei = U256::from(10u64).pow(U256::from(15u64));
+        // 0.001 WETH of gas is 3 USDC
+        assert_eq!(weth_to_token(gas_wei, rate, 6), U256::from(3_000_000u64));
+        assert_eq!(weth_to_token(gas_wei, rate, 18), U256::from(3u64) * *RATE_SCALE_VALUE);
+        assert_eq!(weth_to_token(gas_wei, *RATE_SCALE_VALUE, 18), gas_wei);
+    }
+}
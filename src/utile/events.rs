@@ -1,4 +1,5 @@
 use crate::utile::rgen::FlashQuoter::SwapParams;
+use crate::utile::sandwicher::PendingSwap;
 use crate::utile::swap::SwapPath;
 use alloy::primitives::{Address, U256};
 use alloy::rpc::types::Header;
@@ -7,12 +8,21 @@ use std::collections::HashSet;
 /// Represents messages passed across the bot's internal event pipeline
 #[derive(Debug, Clone)]
 pub enum Event {
-    /// Arbitrage path found (SwapPath, estimated profit, block number)
-    ArbPath((SwapPath, U256, u64)),
-    /// A path validated by quoting engine (params, expected output, block number)
-    ValidPath((SwapParams, U256, u64)),
+    /// Arbitrage path found (SwapPath, optimized input, expected output, block number)
+    ArbPath((SwapPath, U256, U256, u64)),
+    /// A path validated by quoting engine (params, start token, expected output, estimated gas limit, block number).
+    /// Profit is realized in the start token
+    ValidPath((SwapParams, Address, U256, u64, u64)),
     /// Set of pools involved in a previous swap or touched in state update (with block number)
     PoolsTouched(HashSet<Address>, u64),
+    /// Storage slots of a pool whose value changed, sent ahead of the block's `PoolsTouched`
+    SlotsChanged(Address, Vec<U256>),
+    /// Sandwich around a pending swap (front-run, back-run, expected profit, block number)
+    SandwichBundle((SwapPath, SwapPath, U256, u64)),
+    /// Swap decoded from the mempool that targets a tracked pool
+    PendingSwap(PendingSwap),
     /// New block received (raw header)
     NewBlock(Header),
+    /// Catch-up replayed blocks `from..=current` of the gap ending at `to`
+    CatchupProgress { from: u64, to: u64, current: u64 },
 }
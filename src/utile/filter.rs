@@ -1,13 +1,15 @@
-use crate::calculation::state_db::blockstate_db::InsertionType::StateInsertionType;
-use crate::utile::constant::AMOUNT;
-use crate::utile::node_db::InsertionType::NodeInsertionType;
-use crate::utile::node_db::NodeDB;
+use crate::calculation::state_db::blockstate_db::{BlockStateDB, DEFAULT_PROVIDER_TIMEOUT};
+use crate::utile::blacklist::{add_to_blacklist, is_blacklisted};
+use crate::utile::constant::amount_for;
+use crate::utile::market_state::{SimConfig, WarmUpConfig};
+use crate::utile::revert::describe_revert;
 use crate::utile::rgen::ERC20Token::approveCall;
 use crate::utile::rgen::{V2Aerodrome, V2Swap, V3Swap, V3SwapDeadline, V3SwapDeadlineTick};
-use alloy::primitives::{Address, U160, U256, address};
+use alloy::network::Network;
+use alloy::primitives::{Address, U160, U256, address, keccak256};
+use alloy::providers::Provider;
 use alloy::sol_types::{SolCall, SolValue};
 use anyhow::{Context, Result};
-use lazy_static::lazy_static;
 use log::{debug, info};
 use once_cell::sync::Lazy;
 use pool_sync::{Chain, Pool, PoolInfo, PoolType};
@@ -17,10 +19,11 @@ use reth::builder::components::ExecutorBuilder::Executor;
 use reth::chainspec::arbitrary::Result as RethResult;
 use reth::revm::revm::primitives::Bytes;
 use reth::revm::revm::primitives::*;
+use revm::Evm;
+use revm::db::{CacheDB, WrapDatabaseRef};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet, Arc.
 use std::{
-    collections::HashMap,
     fs::{File, create_dir_all},
     io::{BufReader, BufWriter},
     path::Path,
@@ -37,12 +40,6 @@ enum SwapType {
     V3DeadlineTick,
 }
 
-// Blacklisted tokens we don’t want to consider (e.g. scams, malicious)
-lazy_static! {
-    static ref BLACKLIST: Vec<Address> = vec![address!("be5614875952b1683cb0a2c20e6509be46d353a4")];
-    static ref WETH_ADDRESS: Address = address!("4200000000000000000000000000000000000006");
-}
-
 // Common constants
 const DEFAULT_PRIORITY_DIVISOR: usize = 50;
 const SIMULATED_ACCOUNT: Address = address!("0000000000000000000000000000000000000001");
@@ -73,7 +70,18 @@ struct Token {
     address: Option<String>,
 }
 
-pub async fn filter_pools(pools: Vec<Pool>, num_results: usize, chain: Chain) -> Result<Vec<Pool>> {
+/// Simulated swaps run on a fork of `chain` read through `provider`, selling `weth` first where a pool holds it
+pub async fn filter_pools<N, P>(
+    pools: Vec<Pool>,
+    num_results: usize,
+    chain: Chain,
+    weth: Address,
+    provider: P,
+) -> Result<Vec<Pool>>
+where
+    N: Network,
+    P: Provider<N>,
+{
     info!("Initial pool count before filter: {}", pools.len());
 
     let top_volume_tokens = get_top_volume_tokens(chain, num_results)
@@ -87,8 +95,8 @@ pub async fn filter_pools(pools: Vec<Pool>, num_results: usize, chain: Chain) ->
             let token1 = pool.token1_address();
             top_volume_tokens.contains(&token0)
                 && top_volume_tokens.contains(&token1)
-                && !BLACKLIST.contains(&token0)
-                && !BLACKLIST.contains(&token1)
+                && !is_blacklisted(&token0)
+                && !is_blacklisted(&token1)
         })
         .collect();
 
@@ -97,8 +105,9 @@ pub async fn filter_pools(pools: Vec<Pool>, num_results: usize, chain: Chain) ->
         filtered_by_token.len()
     );
 
-    let slot_map = construct_slot_map(&filtered_by_token);
-    let pools_result = filter_by_swap(filtered_by_token, slot_map).await;
+    let db = BlockStateDB::new(provider, DEFAULT_PROVIDER_TIMEOUT)
+        .context("Failed to initialize BlockStateDB for the swap filter")?;
+    let pools_result = filter_by_swap(filtered_by_token, &db, SimConfig::for_chain(chain), weth).await;
 
     debug!(
         "Pool count after simulated swap filter: {}",
@@ -204,29 +213,24 @@ async fn fetch_top_volume_tokens(num_results: usize, chain: Chain) -> Result<Vec
     Ok(addresses)
 }
 
-fn construct_slot_map(pools: &[Pool]) -> HashMap<Address, FixedBytes<32>> {
-    let mut slot_map = HashMap::new();
-
-    for pool in pools {
-        for &token in &[pool.token0_address(), pool.token1_address()] {
-            if !slot_map.contains_key(&token) {
-                // Use token address low 32 bytes as a mock slot, or customize
-                let slot: FixedBytes<32> = FixedBytes::from_slice(&token.0[..32]);
-                slot_map.insert(token, slot);
-            }
-        }
-    }
-
-    slot_map
-}
-
-async fn filter_by_swap(
+/// Keeps pools that survive a funded forward + backward swap through their router on a fork of chain state.
+/// Each pool simulates on its own overlay, the fake balances never leak between pools
+async fn filter_by_swap<N, P>(
     pools: Vec<Pool>,
-    slot_map: HashMap<Address, FixedBytes<32>>,
-) -> Result<Vec<Pool>> {
+    db: &BlockStateDB<N, P>,
+    sim_config: SimConfig,
+    weth: Address,
+) -> Result<Vec<Pool>>
+where
+    N: Network,
+    P: Provider<N>,
+{
     let mut filtered = Vec::with_capacity(pools.len());
-
-    let nodedb = NodeDB::open("./node_db.rs")?;
+    let mut slots = WarmUpConfig {
+        account: SIMULATED_ACCOUNT,
+        sim_config,
+        ..Default::default()
+    };
 
     for pool in pools {
         let (router, swap_type) = match resolve_router_and_type(pool.pool_type()) {
@@ -234,28 +238,32 @@ async fn filter_by_swap(
             None => continue,
         };
 
-        let zero_to_one = determine_swap_direction(&pool);
-
-        let slot0 = slot_map
-            .get(&pool.token0_address())
-            .copied()
-            .ok_or_else(|| anyhow::anyhow!("Missing slot0"))?;
-        let slot1 = slot_map
-            .get(&pool.token1_address())
-            .copied()
-            .ok_or_else(|| anyhow::anyhow!("Missing slot1"))?;
-
-        // Insert fake balances
-        for (token, slot) in [
-            (pool.token0_address(), slot0),
-            (pool.token1_address(), slot1),
-        ] {
-            // nodedb.insert_account_storage(token, slot.into(), *FAKE_TOKEN_AMOUNT, InsertionType::OnChain)
-            //     .map_err(|e| anyhow::anyhow!("Failed to insert account storage: {}", e))?;
+        let zero_to_one = determine_swap_direction(&pool, weth);
+
+        // Fund the simulated account through each token's `balances` mapping
+        let mut overlay = CacheDB::new(WrapDatabaseRef(db));
+        let mut funded = true;
+        for token in [pool.token0_address(), pool.token1_address()] {
+            let Some(slot) = slots.balance_slot(db, token) else {
+                debug!("No balance slot found for {}, dropping pool {}", token, pool.address());
+                funded = false;
+                break;
+            };
+            let storage_slot: U256 = keccak256((SIMULATED_ACCOUNT, slot).abi_encode()).into();
+            if let Err(e) = overlay.insert_account_storage(token, storage_slot, *FAKE_TOKEN_AMOUNT) {
+                debug!("Failed to fund {} for pool {}: {:?}", token, pool.address(), e);
+                funded = false;
+                break;
+            }
+        }
+        if !funded {
+            continue;
         }
 
-        let mut evm = EVM::builder()
-            .with_db(&nodedb)
+        let mut evm = Evm::builder()
+            .with_db(&mut overlay)
+            .with_spec_id(sim_config.spec_id)
+            .modify_cfg_env(|cfg| sim_config.apply_cfg(cfg))
             .modify_tx_env(|tx| {
                 tx.caller = SIMULATED_ACCOUNT;
                 tx.value = U256::ZERO;
@@ -263,6 +271,7 @@ async fn filter_by_swap(
             })
             .build();
 
+        let mut approved = true;
         for token in [pool.token0_address(), pool.token1_address()] {
             evm.tx_mut().data = approveCall {
                 spender: router,
@@ -272,14 +281,28 @@ async fn filter_by_swap(
             .into();
 
             evm.tx_mut().transact_to = TransactTo::Call(token);
-            evm.transact_commit()
-                .ok_or_else(|| anyhow::anyhow!("Approval failed"))?;
+            if !evm.transact_commit().is_ok_and(|result| result.is_success()) {
+                // A funded account can't approve, the token isn't a standard ERC20 and stays out of every path
+                add_to_blacklist(token);
+                approved = false;
+                break;
+            }
+        }
+        if !approved {
+            debug!("Approval failed for pool {}", pool.address());
+            continue;
         }
 
-        let amt_val = *AMOUNT.read().expect("Failed to read amount");
+        let token_in = if zero_to_one {
+            pool.token0_address()
+        } else {
+            pool.token1_address()
+        };
+        let amt_val = amount_for(token_in);
         let min_expected = amt_val * U256::from(MIN_OUTPUT_RATIO) / U256::from(100);
 
-        let forward = simulate_swap(
+        // A swap that reverts means the pool can't absorb the trade, drop it rather than fail the filter
+        let Some(forward) = simulate_swap(
             &mut evm,
             &pool,
             swap_type,
@@ -287,10 +310,12 @@ async fn filter_by_swap(
             SIMULATED_ACCOUNT,
             amt_val,
             zero_to_one,
-        )
-        .ok_or_else(|| anyhow::anyhow!("Forward swap simulation failed"))?;
+        ) else {
+            debug!("Forward swap simulation failed for pool {}", pool.address());
+            continue;
+        };
 
-        let backward = simulate_swap(
+        let Some(backward) = simulate_swap(
             &mut evm,
             &pool,
             swap_type,
@@ -298,8 +323,10 @@ async fn filter_by_swap(
             SIMULATED_ACCOUNT,
             forward,
             !zero_to_one,
-        )
-        .ok_or_else(|| anyhow::anyhow!("Backward swap simulation failed"))?;
+        ) else {
+            debug!("Backward swap simulation failed for pool {}", pool.address());
+            continue;
+        };
 
         if backward >= min_expected {
             filtered.push(pool.clone());
@@ -309,8 +336,8 @@ async fn filter_by_swap(
     Ok(filtered)
 }
 
-fn simulate_swap(
-    evm: &mut InspectEvm,
+fn simulate_swap<DB: revm::Database>(
+    evm: &mut Evm<'_, (), DB>,
     pool: &Pool,
     swap_type: SwapType,
     router: Address,
@@ -327,67 +354,69 @@ fn simulate_swap(
 
     match res {
         ExecutionResult::Success { .. } => {
-            let out = res.output()?;
-            Some(decode_swap_return(out, is_vec))
+            let amount = decode_swap_return(res.output()?, is_vec);
+            if amount.is_none() {
+                debug!("Undecodable swap return from pool {}", pool.address());
+            }
+            amount
+        }
+        ExecutionResult::Revert { output, .. } => {
+            debug!("Swap on pool {} reverted: {}", pool.address(), describe_revert(&output));
+            None
         }
         _ => None,
     }
 }
 
-fn decode_swap_return(output: &Bytes, is_vec: bool) -> U256 {
+/// Amount out of a router swap, the last entry of `amounts` for V2 routers. None when the return data doesn't decode
+fn decode_swap_return(output: &Bytes, is_vec: bool) -> Option<U256> {
     if is_vec {
-        let vec_out = match <Vec<U256>>::abi_decode(output) {
-            Ok(v) => v,
-            Err(e) => {
-                debug!("Vec decode failed: {:?}", e);
-                return U256::ZERO;
-            }
-        };
-
-        *vec_out.last().unwrap()
+        <Vec<U256>>::abi_decode(output).ok()?.last().copied()
     } else {
-        <U256>::abi_decode(output).unwrap()
+        <U256>::abi_decode(output).ok()
     }
 }
 
+/// Router the filter swaps through for each protocol on Base: V2 Router02s, Uniswap's SwapRouter02,
+/// Sushi's V3 router, Aerodrome's router and the Slipstream swap router
 fn resolve_router_and_type(pt: PoolType) -> Option<(Address, SwapType)> {
     match pt {
         PoolType::UniswapV2 => Some((
-            address!("0x4752a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0"),
+            address!("0x4752ba5DBc23f44D87826276BF6Fd6b1C372aD24"),
             SwapType::V2Basic,
         )),
         PoolType::SushiSwapV2 => Some((
-            address!("0x6BDEa1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0"),
+            address!("0x6BDED42c6DA8FBf0d2bA55B2fa120C5e0c8D7891"),
             SwapType::V2Basic,
         )),
         PoolType::PancakeSwapV2 => Some((
-            address!("0x8cFea1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0"),
+            address!("0x8cFe327CEc66d1C090Dd72bd0FF11d690C33a2Eb"),
             SwapType::V2Basic,
         )),
         PoolType::UniswapV3 => Some((
-            address!("0x2626a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0"),
+            address!("0x2626664c2603336E57B271c5C0b26F421741e481"),
             SwapType::V3Basic,
         )),
         PoolType::SushiSwapV3 => Some((
-            address!("0xFB7ea1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0"),
+            address!("0xFB7eF66a7e61224DD6FcD0D7d9C3be5C8B049b9f"),
             SwapType::V3Deadline,
         )),
         PoolType::Aerodrome => Some((
-            address!("0xcF77a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0"),
+            address!("0xcF77a3Ba9A5CA399B7c97c74d54e5b1Beb874E43"),
             SwapType::V2Aerodrome,
         )),
         PoolType::Slipstream => Some((
-            address!("0xBE6Da1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0a1a0"),
+            address!("0xBE6D8f0d05cC4be24d5167a3eF062215bE6D18a5"),
             SwapType::V3DeadlineTick,
         )),
         _ => None,
     }
 }
 
-fn determine_swap_direction(pool: &Pool) -> bool {
-    if pool.token0_address() == *WETH_ADDRESS {
+fn determine_swap_direction(pool: &Pool, weth: Address) -> bool {
+    if pool.token0_address() == weth {
         true
-    } else if pool.token1_address() == *WETH_ADDRESS {
+    } else if pool.token1_address() == weth {
         false
     } else {
         true // default
@@ -502,3 +531,58 @@ fn setup_router_calldata(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_returns_decode_or_drop_the_pool() {
+        let amounts = vec![U256::from(100u64), U256::from(42u64)];
+        assert_eq!(decode_swap_return(&amounts.abi_encode().into(), true), Some(U256::from(42u64)));
+        assert_eq!(decode_swap_return(&U256::from(7u64).abi_encode().into(), false), Some(U256::from(7u64)));
+
+        let empty: Vec<U256> = Vec::new();
+        assert_eq!(decode_swap_return(&empty.abi_encode().into(), true), None);
+        assert_eq!(decode_swap_return(&Bytes::from(vec![0xde, 0xad]), false), None);
+        assert_eq!(decode_swap_return(&Bytes::new(), true), None);
+    }
+
+    // Both pools are Uniswap V2 pairs on Base, the dead one drained so a round trip loses more than 5%.
+    // Run with FULL=<base rpc> FILTER_HEALTHY_POOL=<pair> FILTER_DEAD_POOL=<pair> cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn funded_swaps_keep_healthy_pools_and_drop_dead_ones() {
+        use crate::utile::constant::ChainDefaults;
+        use crate::utile::test_utils::{full_node_provider, v2_pool};
+
+        alloy::sol! {
+            #[sol(rpc)]
+            contract V2Pair {
+                function token0() external view returns (address);
+                function token1() external view returns (address);
+            }
+        }
+
+        let provider = full_node_provider();
+        let mut pools = Vec::new();
+        for var in ["FILTER_HEALTHY_POOL", "FILTER_DEAD_POOL"] {
+            let pool: Address = std::env::var(var)
+                .unwrap_or_else(|_| panic!("{var} must name a Uniswap V2 pair"))
+                .parse()
+                .unwrap();
+            let token0 = V2Pair::new(pool, &provider).token0().call().await.unwrap();
+            let token1 = V2Pair::new(pool, &provider).token1().call().await.unwrap();
+            pools.push(v2_pool(pool, token0, token1, 0, 0));
+        }
+        let (healthy, dead) = (pools[0].address(), pools[1].address());
+
+        let db = BlockStateDB::new(provider, DEFAULT_PROVIDER_TIMEOUT).unwrap();
+        let weth = ChainDefaults::for_chain(Chain::Base).weth;
+        let kept = filter_by_swap(pools, &db, SimConfig::for_chain(Chain::Base), weth).await.unwrap();
+
+        let kept: Vec<Address> = kept.iter().map(|pool| pool.address()).collect();
+        assert!(kept.contains(&healthy), "healthy pool {healthy} was dropped");
+        assert!(!kept.contains(&dead), "dead pool {dead} was kept");
+    }
+}
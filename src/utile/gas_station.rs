@@ -1,10 +1,23 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{File, create_dir_all};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard};
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use alloy::eips::eip1559::{BaseFeeParams, calc_next_block_base_fee};
 use alloy::primitives::U256;
+use anyhow::{Context, Result};
+use pool_sync::Chain;
+use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast::Receiver;
+use tracing::{info, warn};
 
 use crate::utile::events::Event;
+use crate::utile::swap::{
+    POOL_VERSION_AERODROME, POOL_VERSION_BALANCER, POOL_VERSION_CURVE, POOL_VERSION_MAVERICK,
+    POOL_VERSION_V2, POOL_VERSION_V3, SwapPath, pool_version_code,
+};
 
 /// Handles dynamic gas fee estimation using EIP-1559-style base fees.
 pub struct GasStation {
@@ -15,6 +28,200 @@ pub struct GasStation {
 const DEFAULT_PRIORITY_DIVISOR: u128 = 350_000;
 const PROFIT_PERCENTAGE_FOR_GAS: u128 = 2; // Spend up to 50% of profit
 
+// Path gas limits: headroom over the expected gas, and the most a single path may use
+const DEFAULT_PATH_GAS_PADDING_PERCENT: u64 = 25;
+const DEFAULT_PATH_MAX_GAS: u64 = 2_000_000;
+
+/// Gas limit policy for FlashSwap paths, padding the `PathGasModel` estimate of their `poolVersions`
+#[derive(Debug, Clone)]
+pub struct PathGasLimits {
+    /// Headroom over the expected gas, so a path using more than estimated doesn't run out of gas
+    pub padding_percent: u64,
+    /// Paths estimated above this are rejected instead of submitted
+    pub max: u64,
+}
+
+impl Default for PathGasLimits {
+    fn default() -> Self {
+        Self {
+            padding_percent: DEFAULT_PATH_GAS_PADDING_PERCENT,
+            max: DEFAULT_PATH_MAX_GAS,
+        }
+    }
+}
+
+impl PathGasLimits {
+    /// Defaults, with the padding and cap overridable through PATH_GAS_PADDING_PERCENT and PATH_GAS_MAX
+    pub fn from_env() -> Self {
+        let read = |key: &str| std::env::var(key).ok().and_then(|v| v.parse::<u64>().ok());
+        let defaults = Self::default();
+        Self {
+            padding_percent: read("PATH_GAS_PADDING_PERCENT").unwrap_or(defaults.padding_percent),
+            max: read("PATH_GAS_MAX").unwrap_or(defaults.max),
+        }
+    }
+
+    /// Gas limit for a path with the given hop `poolVersions`, uncapped
+    pub fn gas_limit(&self, model: &PathGasModel, pool_versions: &[u8]) -> u64 {
+        let estimate = model.estimate(pool_versions);
+        estimate.saturating_add(estimate.saturating_mul(self.padding_percent) / 100)
+    }
+
+    pub fn within_budget(&self, gas_limit: u64) -> bool {
+        gas_limit <= self.max
+    }
+}
+
+// Expected gas of the flash loan round trip and contract entry, on top of the hops
+const DEFAULT_FLASH_LOAN_OVERHEAD_GAS: u64 = 60_000;
+
+// Each receipt moves a hop cost 1/8 of the way to what it observed
+const CALIBRATION_WEIGHT: u128 = 8;
+
+/// Expected gas used by a path, per hop type. This is a best estimate rather than a limit,
+/// `PathGasLimits` pads it, and it is recalibrated from the gas real receipts report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathGasModel {
+    pub flash_loan_overhead: u64,
+    /// Expected gas of one hop, keyed by `poolVersions` code
+    pub hop_costs: HashMap<u8, u64>,
+}
+
+impl Default for PathGasModel {
+    fn default() -> Self {
+        Self {
+            flash_loan_overhead: DEFAULT_FLASH_LOAN_OVERHEAD_GAS,
+            // V3 includes a single tick crossing, deeper swaps are picked up by calibration
+            hop_costs: HashMap::from([
+                (POOL_VERSION_V2, 90_000),
+                (POOL_VERSION_V3, 120_000),
+                (POOL_VERSION_AERODROME, 150_000),
+                (POOL_VERSION_CURVE, 200_000),
+                (POOL_VERSION_MAVERICK, 150_000),
+                (POOL_VERSION_BALANCER, 180_000),
+            ]),
+        }
+    }
+}
+
+impl PathGasModel {
+    /// Load a calibrated model from `path`, starting from the defaults if it is missing or unreadable
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        match File::open(path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|e| {
+                warn!("Failed to read path gas model from {}: {:?}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        serde_json::to_writer(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Expected gas used by a path with the given hop `poolVersions`
+    pub fn estimate(&self, pool_versions: &[u8]) -> u64 {
+        pool_versions
+            .iter()
+            .fold(self.flash_loan_overhead, |gas, version| gas + self.hop_gas(*version))
+    }
+
+    /// Pulls the cost of every hop type in the path towards the share of `gas_used` it accounts for
+    pub fn calibrate(&mut self, pool_versions: &[u8], gas_used: u64) {
+        let estimated: u128 = pool_versions.iter().map(|version| self.hop_gas(*version) as u128).sum();
+        if estimated == 0 {
+            return;
+        }
+        let observed = gas_used.saturating_sub(self.flash_loan_overhead) as u128;
+
+        let versions: BTreeSet<u8> = pool_versions.iter().copied().collect();
+        for version in versions {
+            let cost = self.hop_gas(version) as u128;
+            let observed_cost = cost * observed / estimated;
+            let calibrated = (cost * (CALIBRATION_WEIGHT - 1) + observed_cost) / CALIBRATION_WEIGHT;
+            self.hop_costs.insert(version, calibrated as u64);
+        }
+    }
+
+    fn hop_gas(&self, pool_version: u8) -> u64 {
+        self.hop_costs
+            .get(&pool_version)
+            .copied()
+            // Unknown hop types get the most expensive known cost
+            .unwrap_or_else(|| self.hop_costs.values().copied().max().unwrap_or_default())
+    }
+}
+
+/// Where a chain's receipt-calibrated hop costs are kept across restarts
+pub fn path_gas_model_path(chain: Chain) -> String {
+    format!("cache/path_gas_model_{}.json", chain)
+}
+
+/// Path gas model of a single chain, shared by its searcher, which prices paths with it,
+/// and its transaction sender, which calibrates it from receipts and persists it to `path`
+#[derive(Debug)]
+pub struct ChainGasModel {
+    model: RwLock<PathGasModel>,
+    path: PathBuf,
+}
+
+impl ChainGasModel {
+    /// Starts from the model calibrated in earlier runs at `path`
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let model = PathGasModel::load(&path);
+        info!("Loaded path gas model from {}: {:?}", path.display(), model.hop_costs);
+        Self { model: RwLock::new(model), path }
+    }
+
+    /// Copy of the current model
+    pub fn snapshot(&self) -> PathGasModel {
+        self.read().clone()
+    }
+
+    /// Expected gas used by executing `path` through FlashSwap, flash loan included
+    pub fn estimate_path(&self, path: &SwapPath) -> u64 {
+        let pool_versions: Vec<u8> = path.steps.iter().map(|step| pool_version_code(step.protocol)).collect();
+        self.read().estimate(&pool_versions)
+    }
+
+    /// Expected gas of the flash loan itself, for quotes that simulate the hops without borrowing
+    pub fn flash_loan_overhead(&self) -> u64 {
+        self.read().flash_loan_overhead
+    }
+
+    /// Feeds the gas a receipt reported for a path with the given hop `poolVersions` back into the model.
+    /// The calibrated model is written out after the lock is released
+    pub fn record(&self, pool_versions: &[u8], gas_used: u64) {
+        let snapshot = {
+            let mut model = self.model.write().unwrap_or_else(|poisoned| {
+                warn!("Path gas model lock poisoned, recovering write guard");
+                poisoned.into_inner()
+            });
+            model.calibrate(pool_versions, gas_used);
+            model.clone()
+        };
+        if let Err(e) = snapshot.save(&self.path) {
+            warn!("Failed to persist path gas model to {}: {:?}", self.path.display(), e);
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, PathGasModel> {
+        self.model.read().unwrap_or_else(|poisoned| {
+            warn!("Path gas model lock poisoned, recovering read guard");
+            poisoned.into_inner()
+        })
+    }
+}
+
 impl GasStation {
     /// Create a new gas estimator with initial base_fee set to 0
     pub fn new() -> Self {
@@ -23,22 +230,43 @@ impl GasStation {
         }
     }
 
+    /// Predicted base fee of the next block in wei
+    #[inline]
+    pub fn base_fee(&self) -> u128 {
+        self.base_fee.load(Ordering::Relaxed) as u128
+    }
+
     /// Compute max fee and priority fee based on profit.
     /// Will spend up to 50% of the profit on gas (split between base + priority).
     pub fn get_gas_fees(&self, profit: U256) -> (u128, u128) {
+        self.get_gas_fees_for_limit(profit, DEFAULT_PRIORITY_DIVISOR as u64)
+    }
+
+    /// Same as `get_gas_fees`, spreading the gas budget over the path's actual gas limit
+    pub fn get_gas_fees_for_limit(&self, profit: U256, gas_limit: u64) -> (u128, u128) {
         let base_fee = self.base_fee.load(Ordering::Relaxed) as u128;
 
-        let max_total_gas_spend = (profit / U256::from(PROFIT_PERCENTAGE_FOR_GAS)).as_u128();
-        let priority_fee = max_total_gas_spend / DEFAULT_PRIORITY_DIVISOR;
+        let max_total_gas_spend = (profit / U256::from(PROFIT_PERCENTAGE_FOR_GAS)).saturating_to::<u128>();
+        let priority_fee = max_total_gas_spend / (gas_limit.max(1) as u128);
 
-        (base_fee + priority_fee, priority_fee)
+        (base_fee.saturating_add(priority_fee), priority_fee)
     }
 
     /// Asynchronously updates the base fee based on new block headers from the event stream.
-    pub async fn update_gas(&self, mut block_rx: Receiver<Event>) {
+    /// Tracks the next base fee from every new block until the feed closes or `shutdown` fires
+    pub async fn update_gas(&self, mut block_rx: Receiver<Event>, mut shutdown: Receiver<()>) {
         let base_fee_params = BaseFeeParams::optimism_canyon();
 
-        while let Ok(event) = block_rx.recv().await {
+        loop {
+            let event = tokio::select! {
+                event = block_rx.recv() => event,
+                _ = shutdown.recv() => return,
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+            };
             if let Event::NewBlock(header) = event {
                 // Safe unwrap with context in case of None
                 let base_fee = header
@@ -65,3 +293,95 @@ impl GasStation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gas_fees_spend_half_the_profit_over_the_gas_limit() {
+        let station = GasStation::new();
+        station.base_fee.store(100_000_000, Ordering::Relaxed);
+
+        // 0.001 ETH profit, half of it over 500k gas is 1 gwei of priority fee
+        let (max_fee, priority_fee) = station.get_gas_fees_for_limit(U256::from(1_000_000_000_000_000u128), 500_000);
+        assert_eq!(priority_fee, 1_000_000_000);
+        assert_eq!(max_fee, 1_100_000_000);
+    }
+
+    #[test]
+    fn gas_fees_saturate_on_huge_profit() {
+        let station = GasStation::new();
+        let (max_fee, priority_fee) = station.get_gas_fees_for_limit(U256::MAX, 1);
+        assert_eq!(priority_fee, u128::MAX);
+        assert_eq!(max_fee, u128::MAX);
+    }
+
+    #[test]
+    fn curve_and_v3_cost_more_than_two_v2_hops() {
+        use crate::utile::swap::{SwapPath, SwapStep};
+        use alloy::primitives::Address;
+        use pool_sync::PoolType;
+
+        let step = |protocol: PoolType| SwapStep {
+            pool_address: Address::ZERO,
+            token_in: Address::ZERO,
+            token_out: Address::ZERO,
+            protocol,
+            fee: 0,
+            split: Vec::new(),
+        };
+        let curve_v3 = SwapPath::new(vec![step(PoolType::CurveTwoCrypto), step(PoolType::UniswapV3)]);
+        let two_v2 = SwapPath::new(vec![step(PoolType::UniswapV2), step(PoolType::SushiSwapV2)]);
+
+        let file = std::env::temp_dir().join(format!("path_gas_model_missing_{}.json", std::process::id()));
+        let chain_model = ChainGasModel::load(file);
+        assert!(chain_model.estimate_path(&curve_v3) > chain_model.estimate_path(&two_v2));
+        let model = PathGasModel::default();
+        assert_eq!(model.estimate(&[POOL_VERSION_V2, POOL_VERSION_V2]), DEFAULT_FLASH_LOAN_OVERHEAD_GAS + 180_000);
+    }
+
+    #[test]
+    fn longer_paths_get_higher_limits_until_the_cap() {
+        let model = PathGasModel::default();
+        let limits = PathGasLimits { padding_percent: 25, max: 700_000 };
+        let two_hops = limits.gas_limit(&model, &[POOL_VERSION_V2; 2]);
+        let five_hops = limits.gas_limit(&model, &[POOL_VERSION_V2; 5]);
+
+        // 60k + 2 * 90k = 240k expected, padded by a quarter
+        assert_eq!(two_hops, 300_000);
+        assert!(five_hops > two_hops);
+        assert!(limits.within_budget(two_hops));
+        assert!(limits.within_budget(five_hops));
+
+        let five_curve_hops = limits.gas_limit(&model, &[POOL_VERSION_CURVE; 5]);
+        assert!(five_curve_hops > limits.max);
+        assert!(!limits.within_budget(five_curve_hops));
+    }
+
+    #[test]
+    fn calibration_converges_on_receipt_gas_and_survives_a_restart() {
+        let path = [POOL_VERSION_V2, POOL_VERSION_V3];
+        let mut model = PathGasModel::default();
+        // The default guess for V2 + V3 is 270k, the chain keeps reporting 400k
+        for _ in 0..100 {
+            model.calibrate(&path, 400_000);
+        }
+        let estimate = model.estimate(&path);
+        assert!(estimate.abs_diff(400_000) < 4_000, "estimate {estimate}");
+        // Hop costs keep their proportions, V3 still costs more than V2
+        assert!(model.hop_costs[&POOL_VERSION_V3] > model.hop_costs[&POOL_VERSION_V2]);
+
+        // Every recorded receipt is persisted, a restart picks up the calibrated model
+        let file = std::env::temp_dir().join(format!("path_gas_model_{}.json", std::process::id()));
+        let chain_model = ChainGasModel::load(file.clone());
+        chain_model.record(&path, 400_000);
+        let reloaded = ChainGasModel::load(file.clone());
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(reloaded.snapshot().estimate(&path), chain_model.snapshot().estimate(&path));
+        assert!(chain_model.snapshot().estimate(&path) > PathGasModel::default().estimate(&path));
+
+        // Receipts of one chain never move another chain's hop costs
+        assert_ne!(path_gas_model_path(Chain::Base), path_gas_model_path(Chain::Ethereum));
+    }
+}
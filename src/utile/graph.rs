@@ -1,10 +1,15 @@
 use std::collections::HashSet;
-use std::hash::{DefaultHasher, Hash, Hasher};
+use std::fs::{File, create_dir_all};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
 
-use alloy::primitives::Address;
+use alloy::primitives::{Address, B256, keccak256};
 use alloy::sol_types::SolCall;
+use anyhow::{Context, Result};
+use log::{info, warn};
 use petgraph::graph::UnGraph;
 use petgraph::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use pool_sync::{BalancerV2Pool, CurveTriCryptoPool, Pool, PoolInfo};
 
@@ -14,39 +19,92 @@ use crate::utile::swap::{SwapPath, SwapStep};
 
 pub struct ArbGraph;
 
+/// On-disk form of generated cycles, tagged with the pool set they were built from
+#[derive(Serialize, Deserialize)]
+struct CachedCycles {
+    fingerprint: B256,
+    cycles: Vec<SwapPath>,
+}
+
 impl ArbGraph {
-    /// Generate arbitrage cycles using known pools
-    pub async fn generate_cycles(working_pools: Vec<Pool>) -> Vec<SwapPath> {
-        // Fetch token (e.g. WETH) as starting point from env
-        let token: Address = std::env::var("WETH")
-            .expect("WETH environment variable must be set")
-            .parse()
-            .expect("Invalid WETH address");
+    /// Keccak hash over the pool universe, cached cycles are only reused when this matches.
+    /// Stable across builds and toolchains, unlike std's `DefaultHasher`
+    pub fn pool_fingerprint(pools: &[Pool], start_tokens: &[Address]) -> B256 {
+        let mut addresses: Vec<Address> = pools.iter().map(|pool| pool.address()).collect();
+        addresses.sort_unstable();
+        addresses.dedup();
+
+        let mut encoded = Vec::with_capacity(8 + (start_tokens.len() + addresses.len()) * 20);
+        encoded.extend_from_slice(&(start_tokens.len() as u64).to_be_bytes());
+        for token in start_tokens {
+            encoded.extend_from_slice(token.as_slice());
+        }
+        for address in &addresses {
+            encoded.extend_from_slice(address.as_slice());
+        }
+        keccak256(encoded)
+    }
+
+    /// Write generated cycles to `path` along with the fingerprint of their pool set
+    pub fn save_cycles(path: impl AsRef<Path>, fingerprint: B256, cycles: &[SwapPath]) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let cached = CachedCycles {
+            fingerprint,
+            cycles: cycles.to_vec(),
+        };
+        serde_json::to_writer(BufWriter::new(file), &cached)?;
+        Ok(())
+    }
+
+    /// Load cycles from `path`, returns None if the file is missing, unreadable or stale
+    pub fn load_cycles(path: impl AsRef<Path>, fingerprint: B256) -> Option<Vec<SwapPath>> {
+        let path = path.as_ref();
+        let file = File::open(path).ok()?;
+
+        let cached: CachedCycles = match serde_json::from_reader(BufReader::new(file)) {
+            Ok(cached) => cached,
+            Err(e) => {
+                warn!("Failed to read cached cycles from {}: {:?}", path.display(), e);
+                return None;
+            }
+        };
+
+        if cached.fingerprint != fingerprint {
+            info!("Cached cycles in {} are stale, regenerating", path.display());
+            return None;
+        }
+
+        Some(cached.cycles)
+    }
 
+    /// Generate arbitrage cycles using known pools, each starting and ending at one of `start_tokens`.
+    /// The graph is built once, start tokens missing from it are skipped
+    pub async fn generate_cycles(working_pools: Vec<Pool>, start_tokens: &[Address]) -> Vec<SwapPath> {
         let graph = Self::build_graph(working_pools).await;
 
-        let start_node = graph
-            .node_indices()
-            .find(|node| graph[*node] == token)
-            .expect("Start token not found in graph");
-
-        let cycles = Self::find_all_arbitrage_paths(&graph, start_node, 2);
-
-        // Hash & structure the cycles
-        cycles
-            .into_iter()
-            .map(|cycle| {
-                let mut hasher = DefaultHasher::new();
-                for step in &cycle {
-                    step.hash(&mut hasher);
+        let mut seen: HashSet<SwapPath> = HashSet::new();
+        let mut paths = Vec::new();
+        for token in start_tokens {
+            let Some(start_node) = graph.node_indices().find(|node| graph[*node] == *token) else {
+                warn!("Start token {} not found in graph, skipping", token);
+                continue;
+            };
+
+            // Hash & structure the cycles
+            for cycle in Self::find_all_arbitrage_paths(&graph, start_node, 2) {
+                let path = SwapPath::new(cycle);
+                if seen.insert(path.clone()) {
+                    paths.push(path);
                 }
+            }
+        }
 
-                SwapPath {
-                    steps: cycle,
-                    hash: hasher.finish(),
-                }
-            })
-            .collect()
+        paths
     }
 
     /// Build token connectivity graph from pool list
@@ -221,6 +279,7 @@ impl ArbGraph {
                             token_out: graph[*quote],
                             protocol: pool.pool_type(),
                             fee: pool.fee(),
+                            split: Vec::new(),
                         })
                         .collect();
 
@@ -246,3 +305,79 @@ impl ArbGraph {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::test_utils::{aerodrome_pool, v2_pool};
+
+    // Two pool types on each pair, so every pair closes 2-hop cycles in both directions
+    fn pools(weth: Address, usdc: Address, cbeth: Address, dai: Address) -> Vec<Pool> {
+        vec![
+            v2_pool(Address::repeat_byte(0xb1), weth, usdc, 1_000, 3_000),
+            aerodrome_pool(Address::repeat_byte(0xb2), weth, usdc, 1_000, 3_000, false),
+            v2_pool(Address::repeat_byte(0xb3), cbeth, dai, 1_000, 3_000),
+            aerodrome_pool(Address::repeat_byte(0xb4), cbeth, dai, 1_000, 3_000, false),
+        ]
+    }
+
+    // WETH and cbETH trade on disjoint pairs. USDC shares WETH's pools, its cycles are the same loops
+    // borrowing the other token, so they are kept. WETH listed twice only adds its cycles once
+    #[tokio::test]
+    async fn cycles_are_rooted_at_every_start_token_once() {
+        let [weth, usdc, cbeth, dai, unknown] = [0xa1, 0xa2, 0xa3, 0xa4, 0xa5].map(Address::repeat_byte);
+        let cycles = ArbGraph::generate_cycles(pools(weth, usdc, cbeth, dai), &[weth, cbeth, usdc, weth, unknown]).await;
+
+        let rooted_at = |token: Address| cycles.iter().filter(|path| path.steps[0].token_in == token).count();
+        assert_eq!(rooted_at(weth), 2);
+        assert_eq!(rooted_at(cbeth), 2);
+        assert_eq!(rooted_at(usdc), 2);
+        assert_eq!(cycles.len(), 6);
+        assert_eq!(cycles.iter().collect::<HashSet<_>>().len(), cycles.len());
+
+        for path in &cycles {
+            let (first, last) = (&path.steps[0], path.steps.last().unwrap());
+            assert_eq!(first.token_in, last.token_out);
+            assert_ne!(first.pool_address, last.pool_address);
+        }
+        // cbETH cycles never touch the WETH/USDC pools
+        assert!(cycles
+            .iter()
+            .filter(|path| path.steps[0].token_in == cbeth)
+            .all(|path| path.steps.iter().all(|step| [cbeth, dai].contains(&step.token_in))));
+    }
+
+    #[test]
+    fn fingerprints_ignore_pool_order_but_not_pool_changes() {
+        let [weth, usdc, cbeth, dai] = [0xa1, 0xa2, 0xa3, 0xa4].map(Address::repeat_byte);
+        let pools = pools(weth, usdc, cbeth, dai);
+        let fingerprint = ArbGraph::pool_fingerprint(&pools, &[weth]);
+
+        let reversed: Vec<Pool> = pools.iter().rev().cloned().collect();
+        assert_eq!(ArbGraph::pool_fingerprint(&reversed, &[weth]), fingerprint);
+
+        let mut grown = pools.clone();
+        grown.push(v2_pool(Address::repeat_byte(0xb5), usdc, dai, 1_000, 1_000));
+        assert_ne!(ArbGraph::pool_fingerprint(&grown, &[weth]), fingerprint);
+        assert_ne!(ArbGraph::pool_fingerprint(&pools[1..], &[weth]), fingerprint);
+        assert_ne!(ArbGraph::pool_fingerprint(&pools, &[weth, cbeth]), fingerprint);
+    }
+
+    #[tokio::test]
+    async fn cached_cycles_round_trip_until_the_pools_change() {
+        let [weth, usdc, cbeth, dai] = [0xa1, 0xa2, 0xa3, 0xa4].map(Address::repeat_byte);
+        let pools = pools(weth, usdc, cbeth, dai);
+        let cycles = ArbGraph::generate_cycles(pools.clone(), &[weth]).await;
+        let fingerprint = ArbGraph::pool_fingerprint(&pools, &[weth]);
+
+        let file = std::env::temp_dir().join(format!("cycles_{}.json", std::process::id()));
+        ArbGraph::save_cycles(&file, fingerprint, &cycles).unwrap();
+        let loaded = ArbGraph::load_cycles(&file, fingerprint);
+        let stale = ArbGraph::load_cycles(&file, ArbGraph::pool_fingerprint(&pools[..2], &[weth]));
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(loaded, Some(cycles));
+        assert_eq!(stale, None);
+        assert_eq!(ArbGraph::load_cycles(&file, fingerprint), None);
+    }
+}
@@ -1,5 +1,6 @@
 use alloy::consensus::constants::KECCAK_EMPTY;
 use alloy::primitives::{Address, B256, StorageKey, U256};
+use dashmap::DashMap;
 use eyre::{Context, Result};
 use reth::api::NodeTypesWithDBAdapter;
 use reth::primitives::{Bytecode, H256};
@@ -33,9 +34,14 @@ pub enum HistoryDbError {
 impl DBErrorMarker for HistoryDbError {}
 
 /// Core struct that provides access to historical state from Reth database.
+/// Account and storage reads are cached in memory; state at a fixed block is immutable,
+/// so the cache only needs clearing when the DB is moved to another block.
 pub struct HistoryDB {
     db_provider: StateProviderBox,
     provider_factory: ProviderFactory<NodeTypesWithDBAdapter<EthereumNode, Arc<DatabaseEnv>>>,
+    block: u64,
+    account_cache: DashMap<Address, Option<AccountInfo>>,
+    storage_cache: DashMap<(Address, U256), U256>,
 }
 
 impl HistoryDB {
@@ -69,8 +75,37 @@ impl HistoryDB {
         Ok(Self {
             db_provider: provider,
             provider_factory: factory,
+            block,
+            account_cache: DashMap::new(),
+            storage_cache: DashMap::new(),
         })
     }
+
+    /// Block the historical state is currently read at
+    pub fn block(&self) -> u64 {
+        self.block
+    }
+
+    /// Repositions the DB to the state at `block`, dropping all cached reads
+    pub fn set_block(&mut self, block: u64) -> Result<()> {
+        if block == self.block {
+            return Ok(());
+        }
+
+        self.db_provider = self
+            .provider_factory
+            .history_by_block_number(block)
+            .wrap_err_with(|| format!("Failed to load historical state at block {}", block))?;
+        self.block = block;
+        self.clear_cache();
+        Ok(())
+    }
+
+    /// Drops all cached accounts and storage slots
+    pub fn clear_cache(&self) {
+        self.account_cache.clear();
+        self.storage_cache.clear();
+    }
 }
 
 // === revm Database Implementation ===
@@ -102,6 +137,10 @@ impl DatabaseRef for HistoryDB {
     type Error = HistoryDbError;
 
     fn basic_ref(&self, address: Address) -> std::result::Result<Option<AccountInfo>, Self::Error> {
+        if let Some(cached) = self.account_cache.get(&address) {
+            return Ok(cached.clone());
+        }
+
         let reth_account_opt = self.db_provider.basic_account(&address)
             .map_err(HistoryDbError::Provider)?;
 
@@ -130,9 +169,13 @@ impl DatabaseRef for HistoryDB {
                     },
                 };
 
+                self.account_cache.insert(address, Some(account_info.clone()));
                 Ok(Some(account_info))
             },
-            None => Ok(None),
+            None => {
+                self.account_cache.insert(address, None);
+                Ok(None)
+            }
         }
     }
 
@@ -158,10 +201,16 @@ impl DatabaseRef for HistoryDB {
     }
 
     fn storage_ref(&self, address: Address, index: U256) -> std::result::Result<U256, Self::Error> {
+        if let Some(cached) = self.storage_cache.get(&(address, index)) {
+            return Ok(*cached);
+        }
+
         let key = StorageKey::from(index);
         let value = self.db_provider.storage(address, key)
-            .map_err(HistoryDbError::Provider)?;
-        Ok(value.unwrap_or_default())
+            .map_err(HistoryDbError::Provider)?
+            .unwrap_or_default();
+        self.storage_cache.insert((address, index), value);
+        Ok(value)
     }
 
     fn block_hash_ref(&self, number: u64) -> std::result::Result<B256, Self::Error> {
@@ -1,6 +1,6 @@
 use std::{
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicBool, Ordering::Relaxed},
     },
     time::Duration,
@@ -8,47 +8,281 @@ use std::{
 // Removed unstable std mpmc channel import
 // use std::sync::mpmc::channel;
 use crate::utile::{
-    estimator::Estimator, events::Event, filter::filter_pools, gas_station::GasStation,
-    graph::ArbGraph, market_state::MarketState, searcher::Searchoor, stream::stream_new_blocks,
+    api::{self, ApiState},
+    constant::{Deployment, FlashLoanConfig},
+    blacklist::watch_blacklist,
+    errors::{WorkerError, env_var},
+    estimator::Estimator, events::Event, filter::filter_pools, gas_station::{ChainGasModel, GasStation, path_gas_model_path},
+    graph::ArbGraph, market_state::MarketState, sandwicher::Sandwicher, searcher::Searchoor,
+    stream::{stream_new_blocks, stream_pending_txs},
     tx_sender::TransactionSender,
+    watchdog::{Heartbeat, Watchdog},
 };
+use alloy::primitives::Address;
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use alloy::providers::ProviderBuilder;
 //use alloy_provider::{ProviderBuilder, Provider};
 use log::{error, info, warn};
-use pool_sync::{Chain, Pool};
+use pool_sync::{Chain, Pool, PoolInfo, PoolType};
 use tokio::signal;
 use tokio::sync::{
     broadcast,
     mpsc::{Receiver, Sender, channel},
 };
+use tokio::task::JoinHandle;
 use alloy_transport_http::Http;
 use reqwest::Client;
 use anyhow::Context;
 use alloy::providers::Provider;
 use alloy::network::Network;
 
-/// Bootstraps the entire system: syncing, simulation, and arbitrage search
-pub async fn start_workers(pools: Vec<Pool>, last_synced_block: u64) {
-    let (block_sender, _) = broadcast::channel::<Event>(100);
-    let (block_tx, mut block_rx): (Sender<Event>, Receiver<Event>) = channel(100);
-    let (address_sender, address_receiver): (Sender<Event>, Receiver<Event>) = channel(100);
-    let (paths_sender, paths_receiver): (Sender<Event>, Receiver<Event>) = channel(100);
-    let (profitable_sender, profitable_receiver): (Sender<Event>, Receiver<Event>) = channel(100);
+/// Everything one chain's pipeline needs, every configured chain runs independently of the others
+pub struct ChainConfig {
+    pub chain: Chain,
+    pub http_url: String,
+    pub ipc_path: String,
+    pub pools: Vec<Pool>,
+    pub last_synced_block: u64,
+    /// Token profit is realized in, the estimator prices every path in it
+    pub base_token: Address,
+    /// Tokens cycles are rooted at, `base_token` unless START_TOKENS lists more
+    pub start_tokens: Vec<Address>,
+    /// Quoter, FlashSwap, simulation account and relays
+    pub deployment: Deployment,
+    pub api_addr: Option<SocketAddr>,
+    pub channels: ChannelConfig,
+}
+
+/// Capacities of the pipeline channels of one chain
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelConfig {
+    /// Broadcast block/pending-tx feed, receivers that fall this far behind miss events
+    pub block_broadcast: usize,
+    /// Proxied block feed into the state updater
+    pub block: usize,
+    /// Touched pools and changed slots into the searcher
+    pub address: usize,
+    /// Candidate paths into the simulator
+    pub paths: usize,
+    /// Validated paths and bundles into the transaction sender
+    pub profitable: usize,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            block_broadcast: 100,
+            block: 100,
+            address: 100,
+            paths: 100,
+            profitable: 100,
+        }
+    }
+}
+
+impl ChannelConfig {
+    /// Defaults, overridable per channel through CHANNEL_BLOCK_BROADCAST, CHANNEL_BLOCK,
+    /// CHANNEL_ADDRESS, CHANNEL_PATHS and CHANNEL_PROFITABLE
+    pub fn from_env() -> Self {
+        let read = |key: &str| std::env::var(key).ok().and_then(|v| v.parse::<usize>().ok()).filter(|cap| *cap > 0);
+        let defaults = Self::default();
+        Self {
+            block_broadcast: read("CHANNEL_BLOCK_BROADCAST").unwrap_or(defaults.block_broadcast),
+            block: read("CHANNEL_BLOCK").unwrap_or(defaults.block),
+            address: read("CHANNEL_ADDRESS").unwrap_or(defaults.address),
+            paths: read("CHANNEL_PATHS").unwrap_or(defaults.paths),
+            profitable: read("CHANNEL_PROFITABLE").unwrap_or(defaults.profitable),
+        }
+    }
+}
+
+impl ChainConfig {
+    /// Reads FULL, IPC, START_TOKENS, API_ADDR and the `Deployment` vars prefixed with the chain name
+    /// (e.g. BASE_FULL), falling back to the unprefixed names so single-chain setups keep working
+    pub fn from_env(chain: Chain, pools: Vec<Pool>, last_synced_block: u64) -> Result<Self, WorkerError> {
+        let api_addr = match chain_var(chain, "API_ADDR") {
+            Ok(addr) => match addr.parse::<SocketAddr>() {
+                Ok(addr) => Some(addr),
+                Err(e) => {
+                    warn!("Invalid API_ADDR {} for {}: {:?}", addr, chain, e);
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+
+        let deployment = Deployment::from_vars(chain, |key| chain_var(chain, key))?;
+        let base_token = deployment.weth;
+        // START_TOKENS is a comma separated address list, e.g. WETH,USDC,cbETH
+        let start_tokens = match chain_var(chain, "START_TOKENS") {
+            Ok(list) => list
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(|token| {
+                    token.parse().map_err(|e| {
+                        WorkerError::ConfigMissing(format!("Invalid START_TOKENS entry {} for {}: {:?}", token, chain, e))
+                    })
+                })
+                .collect::<Result<Vec<Address>, _>>()?,
+            Err(_) => vec![base_token],
+        };
+
+        Ok(Self {
+            chain,
+            http_url: chain_var(chain, "FULL")?,
+            ipc_path: chain_var(chain, "IPC")?,
+            pools,
+            last_synced_block,
+            base_token,
+            start_tokens,
+            deployment,
+            api_addr,
+            channels: ChannelConfig::from_env(),
+        })
+    }
+}
+
+/// Reads `{CHAIN}_{key}`, or `key` when the chain specific var is not set
+fn chain_var(chain: Chain, key: &str) -> Result<String, WorkerError> {
+    let prefixed = format!("{}_{}", chain.to_string().to_uppercase(), key);
+    std::env::var(&prefixed).or_else(|_| env_var(key))
+}
+
+/// How long workers get to finish their current unit of work once shutdown is signalled
+const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
 
+/// Handles of the shutdown-aware workers, awaited before `start_workers` returns
+type Workers = Arc<Mutex<Vec<JoinHandle<()>>>>;
+
+/// Bootstraps the entire system: one syncing, simulation and arbitrage search pipeline per chain
+pub async fn start_workers(configs: Vec<ChainConfig>) -> Result<(), WorkerError> {
     // Graceful shutdown channel
     let (shutdown_tx, mut shutdown_rx) = tokio::sync::broadcast::channel::<()>(1);
 
+    // --- Blacklist ---
+    // Hot-reload BLACKLIST_FILE so tokens can be excluded without a restart
+    tokio::spawn(watch_blacklist(Duration::from_secs(30)));
+
+    // --- Watchdog ---
+    // Warns (or exits, with WATCHDOG_EXIT) when a worker stops making progress
+    let watchdog = Arc::new(Watchdog::from_env());
+    tokio::spawn(Arc::clone(&watchdog).run());
+
+    // --- Pipelines ---
+    let workers: Workers = Arc::new(Mutex::new(Vec::new()));
+    for config in configs {
+        let name = format!("{}/setup", config.chain);
+        let pipeline = tokio::spawn(run_chain(
+            config,
+            Arc::clone(&watchdog),
+            shutdown_tx.clone(),
+            Arc::clone(&workers),
+        ));
+        supervise(name, pipeline, shutdown_tx.clone());
+    }
+
+    // --- Graceful Shutdown Handler ---
+    {
+        let shutdown_tx = shutdown_tx.clone();
+        tokio::spawn(async move {
+            if let Err(err) = signal::ctrl_c().await {
+                error!("Failed to listen for shutdown: {:?}", err);
+            }
+            info!("🛑 Ctrl-C detected. Shutting down...");
+            let _ = shutdown_tx.send(());
+        });
+    }
+
+    // --- Await Shutdown Signal ---
+    let _ = shutdown_rx.recv().await;
+    info!("🚪 All workers will now terminate.");
+
+    // --- Drain Workers ---
+    // SHUTDOWN_GRACE_SECS bounds how long in-flight blocks and transactions get to finish
+    let grace = std::env::var("SHUTDOWN_GRACE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SHUTDOWN_GRACE);
+    let handles = std::mem::take(&mut *workers.lock().unwrap());
+    let count = handles.len();
+    match tokio::time::timeout(grace, futures::future::join_all(handles)).await {
+        Ok(_) => info!("All {} workers stopped", count),
+        Err(_) => warn!("Workers did not stop within {:?}, exiting anyway", grace),
+    }
+    Ok(())
+}
+
+/// Spawns the full pipeline for a single chain, every channel and worker it creates is scoped to that chain
+async fn run_chain(
+    config: ChainConfig,
+    watchdog: Arc<Watchdog>,
+    shutdown_tx: broadcast::Sender<()>,
+    workers: Workers,
+) -> Result<(), WorkerError> {
+    let ChainConfig {
+        chain,
+        http_url,
+        ipc_path,
+        pools,
+        last_synced_block,
+        base_token,
+        start_tokens,
+        deployment,
+        api_addr,
+        channels,
+    } = config;
+
+    let (block_sender, _) = broadcast::channel::<Event>(channels.block_broadcast);
+    let (block_tx, mut block_rx): (Sender<Event>, Receiver<Event>) = channel(channels.block);
+    let (address_sender, address_receiver): (Sender<Event>, Receiver<Event>) = channel(channels.address);
+    let (paths_sender, paths_receiver): (Sender<Event>, Receiver<Event>) = channel(channels.paths);
+    let (profitable_sender, profitable_receiver): (Sender<Event>, Receiver<Event>) = channel(channels.profitable);
+
+    // --- Provider ---
+    let parsed_url = http_url
+        .parse::<reqwest::Url>()
+        .map_err(|e| WorkerError::ConfigMissing(format!("Invalid http url for {}: {:?}", chain, e)))?;
+    // Assuming Http transport using reqwest client
+    let http_client = Client::new();
+    let provider = ProviderBuilder::new()
+        // .with_recommended_fillers() // Consider adding fillers
+        .provider(alloy_transport_http::Http::new_with_client(parsed_url, http_client));
+    let provider = Arc::new(provider); // Wrap in Arc
+
     // --- Pool Filtering ---
-    info!("Pool count before filtering: {}", pools.len());
-    let pools = filter_pools(pools, 4000, Chain::Base).await.context("Failed to filter pools")?;
-    info!("Pool count after filtering: {}", pools.len());
+    info!("[{}] Pool count before filtering: {}", chain, pools.len());
+    let pools = filter_pools(pools, 4000, chain, deployment.weth, Arc::clone(&provider))
+        .await
+        .context("Failed to filter pools")?;
+    info!("[{}] Pool count after filtering: {}", chain, pools.len());
+
+    let stream_heartbeat = Arc::new(Heartbeat::new());
+    watchdog.track(format!("{}/block_stream", chain), Arc::clone(&stream_heartbeat));
 
     // --- Block Event Proxy ---
     {
         let mut block_subscriber = block_sender.subscribe();
         let block_tx = block_tx.clone();
         tokio::spawn(async move {
-            while let Ok(event) = block_subscriber.recv().await {
+            loop {
+                let event = match block_subscriber.recv().await {
+                    Ok(event) => event,
+                    // The broadcast dropped its oldest events, blocks among them are never processed
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(
+                            "[{}] Block proxy lagged, {} events dropped (broadcast capacity {})",
+                            chain, missed, channels.block_broadcast
+                        );
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if matches!(event, Event::NewBlock(_)) {
+                    stream_heartbeat.beat();
+                }
                 if block_tx.send(event).await.is_err() {
                     break;
                 }
@@ -57,61 +291,93 @@ pub async fn start_workers(pools: Vec<Pool>, last_synced_block: u64) {
     }
 
     // --- Streamer to push new blocks into broadcast ---
-    tokio::spawn(stream_new_blocks(block_sender.clone()));
+    let block_stream = tokio::spawn(stream_new_blocks(
+        block_sender.clone(),
+        ipc_path.clone(),
+        shutdown_tx.subscribe(),
+    ));
+    workers.lock().unwrap().push(block_stream);
 
     // --- Gas Station ---
     let gas_station = Arc::new(GasStation::new());
     {
         let gas_station = Arc::clone(&gas_station);
-        let mut block_gas_sub = block_sender.subscribe();
-        tokio::spawn(async move {
-            gas_station.update_gas(block_gas_sub).await;
+        let block_gas_sub = block_sender.subscribe();
+        let shutdown = shutdown_tx.subscribe();
+        let gas_task = tokio::spawn(async move {
+            gas_station.update_gas(block_gas_sub, shutdown).await;
         });
+        workers.lock().unwrap().push(gas_task);
     }
 
     // --- State Catch-up Flag ---
     let caught_up = Arc::new(AtomicBool::new(false));
 
     // --- Market State ---
-    info!("Initializing market state...");
-    let http_url_str = std::env::var("FULL").context("FULL env var not set")?;
-    let http_url = http_url_str.parse::<reqwest::Url>().context("Failed to parse FULL env var as URL")?;
-    // Assuming Http transport using reqwest client
-    let http_client = Client::new();
-    let provider = ProviderBuilder::new()
-        // .with_recommended_fillers() // Consider adding fillers
-        .provider(alloy_transport_http::Http::new_with_client(http_url, http_client));
-    let provider = Arc::new(provider); // Wrap in Arc
-
-    let market_state = MarketState::init_state_and_start_stream(
+    info!("[{}] Initializing market state...", chain);
+    let (market_state, state_updater) = MarketState::init_state_and_start_stream(
         pools.clone(),
         block_rx,
         address_sender.clone(),
         last_synced_block,
         provider,
+        http_url.clone(),
         Arc::clone(&caught_up),
+        deployment.clone(),
+        shutdown_tx.subscribe(),
     )
     .await
-    .expect("Failed to initialize market state");
+    .context("Failed to initialize market state")?;
+    let state_updater = supervise(format!("{}/state_updater", chain), state_updater, shutdown_tx.clone());
+    workers.lock().unwrap().push(state_updater);
 
-    info!("Market state initialized!");
+    info!("[{}] Market state initialized!", chain);
+    watchdog.track(format!("{}/state_updater", chain), Arc::clone(&market_state.heartbeat));
 
     // --- Wait for catch-up ---
-    info!("Waiting for block sync before initializing estimator...");
+    info!("[{}] Waiting for block sync before initializing estimator...", chain);
     while !caught_up.load(Relaxed) {
         tokio::time::sleep(Duration::from_millis(100)).await;
     }
 
     // --- Estimator Init ---
-    info!("Calculating initial rates...");
-    let mut estimator = Estimator::new(Arc::clone(&market_state));
+    info!("[{}] Calculating initial rates...", chain);
+    let mut estimator = Estimator::new(Arc::clone(&market_state), base_token);
     estimator.process_pools(pools.clone());
-    info!("Initial rates calculated!");
+    info!("[{}] Initial rates calculated!", chain);
+
+    // --- Flash Loans ---
+    // FLASH_LOAN_PROVIDER / FLASH_LOAN_FEE_BPS set what the searcher charges paths for borrowing their input
+    let flash_loan = FlashLoanConfig::from_env();
+    // DRY_RUN signs and logs would-be transactions without broadcasting them
+    let dry_run: bool = std::env::var("DRY_RUN")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(false);
+    flash_loan.ensure_executable(dry_run)?;
+
+    // --- Path Gas Model ---
+    // Hop costs calibrated from this chain's receipts in earlier runs
+    let gas_model = Arc::new(ChainGasModel::load(path_gas_model_path(chain)));
 
     // --- Arbitrage Cycles ---
-    info!("Generating arbitrage cycles...");
-    let cycles = ArbGraph::generate_cycles(pools.clone()).await;
-    info!("Generated {} arbitrage cycles", cycles.len());
+    let fingerprint = ArbGraph::pool_fingerprint(&pools, &start_tokens);
+    let cycles_file = format!("cache/cycles_{}.json", chain);
+    let cycles = match ArbGraph::load_cycles(&cycles_file, fingerprint) {
+        Some(cycles) => {
+            info!("[{}] Loaded {} cached arbitrage cycles", chain, cycles.len());
+            cycles
+        }
+        None => {
+            info!("[{}] Generating arbitrage cycles...", chain);
+            let cycles = ArbGraph::generate_cycles(pools.clone(), &start_tokens).await;
+            info!("[{}] Generated {} arbitrage cycles", chain, cycles.len());
+            if let Err(e) = ArbGraph::save_cycles(&cycles_file, fingerprint, &cycles) {
+                warn!("Failed to cache arbitrage cycles: {:?}", e);
+            }
+            cycles
+        }
+    };
 
     // --- Simulator ---
     {
@@ -122,34 +388,112 @@ pub async fn start_workers(pools: Vec<Pool>, last_synced_block: u64) {
 
     // --- Searcher ---
     {
-        let mut searcher = Searchoor::new(cycles, Arc::clone(&market_state), estimator);
-        tokio::spawn(async move {
-            if let Err(e) = searcher.search_paths(paths_sender, address_receiver).await {
-                error!("Searcher failed: {:?}", e);
-            }
+        let mut searcher = Searchoor::new(
+            cycles,
+            Arc::clone(&market_state),
+            estimator,
+            flash_loan.clone(),
+            Arc::clone(&gas_model),
+        );
+        watchdog.track(format!("{}/searcher", chain), searcher.heartbeat());
+
+        // --- API ---
+        // Read-only status endpoints, only served when an API address is configured
+        if let Some(addr) = api_addr {
+            let api_state = ApiState {
+                market_state: Arc::clone(&market_state),
+                caught_up: Arc::clone(&caught_up),
+                cache: searcher.cache(),
+                opportunities: searcher.opportunities(),
+            };
+            tokio::spawn(api::serve(addr, api_state));
+        }
+
+        let shutdown = shutdown_tx.subscribe();
+        let searcher_task = tokio::spawn(async move {
+            searcher.search_paths(paths_sender, address_receiver, shutdown).await
         });
+        let searcher_task = supervise(format!("{}/searcher", chain), searcher_task, shutdown_tx.clone());
+        workers.lock().unwrap().push(searcher_task);
+    }
+
+    // --- Mempool ---
+    // MEMPOOL streams pending router swaps and sizes sandwiches around them
+    let mempool: bool = std::env::var("MEMPOOL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(false);
+    if mempool {
+        let v2_pools: HashMap<(Address, Address), Address> = pools
+            .iter()
+            .filter(|pool| pool.pool_type() == PoolType::UniswapV2)
+            .map(|pool| {
+                let (a, b) = (pool.token0_address(), pool.token1_address());
+                ((a.min(b), a.max(b)), pool.address())
+            })
+            .collect();
+        let pending_stream = tokio::spawn(stream_pending_txs(
+            block_sender.clone(),
+            Arc::new(v2_pools),
+            deployment.v2_router,
+            ipc_path,
+            shutdown_tx.subscribe(),
+        ));
+        workers.lock().unwrap().push(pending_stream);
+
+        let sandwicher = Sandwicher::new(Arc::clone(&market_state));
+        let sandwich_task = tokio::spawn(sandwicher.run(
+            block_sender.subscribe(),
+            profitable_sender.clone(),
+            shutdown_tx.subscribe(),
+        ));
+        workers.lock().unwrap().push(sandwich_task);
     }
 
     // --- Transaction Sender ---
     {
-        let mut tx_sender = TransactionSender::new(Arc::clone(&gas_station)).await;
-        tokio::spawn(async move {
-            tx_sender.send_transactions(profitable_receiver).await;
+        let mut tx_sender = TransactionSender::new(
+            Arc::clone(&gas_station),
+            dry_run,
+            http_url,
+            &deployment,
+            format!("cache/ledger_{}.jsonl", chain),
+            flash_loan,
+            gas_model,
+        )
+        .await
+        .context("Failed to initialize transaction sender")?;
+        let shutdown = shutdown_tx.subscribe();
+        let sender_task = tokio::spawn(async move {
+            tx_sender.send_transactions(profitable_receiver, shutdown).await;
         });
+        workers.lock().unwrap().push(sender_task);
     }
 
-    // --- Graceful Shutdown Handler ---
+    info!("[{}] Pipeline started", chain);
+    Ok(())
+}
+
+/// Logs how a worker ended, a fatal error (or a panic) triggers shutdown of the whole bot
+fn supervise(
+    name: String,
+    task: JoinHandle<Result<(), WorkerError>>,
+    shutdown_tx: broadcast::Sender<()>,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
-        if let Err(err) = signal::ctrl_c().await {
-            error!("Failed to listen for shutdown: {:?}", err);
+        match task.await {
+            Ok(Ok(())) => info!("Worker {} finished", name),
+            Ok(Err(e)) if e.is_fatal() => {
+                error!("Worker {} failed fatally: {}", name, e);
+                let _ = shutdown_tx.send(());
+            }
+            Ok(Err(e)) => warn!("Worker {} stopped: {}", name, e),
+            Err(e) => {
+                error!("Worker {} panicked: {:?}", name, e);
+                let _ = shutdown_tx.send(());
+            }
         }
-        info!("🛑 Ctrl-C detected. Shutting down...");
-        let _ = shutdown_tx.send(());
-    });
-
-    // --- Await Shutdown Signal ---
-    let _ = shutdown_rx.recv().await;
-    info!("🚪 All workers will now terminate.");
+    })
 }
 
 async fn simulate_paths(
@@ -0,0 +1,226 @@
+use std::fs::{File, OpenOptions, create_dir_all};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use alloy::primitives::{Address, B256, I256, U256};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// One submitted path, filled in with realized numbers once its receipt is seen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub tx_hash: B256,
+    pub block_number: u64,
+    /// Token profit is denominated in
+    pub token: Address,
+    pub expected_profit: U256,
+    /// Balance delta of `token` minus gas cost, None until the receipt is reconciled
+    pub realized_profit: Option<I256>,
+    pub gas_cost: Option<U256>,
+    pub success: Option<bool>,
+}
+
+/// One line of the on-disk ledger, entries are rebuilt by replaying these in order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum LedgerRecord {
+    Submission {
+        tx_hash: B256,
+        block_number: u64,
+        token: Address,
+        expected_profit: U256,
+    },
+    Receipt {
+        tx_hash: B256,
+        success: bool,
+        balance_delta: I256,
+        gas_cost: U256,
+    },
+}
+
+/// Aggregate performance over every reconciled entry
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LedgerSummary {
+    pub submitted: usize,
+    pub confirmed: usize,
+    pub wins: usize,
+    pub win_rate: f64,
+    pub total_realized_profit: I256,
+    pub total_gas: U256,
+    /// Mean shortfall of realized vs expected profit, in basis points of the expectation
+    pub avg_slippage_bps: f64,
+}
+
+/// Expected vs realized profit of submitted transactions.
+/// Every change is appended to a JSON Lines log at `path`, so a write never rewrites past entries
+pub struct Ledger {
+    path: PathBuf,
+    entries: Mutex<Vec<LedgerEntry>>,
+}
+
+impl Ledger {
+    /// Load the ledger at `path`, starting empty if it is missing. Unreadable lines are skipped
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = Vec::new();
+        if let Ok(file) = File::open(&path) {
+            for (number, line) in BufReader::new(file).lines().enumerate() {
+                let record = line
+                    .map_err(anyhow::Error::from)
+                    .and_then(|line| serde_json::from_str::<LedgerRecord>(&line).map_err(Into::into));
+                match record {
+                    Ok(record) => apply(&mut entries, record),
+                    Err(e) => warn!("Skipping ledger line {} of {}: {:?}", number + 1, path.display(), e),
+                }
+            }
+        }
+
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// Record a submitted transaction and the profit the simulation promised
+    pub fn record_submission(&self, tx_hash: B256, block_number: u64, token: Address, expected_profit: U256) {
+        self.record(LedgerRecord::Submission {
+            tx_hash,
+            block_number,
+            token,
+            expected_profit,
+        });
+    }
+
+    /// Reconcile a submission against its receipt
+    pub fn record_receipt(&self, tx_hash: B256, success: bool, balance_delta: I256, gas_cost: U256) {
+        self.record(LedgerRecord::Receipt {
+            tx_hash,
+            success,
+            balance_delta,
+            gas_cost,
+        });
+    }
+
+    pub fn summary(&self) -> LedgerSummary {
+        let entries = self.entries.lock().unwrap();
+        let mut summary = LedgerSummary {
+            submitted: entries.len(),
+            ..Default::default()
+        };
+
+        let mut slippage_sum = 0f64;
+        let mut slippage_count = 0usize;
+        for entry in entries.iter() {
+            let (Some(realized), Some(gas_cost)) = (entry.realized_profit, entry.gas_cost) else {
+                continue;
+            };
+
+            summary.confirmed += 1;
+            summary.total_realized_profit += realized;
+            summary.total_gas += gas_cost;
+            if realized > I256::ZERO {
+                summary.wins += 1;
+            }
+
+            if !entry.expected_profit.is_zero() {
+                let expected = f64::from(entry.expected_profit);
+                let realized = realized.to_string().parse::<f64>().unwrap_or_default();
+                slippage_sum += (expected - realized) / expected * 10_000.0;
+                slippage_count += 1;
+            }
+        }
+
+        if summary.confirmed > 0 {
+            summary.win_rate = summary.wins as f64 / summary.confirmed as f64;
+        }
+        if slippage_count > 0 {
+            summary.avg_slippage_bps = slippage_sum / slippage_count as f64;
+        }
+        summary
+    }
+
+    fn record(&self, record: LedgerRecord) {
+        apply(&mut self.entries.lock().unwrap(), record.clone());
+        if let Err(e) = self.append(&record) {
+            warn!("Failed to append to ledger {}: {:?}", self.path.display(), e);
+        }
+    }
+
+    fn append(&self, record: &LedgerRecord) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        // One write per record, O_APPEND keeps concurrent lines whole
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?
+            .write_all(&line)?;
+        Ok(())
+    }
+}
+
+fn apply(entries: &mut Vec<LedgerEntry>, record: LedgerRecord) {
+    match record {
+        LedgerRecord::Submission {
+            tx_hash,
+            block_number,
+            token,
+            expected_profit,
+        } => entries.push(LedgerEntry {
+            tx_hash,
+            block_number,
+            token,
+            expected_profit,
+            realized_profit: None,
+            gas_cost: None,
+            success: None,
+        }),
+        LedgerRecord::Receipt {
+            tx_hash,
+            success,
+            balance_delta,
+            gas_cost,
+        } => {
+            let Some(entry) = entries.iter_mut().find(|entry| entry.tx_hash == tx_hash) else {
+                warn!("Receipt for unknown ledger entry {}", tx_hash);
+                return;
+            };
+            entry.success = Some(success);
+            entry.gas_cost = Some(gas_cost);
+            entry.realized_profit = Some(balance_delta - I256::from_raw(gas_cost));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_replays_appended_records() {
+        let path = std::env::temp_dir().join(format!("ledger_test_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let tx_hash = B256::repeat_byte(0xab);
+        let token = Address::repeat_byte(0x42);
+
+        let ledger = Ledger::load(&path);
+        ledger.record_submission(tx_hash, 100, token, U256::from(1_000));
+        ledger.record_receipt(tx_hash, true, I256::try_from(900).unwrap(), U256::from(100));
+
+        let reloaded = Ledger::load(&path);
+        let entries = reloaded.entries.lock().unwrap().clone();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].token, token);
+        assert_eq!(entries[0].success, Some(true));
+        assert_eq!(entries[0].realized_profit, Some(I256::try_from(800).unwrap()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
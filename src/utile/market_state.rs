@@ -1,48 +1,257 @@
 
-use crate::calculation::state_db::blockstate_db::{BlockStateDB, InsertionType};
-use crate::utile::constant::AMOUNT;
+use crate::state_db::balancer_db::{BALANCER_VAULT, BalancerVault};
+use crate::calculation::state_db::blockstate_db::{BlockStateDB, BlockStateDBSlot, DEFAULT_PROVIDER_TIMEOUT, InsertionType};
+use crate::utile::constant::{DEFAULT_QUOTER_ADDRESS, DEFAULT_SIM_ACCOUNT, Deployment, amount_for, chain_from_id};
+use crate::utile::errors::WorkerError;
 use crate::utile::events::Event;
 use crate::utile::rgen::{ERC20Token, FlashQuoter};
+use crate::utile::swap::{pool_type_name, pool_version_code};
+use crate::utile::watchdog::Heartbeat;
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicBool, Ordering},
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use alloy::network::Network;
 use alloy::primitives::Keccak256;
-use alloy::primitives::{Address, U256, address};
+use alloy::primitives::{Address, B256, U256};
 use alloy::providers::{Provider, ProviderBuilder};
+use alloy::rpc::types::BlockId;
+use alloy::rpc::types::trace::geth::AccountState as GethAccountState;
 use alloy::sol_types::{SolCall, SolValue};
 use alloy::transports::http::Http, Client, RootProvider;
 use anyhow::{Context, Result};
-use pool_sync::{Pool, PoolInfo};
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use pool_sync::{Chain, Pool, PoolInfo, PoolType};
 use reth::primitives::Bytecode;
 use reth::revm::revm::context::Evm;
 use reth::revm::revm::context::TransactTo;
 use reth::revm::revm::state::AccountInfo;
+use revm::DatabaseRef;
+use revm::db::{CacheDB, WrapDatabaseRef};
+use revm::primitives::{BlockEnv, CfgEnv, SpecId};
 use reth::rpc::types::BlockNumberOrTag;
 use tokio::sync::{
-    RwLock,
+    RwLock, broadcast,
     mpsc::{Receiver, Sender},
 };
-use tracing::{debug, error, info};
-use tracing::debug_trace_block;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+use tracing::{debug_trace_block, debug_trace_block_prestate};
+
+// Retry policy for block traces, override the retry count with TRACE_RETRIES
+const DEFAULT_TRACE_RETRIES: u32 = 3;
+const TRACE_RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+// Gaps longer than this are resynced at the head instead of replayed block by block. Override with MAX_BACKFILL_BLOCKS
+const DEFAULT_MAX_BACKFILL_BLOCKS: u64 = 1_000;
+// Blocks replayed between catch-up progress reports
+const CATCHUP_PROGRESS_INTERVAL: u64 = 100;
+// Concurrent storage reads while resyncing tracked slots
+const RESYNC_CONCURRENCY: usize = 32;
+
+// Storage slots probed when looking for a token's `balances` mapping
+const BALANCE_SLOT_CANDIDATES: u64 = 10;
+
+/// EVM settings every simulation runs under, so gas and opcode availability match the target chain
+#[derive(Debug, Clone, Copy)]
+pub struct SimConfig {
+    /// Hardfork the simulated EVM follows
+    pub spec_id: SpecId,
+    pub chain_id: u64,
+    /// Quotes run with a zero gas price, which the base fee check would otherwise reject
+    pub disable_base_fee: bool,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            spec_id: SpecId::LATEST,
+            chain_id: 1,
+            disable_base_fee: true,
+        }
+    }
+}
+
+impl SimConfig {
+    /// Settings for the chain with `chain_id`, SIM_SPEC (a hardfork name such as `cancun`) overrides the spec
+    pub fn for_chain_id(chain_id: u64) -> Self {
+        let spec_id = match chain_id {
+            // Base follows L1 Cancun semantics
+            8453 => SpecId::CANCUN,
+            _ => SpecId::LATEST,
+        };
+        let spec_id = std::env::var("SIM_SPEC")
+            .ok()
+            .map(|name| SpecId::from(name.to_lowercase().as_str()))
+            .unwrap_or(spec_id);
+        Self {
+            spec_id,
+            chain_id,
+            ..Default::default()
+        }
+    }
+
+    pub fn for_chain(chain: Chain) -> Self {
+        match chain {
+            Chain::Base => Self::for_chain_id(8453),
+            Chain::Ethereum => Self::for_chain_id(1),
+        }
+    }
+
+    /// Copies the chain settings onto `cfg`, the spec is set on the EVM itself
+    pub fn apply_cfg(&self, cfg: &mut CfgEnv) {
+        cfg.chain_id = self.chain_id;
+        cfg.disable_base_fee = self.disable_base_fee;
+    }
+}
+
+/// Places the FlashQuoter bytecode at `quoter`, every quote simulation calls it there
+pub(crate) fn inject_quoter<N: Network, P: Provider<N>>(db: &mut BlockStateDB<N, P>, quoter: Address) {
+    let quoter_bytecode = FlashQuoter::DEPLOYED_BYTECODE.clone();
+    let quoter_info = AccountInfo {
+        nonce: 0,
+        balance: U256::ZERO,
+        code_hash: Keccak256::hash(&quoter_bytecode),
+        code: Some(Bytecode::new_raw(quoter_bytecode)),
+    };
+    db.insert_account_info(quoter, quoter_info, InsertionType::Custom);
+}
+
+/// Accounts and token layouts used to fund warm-up quotes
+#[derive(Debug, Clone)]
+pub struct WarmUpConfig {
+    /// Account funded with each pool's token0 and used as the quote caller
+    pub account: Address,
+    /// Address the FlashQuoter bytecode is injected at
+    pub quoter: Address,
+    /// `balances` mapping slot for tokens without an override (3 for the OpenZeppelin layout)
+    pub default_balance_slot: U256,
+    /// Per-token `balances` slots, filled in by discovery for tokens that don't use the default
+    pub balance_slots: HashMap<Address, U256>,
+    /// EVM settings of the warm-up and probe calls
+    pub sim_config: SimConfig,
+}
+
+impl Default for WarmUpConfig {
+    fn default() -> Self {
+        Self {
+            account: DEFAULT_SIM_ACCOUNT,
+            quoter: DEFAULT_QUOTER_ADDRESS,
+            default_balance_slot: U256::from(3),
+            balance_slots: HashMap::new(),
+            sim_config: SimConfig::default(),
+        }
+    }
+}
+
+impl WarmUpConfig {
+    /// Resolves the `balances` slot for `token`: override, then default if `balanceOf` agrees, then a scan
+    pub fn balance_slot<D: DatabaseRef>(&mut self, db: &D, token: Address) -> Option<U256> {
+        if let Some(slot) = self.balance_slots.get(&token) {
+            return Some(*slot);
+        }
+
+        let slot = std::iter::once(self.default_balance_slot)
+            .chain((0..=BALANCE_SLOT_CANDIDATES).map(U256::from))
+            .find(|slot| probe_balance_slot(db, token, self.account, *slot, &self.sim_config))?;
+        self.balance_slots.insert(token, slot);
+        Some(slot)
+    }
+}
+
+/// Writes a sentinel balance at `slot` on an overlay and checks that `balanceOf` reads it back
+pub fn probe_balance_slot<D: DatabaseRef>(
+    db: &D,
+    token: Address,
+    account: Address,
+    slot: U256,
+    sim_config: &SimConfig,
+) -> bool {
+    let sentinel = U256::from(0x5eed_cafe_u64);
+    let mut overlay = CacheDB::new(WrapDatabaseRef(db));
+    let storage_slot: U256 = Keccak256::hash(&(account, slot).abi_encode()).into();
+    if overlay.insert_account_storage(token, storage_slot, sentinel).is_err() {
+        return false;
+    }
+
+    let mut evm = Evm::new(&mut overlay, (), ());
+    evm.modify_spec_id(sim_config.spec_id);
+    evm.modify_cfg_env(|cfg| sim_config.apply_cfg(cfg));
+    evm.modify_tx_env(|tx| {
+        tx.caller = account;
+        tx.data = ERC20Token::balanceOfCall { account }.abi_encode().into();
+        tx.transact_to = TransactTo::Call(token);
+    });
+
+    match evm.transact().map(|res| res.result) {
+        Ok(result) => result
+            .output()
+            .and_then(|out| U256::abi_decode(out, false).ok())
+            .is_some_and(|balance| balance == sentinel),
+        Err(_) => false,
+    }
+}
 
 pub struct NamedAccountInfo {
     pub name: String,
 }
 
+/// Normalized view of a tracked pool's in-memory state
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolSnapshot {
+    pub address: Address,
+    pub pool_type: &'static str,
+    pub token0: Address,
+    pub token1: Address,
+    /// Fee as pool_sync reports it, e.g. the V3 tier in hundredths of a bip
+    pub fee: u32,
+    pub state: PoolStateSnapshot,
+    /// Block in which the pool's storage last changed, None if untouched since startup
+    pub last_updated_block: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PoolStateSnapshot {
+    Reserves { reserve0: U256, reserve1: U256 },
+    Concentrated { sqrt_price_x96: U256, liquidity: u128, tick: i32 },
+    Weighted { balances: Vec<U256>, weights: Vec<U256> },
+    /// Pool types whose state only lives in contract storage we don't decode
+    Opaque,
+}
+
 // State manager for live blockchain pool information
 pub struct MarketState<N, P>
 where
     N: Network,
     P: Provider<N>,
 {
+    /// Live pool state. The state updater is the only writer, holding the write lock just to apply a block's diff.
+    ///
+    /// Lock ordering, every reader follows it so a reader and the updater can't deadlock:
+    /// - take at most one guard per call and pass `&BlockStateDB` down, never lock again while holding it
+    ///   (a queued writer blocks new readers, so a nested read behind it never returns)
+    /// - drop the guard before awaiting or sending on a channel
+    /// - the updater never holds `block_env` and `db` together, so reading `block_env` under a `db` guard is safe
+    /// - simulations run on a `CacheDB<WrapDatabaseRef<&BlockStateDB>>` overlay under the read guard,
+    ///   their writes (funding, swaps, lazily fetched accounts) are discarded with the overlay
     pub db: RwLock<BlockStateDB<N, P>>,
+    /// Block context of the latest processed block, used by view-call simulations
+    pub block_env: RwLock<BlockEnv>,
+    /// Hardfork and chain settings of every simulation against `db`
+    pub sim_config: SimConfig,
+    /// Quoter and simulation account the warm-up injected and funded
+    pub deployment: Deployment,
+    /// Bumped for every processed block, watched by the watchdog
+    pub heartbeat: Arc<Heartbeat>,
+    /// Blocks whose trace failed after all retries, re-traced on the next catch-up
+    pub skipped_blocks: Mutex<BTreeSet<u64>>,
 }
 
 impl<N, P> MarketState<N, P>
@@ -50,52 +259,114 @@ where
     N: Network,
     P: Provider<N> + Clone + Send + Sync + 'static,
 {
+    /// Builds the state and spawns the updater, returning its handle so the caller can supervise it
     pub async fn init_state_and_start_stream(
         pools: Vec<Pool>,
         block_rx: Receiver<Event>, // ✅ Must match tokio::sync::mpsc
         address_tx: Sender<Event>, // <-- must be tokio::mpsc::Sender
         last_synced_block: u64,
         provider: P,
+        http_url: String,
         caught_up: Arc<AtomicBool>,
-    ) -> Result<Arc<Self>> {
+        deployment: Deployment,
+        shutdown: broadcast::Receiver<()>,
+    ) -> Result<(Arc<Self>, JoinHandle<Result<(), WorkerError>>)> {
         debug!("Populating the db with {} pools", pools.len());
 
-        let mut db = BlockStateDB::new(provider).context("Failed to initialize BlockStateDB")?;
-        Self::warm_up_database(&pools, &mut db);
-        Self::populate_db_with_pools(pools, &mut db);
+        // PROVIDER_TIMEOUT_MS bounds every provider read the simulation falls back to
+        let provider_timeout = std::env::var("PROVIDER_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_PROVIDER_TIMEOUT);
+        let sim_config = match provider.get_chain_id().await {
+            Ok(chain_id) => SimConfig::for_chain_id(chain_id),
+            Err(e) => {
+                warn!("Failed to fetch chain id, simulating with defaults: {:?}", e);
+                SimConfig::default()
+            }
+        };
+        info!("Simulating under {:?} on chain {}", sim_config.spec_id, sim_config.chain_id);
+
+        let mut db =
+            BlockStateDB::new(provider, provider_timeout).context("Failed to initialize BlockStateDB")?;
+        let mut warm_up = WarmUpConfig {
+            account: deployment.sim_account,
+            quoter: deployment.quoter,
+            sim_config,
+            ..Default::default()
+        };
+        Self::warm_up_database(&pools, &mut db, &mut warm_up);
+        Self::populate_db_with_pools(pools, &mut db, deployment.maverick_v2_quoter);
 
         let market_state = Arc::new(Self {
             db: RwLock::new(db),
+            block_env: RwLock::new(BlockEnv::default()),
+            sim_config,
+            deployment,
+            heartbeat: Arc::new(Heartbeat::new()),
+            skipped_blocks: Mutex::new(BTreeSet::new()),
         });
 
-        tokio::spawn(Self::state_updater(
+        let updater = tokio::spawn(Self::state_updater(
             market_state.clone(),
             block_rx,
             address_tx,
             last_synced_block,
+            http_url,
             caught_up,
+            shutdown,
         ));
 
-        Ok(market_state)
+        Ok((market_state, updater))
     }
 
-    fn warm_up_database(pools: &[Pool], db: &mut BlockStateDB<N, P>) {
-        let account = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
-        let quoter = address!("0000000000000000000000000000000000001000");
+    /// State holding just `pools`, read once from `provider` at the latest block.
+    /// No block stream or updater runs behind it, the state never advances. Needs a multi-threaded runtime
+    pub async fn standalone(pools: Vec<Pool>, provider: P) -> Result<Arc<Self>> {
+        let chain_id = match provider.get_chain_id().await {
+            Ok(chain_id) => Some(chain_id),
+            Err(e) => {
+                warn!("Failed to fetch chain id, simulating with defaults: {:?}", e);
+                None
+            }
+        };
+        let sim_config = chain_id.map(SimConfig::for_chain_id).unwrap_or_default();
+        // Unknown chains borrow Base's contract addresses, only Maverick V2 quotes read them
+        let deployment = Deployment::simulation_only(chain_id.and_then(chain_from_id).unwrap_or(Chain::Base));
+        let mut db = BlockStateDB::new(provider, DEFAULT_PROVIDER_TIMEOUT)
+            .context("Failed to initialize BlockStateDB, a multi-threaded runtime is required")?;
+        Self::populate_db_with_pools(pools, &mut db, deployment.maverick_v2_quoter);
+
+        Ok(Arc::new(Self {
+            db: RwLock::new(db),
+            block_env: RwLock::new(BlockEnv::default()),
+            sim_config,
+            deployment,
+            heartbeat: Arc::new(Heartbeat::new()),
+            skipped_blocks: Mutex::new(BTreeSet::new()),
+        }))
+    }
+
+    fn warm_up_database(pools: &[Pool], db: &mut BlockStateDB<N, P>, config: &mut WarmUpConfig) {
+        let account = config.account;
+        let quoter = config.quoter;
 
         let ten_units = U256::from(10_000_000_000_000_000_000u128);
-        let balance_slot = Keccak256::hash(&(account, U256::from(3)).abi_encode());
-
-        let quoter_bytecode = FlashQuoter::DEPLOYED_BYTECODE.clone();
-        let quoter_info = AccountInfo {
-            nonce: 0,
-            balance: U256::ZERO,
-            code_hash: Keccak256::hash(&quoter_bytecode),
-            code: Some(Bytecode::new_raw(quoter_bytecode)),
-        };
-        db.insert_account_info(quoter, quoter_info, InsertionType::Custom);
+
+        inject_quoter(db, quoter);
 
         for pool in pools {
+            let Some(slot) = config.balance_slot(&*db, pool.token0_address()) else {
+                warn!(
+                    "No balance slot found for {}, skipping warm-up of pool {}",
+                    pool.token0_address(),
+                    pool.address()
+                );
+                continue;
+            };
+            let balance_slot = Keccak256::hash(&(account, slot).abi_encode());
+
             db.insert_account_storage(
                 pool.token0_address(),
                 balance_slot.into(),
@@ -104,6 +375,27 @@ where
             )
             .unwrap();
 
+            // Maverick quotes run in both bin directions, so tokenB has to be funded as well
+            if matches!(pool.pool_type(), PoolType::MaverickV1 | PoolType::MaverickV2) {
+                match config.balance_slot(&*db, pool.token1_address()) {
+                    Some(slot) => {
+                        let balance_slot = Keccak256::hash(&(account, slot).abi_encode());
+                        db.insert_account_storage(
+                            pool.token1_address(),
+                            balance_slot.into(),
+                            ten_units,
+                            InsertionType::OnChain,
+                        )
+                        .unwrap();
+                    }
+                    None => warn!(
+                        "No balance slot found for {}, Maverick pool {} only warmed one way",
+                        pool.token1_address(),
+                        pool.address()
+                    ),
+                }
+            }
+
             let approve = ERC20Token::approveCall {
                 spender: quoter,
                 amount: U256::from(1e18),
@@ -111,6 +403,8 @@ where
             .abi_encode();
 
             let mut evm = Evm::new(&mut *db, (), ());
+            evm.modify_spec_id(config.sim_config.spec_id);
+            evm.modify_cfg_env(|cfg| config.sim_config.apply_cfg(cfg));
             evm.modify_tx_env(|tx| {
                 tx.caller = account;
                 tx.data = approve.into();
@@ -121,8 +415,8 @@ where
 
             let quote_path = FlashQuoter::SwapParams {
                 pools: vec![pool.address()],
-                poolVersions: vec![if pool.is_v3() { 1 } else { 0 }],
-                amountIn: *AMOUNT.read().unwrap(),
+                poolVersions: vec![pool_version_code(pool.pool_type())],
+                amountIn: amount_for(pool.token0_address()),
             };
 
             let quote_call = FlashQuoter::quoteArbitrageCall { params: quote_path }.abi_encode();
@@ -139,28 +433,64 @@ where
         mut block_rx: Receiver<Event>,
         address_tx: Sender<Event>,
         mut last_synced_block: u64,
+        http_url: String,
         caught_up: Arc<AtomicBool>,
-    ) {
-        let http: Arc<RootProvider<Http<Client>>> = Arc::new(...)
-        let http_url = std::env::var("FULL").unwrap(); // assumed validated externally
-        let http = Arc::new(ProviderBuilder::connect_http(http_url.parse().unwrap()).await);
-
-        let mut current_block = http.get_block_number().await.unwrap();
+        mut shutdown: broadcast::Receiver<()>,
+    ) -> Result<(), WorkerError> {
+        let http_url = http_url
+            .parse()
+            .map_err(|e| WorkerError::ConfigMissing(format!("Invalid http url {}: {:?}", http_url, e)))?;
+        let http = Arc::new(ProviderBuilder::connect_http(http_url).await);
+
+        let mut current_block = http
+            .get_block_number()
+            .await
+            .map_err(|e| WorkerError::Provider(format!("Failed to fetch block number: {:?}", e)))?;
+
+        let max_backfill = std::env::var("MAX_BACKFILL_BLOCKS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_BACKFILL_BLOCKS);
 
         while last_synced_block < current_block {
-            debug!(
-                "Catching up from {} to {}",
-                last_synced_block, current_block
-            );
-            for block_num in (last_synced_block + 1)..=current_block {
-                let _ = self.update_state(http.clone(), block_num).await;
+            let gap = current_block - last_synced_block;
+            if gap > max_backfill {
+                warn!(
+                    "{} blocks behind, over the {} block backfill cap, resyncing tracked slots at block {}",
+                    gap, max_backfill, current_block
+                );
+                self.resync_tracked_slots(http.clone(), current_block).await;
+                // The resync already covers them, replaying their diffs later would roll pools back
+                self.skipped_blocks.lock().unwrap().clear();
+            } else {
+                self.backfill(http.clone(), &address_tx, last_synced_block + 1, current_block)
+                    .await;
             }
             last_synced_block = current_block;
-            current_block = http.get_block_number().await.unwrap();
+            current_block = http
+                .get_block_number()
+                .await
+                .map_err(|e| WorkerError::Provider(format!("Failed to fetch block number: {:?}", e)))?;
         }
+        self.retrace_skipped_blocks(http.clone()).await;
 
         caught_up.store(true, Ordering::Relaxed);
-        while let Some(Event::NewBlock(block_header)) = block_rx.recv().await {
+        loop {
+            // A block already being applied is finished before shutdown is noticed
+            let event = tokio::select! {
+                event = block_rx.recv() => event,
+                _ = shutdown.recv() => {
+                    info!("State updater shutting down after block {}", last_synced_block);
+                    return Ok(());
+                }
+            };
+            let Some(event) = event else {
+                break;
+            };
+            // Pending-tx events share this channel when MEMPOOL is enabled
+            let Event::NewBlock(block_header) = event else {
+                continue;
+            };
             let start = Instant::now();
             let block_number = block_header.inner.number;
 
@@ -170,47 +500,508 @@ where
             }
 
             info!("New block received: {}", block_number);
-            let updated = self.update_state(http.clone(), block_number).await;
-
-            if let Err(e) = address_tx
-                .send(Event::PoolsTouched(updated.clone(), block_number))
-                .await
             {
-                error!("Error sending updates: {}", e);
-            } else {
-                info!("Block {} processed in {:?}", block_number, start.elapsed());
+                let mut block_env = self.block_env.write().unwrap();
+                block_env.number = U256::from(block_number);
+                block_env.timestamp = U256::from(block_header.inner.timestamp);
+                block_env.coinbase = block_header.inner.beneficiary;
+                block_env.gas_limit = U256::from(block_header.inner.gas_limit);
+                block_env.basefee = U256::from(block_header.inner.base_fee_per_gas.unwrap_or_default());
+            }
+            // Blocks that failed to trace while live are retried ahead of the new one, so its diff wins
+            let mut changed_slots = self.retrace_skipped_blocks(http.clone()).await;
+            for (pool, slots) in self.update_state(http.clone(), block_number).await {
+                changed_slots.entry(pool).or_default().extend(slots);
             }
+            let updated: HashSet<Address> = changed_slots.keys().copied().collect();
+
+            for (pool, slots) in changed_slots {
+                if slots.is_empty() {
+                    continue;
+                }
+                address_tx.send(Event::SlotsChanged(pool, slots)).await?;
+            }
+
+            address_tx
+                .send(Event::PoolsTouched(updated, block_number))
+                .await?;
+            info!("Block {} processed in {:?}", block_number, start.elapsed());
 
             last_synced_block = block_number;
+            self.heartbeat.beat();
         }
+
+        Err(WorkerError::ChannelClosed("block stream ended".to_string()))
     }
 
-    fn populate_db_with_pools(pools: Vec<Pool>, db: &mut BlockStateDB<N, P>) {
+    fn populate_db_with_pools(pools: Vec<Pool>, db: &mut BlockStateDB<N, P>, maverick_v2_quoter: Address) {
         for pool in pools {
             if pool.is_v2() {
                 db.insert_v2(pool);
             } else if pool.is_v3() {
                 db.insert_v3(pool).unwrap();
+            } else if matches!(pool.pool_type(), PoolType::CurveTwoCrypto | PoolType::CurveTriCrypto) {
+                db.insert_curve(pool).unwrap();
+            } else if matches!(pool.pool_type(), PoolType::MaverickV1 | PoolType::MaverickV2) {
+                let address = pool.address();
+                if let Err(e) = db.insert_maverick(pool, maverick_v2_quoter) {
+                    warn!("Failed to insert Maverick pool {}: {:?}", address, e);
+                }
+            } else if let Pool::BalancerV2(balancer_pool) = pool {
+                db.insert_balancer(balancer_pool);
+            }
+        }
+    }
+
+    /// Replays every block of `from..=to`, reporting progress every `CATCHUP_PROGRESS_INTERVAL` blocks
+    async fn backfill(
+        &self,
+        provider: Arc<dyn Provider<Http<Client>>>,
+        address_tx: &Sender<Event>,
+        from: u64,
+        to: u64,
+    ) {
+        info!("Catching up from {} to {}", from, to);
+        let started = Instant::now();
+        for block_num in from..=to {
+            let _ = self.update_state(provider.clone(), block_num).await;
+
+            let done = block_num - from + 1;
+            if done % CATCHUP_PROGRESS_INTERVAL == 0 || block_num == to {
+                let eta = started.elapsed().mul_f64((to - block_num) as f64 / done as f64);
+                info!("Catch-up at block {} ({}/{}), ETA {:?}", block_num, done, to - from + 1, eta);
+                // Nothing drains the channel until catch-up ends, a full channel just drops the report
+                let _ = address_tx.try_send(Event::CatchupProgress {
+                    from,
+                    to,
+                    current: block_num,
+                });
             }
         }
     }
 
+    /// Re-reads every tracked pool slot at `block`, replacing a replay of the blocks in between.
+    /// Returns each pool with the slots that changed
+    async fn resync_tracked_slots(
+        &self,
+        provider: Arc<dyn Provider<Http<Client>>>,
+        block: u64,
+    ) -> HashMap<Address, Vec<U256>> {
+        let keys: Vec<(Address, U256)> = {
+            let db = self.db_read();
+            db.pools
+                .iter()
+                .filter_map(|pool| db.accounts.get(pool).map(|account| (*pool, account)))
+                .flat_map(|(pool, account)| account.storage.keys().map(move |slot| (pool, *slot)))
+                .collect()
+        };
+        info!("Resyncing {} tracked slots at block {}", keys.len(), block);
+
+        let values: Vec<(Address, U256, Option<U256>)> = stream::iter(keys)
+            .map(|(pool, slot)| {
+                let provider = provider.clone();
+                async move {
+                    let value = provider
+                        .get_storage_at(pool, slot)
+                        .block_id(block.into())
+                        .await
+                        .map_err(|e| warn!("Failed to resync slot {} of {}: {:?}", slot, pool, e))
+                        .ok();
+                    (pool, slot, value)
+                }
+            })
+            .buffer_unordered(RESYNC_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut updated_pools: HashMap<Address, Vec<U256>> = HashMap::new();
+        let mut db = self.db.write().unwrap();
+        for (pool, slot, value) in values {
+            let Some(value) = value else {
+                continue;
+            };
+            let Some(account) = db.accounts.get_mut(&pool) else {
+                continue;
+            };
+            let previous = account.storage.insert(slot, BlockStateDBSlot {
+                value,
+                insertion_type: InsertionType::OnChain,
+            });
+            if previous.map_or(true, |prev| prev.value != value) {
+                updated_pools.entry(pool).or_default().push(slot);
+            }
+        }
+        for pool in updated_pools.keys() {
+            db.pool_last_updated.insert(*pool, block);
+        }
+        info!("Resync at block {} changed {} pools", block, updated_pools.len());
+        updated_pools
+    }
+
+    /// Re-trace blocks that were skipped earlier, anything that still fails stays recorded.
+    /// Returns each pool the retraced blocks touched with the slots that changed
+    async fn retrace_skipped_blocks(
+        &self,
+        provider: Arc<dyn Provider<Http<Client>>>,
+    ) -> HashMap<Address, Vec<U256>> {
+        let skipped: Vec<u64> = std::mem::take(&mut *self.skipped_blocks.lock().unwrap())
+            .into_iter()
+            .collect();
+
+        let mut updated_pools: HashMap<Address, Vec<U256>> = HashMap::new();
+        for block_num in skipped {
+            info!("Re-tracing previously skipped block {}", block_num);
+            for (pool, slots) in self.update_state(provider.clone(), block_num).await {
+                updated_pools.entry(pool).or_default().extend(slots);
+            }
+        }
+        updated_pools
+    }
+
+    /// Applies the block's state diff, returning each touched pool with the slots that changed
     async fn update_state(
         &self,
         provider: Arc<dyn Provider<Http<Client>>>,
         block_num: u64,
-    ) -> HashSet<Address> {
-        let mut updated_pools = HashSet::new();
-        let updates = debug_trace_block(provider, BlockNumberOrTag::Number(block_num), true).await;
+    ) -> HashMap<Address, Vec<U256>> {
+        let mut updated_pools: HashMap<Address, Vec<U256>> = HashMap::new();
+        let retries = std::env::var("TRACE_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_TRACE_RETRIES);
+
+        let mut attempt = 0;
+        let updates = loop {
+            match debug_trace_block(provider.clone(), BlockNumberOrTag::Number(block_num), true).await {
+                Ok(updates) => break updates,
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    warn!("Trace of block {} failed (attempt {}/{}): {:?}", block_num, attempt, retries, e);
+                    tokio::time::sleep(TRACE_RETRY_BACKOFF * 2u32.pow(attempt - 1)).await;
+                }
+                Err(e) => {
+                    error!("Skipping block {} after {} failed traces: {:?}", block_num, attempt + 1, e);
+                    self.skipped_blocks.lock().unwrap().insert(block_num);
+                    return updated_pools;
+                }
+            }
+        };
+
+        // Balancer balances live in the Vault's storage, not the pools', so they're re-read whenever it was touched
+        let touched_vault = updates.iter().any(|map| map.contains_key(&BALANCER_VAULT));
+        let balancer_balances = if touched_vault {
+            self.fetch_balancer_balances(provider, block_num).await
+        } else {
+            Vec::new()
+        };
+
+        // The Vault slots the block wrote stand in for a refreshed pool's changed slots, so the searcher re-quotes it
+        let vault_slots: Vec<U256> = updates
+            .iter()
+            .filter_map(|map| map.get(&BALANCER_VAULT))
+            .flat_map(|state| state.storage.keys().map(|slot| (*slot).into()))
+            .collect();
 
         let mut db = self.db.write().unwrap();
+        let mut updated_pools = Self::apply_updates(&mut db, &updates, block_num);
+        for (pool, balances) in balancer_balances {
+            if db.update_balancer_balances(&pool, &balances) {
+                db.pool_last_updated.insert(pool, block_num);
+                updated_pools.entry(pool).or_default().extend(vault_slots.iter().copied());
+            }
+        }
+        updated_pools
+    }
+
+    /// Raw Vault balances of every tracked Balancer pool at `block_num`, pools whose read fails are left out
+    async fn fetch_balancer_balances(
+        &self,
+        provider: Arc<dyn Provider<Http<Client>>>,
+        block_num: u64,
+    ) -> Vec<(Address, Vec<U256>)> {
+        let pool_ids = self.db_read().balancer_pool_ids();
+        stream::iter(pool_ids)
+            .map(|(pool, pool_id)| {
+                let provider = provider.clone();
+                async move {
+                    let vault = BalancerVault::new(BALANCER_VAULT, provider);
+                    vault
+                        .getPoolTokens(pool_id)
+                        .block(block_num.into())
+                        .call()
+                        .await
+                        .map(|tokens| (pool, tokens.balances))
+                        .map_err(|e| warn!("Failed to refresh Balancer pool {}: {:?}", pool, e))
+                        .ok()
+                }
+            })
+            .buffer_unordered(RESYNC_CONCURRENCY)
+            .filter_map(|balances| async move { balances })
+            .collect()
+            .await
+    }
+
+    /// Rebuilds the state `block_num` ran on and applies its state diff, leaving the live state untouched.
+    /// The base is block N-1: accounts the block touched take their prestate trace values, every other
+    /// account and slot is read from the provider at N-1. Returns the pools the block touched and the result
+    pub async fn replay_block(
+        &self,
+        block_num: u64,
+        provider: Arc<dyn Provider<Http<Client>>>,
+    ) -> Result<(HashSet<Address>, BlockStateDB<N, P>)> {
+        let parent = block_num
+            .checked_sub(1)
+            .context("Block 0 has no parent state to replay on")?;
+        let pre_states = debug_trace_block_prestate(provider.clone(), BlockNumberOrTag::Number(block_num))
+            .await
+            .with_context(|| format!("Failed to prestate-trace block {}", block_num))?;
+        let updates = debug_trace_block(provider, BlockNumberOrTag::Number(block_num), true)
+            .await
+            .with_context(|| format!("Failed to trace block {}", block_num))?;
+
+        let mut db = self
+            .db_read()
+            .snapshot()
+            .context("Failed to snapshot BlockStateDB")?
+            .with_read_block(BlockId::number(parent));
+        // Live accounts hold head values, only the injected quoter is kept, the rest is re-read at N-1
+        db.accounts.retain(|address, _| *address == self.deployment.quoter);
+
+        // The first transaction touching a slot saw its N-1 value, later ones see earlier writes of the block
+        let mut seen: HashSet<(Address, B256)> = HashSet::new();
+        for (address, mut state) in pre_states.into_iter().flat_map(|map| map.into_iter()) {
+            state.storage.retain(|slot, _| seen.insert((address, *slot)));
+            if !db.accounts.contains_key(&address) {
+                match db.basic_ref(address) {
+                    Ok(Some(info)) => db.insert_account_info(address, info, InsertionType::OnChain),
+                    _ => warn!("Failed to read {} at block {}, replaying without its prestate", address, parent),
+                }
+            }
+            db.update_all_slots(address, state)?;
+        }
+
+        let touched = Self::apply_updates(&mut db, &updates, block_num)
+            .into_keys()
+            .collect();
+        // Tokens and routers the block wrote to are replayed too, not just the tracked pools
+        for (address, state) in updates.iter().flat_map(|map| map.iter()) {
+            if !db.tracking_pool(address) {
+                db.update_all_slots(*address, state.clone())?;
+            }
+        }
+
+        info!("Replayed block {} on the state of block {}", block_num, parent);
+        Ok((touched, db))
+    }
+
+    /// Writes traced account states into `db`, returning each tracked pool with the slots that changed
+    fn apply_updates(
+        db: &mut BlockStateDB<N, P>,
+        updates: &[BTreeMap<Address, GethAccountState>],
+        block_num: u64,
+    ) -> HashMap<Address, Vec<U256>> {
+        let mut updated_pools: HashMap<Address, Vec<U256>> = HashMap::new();
         for (addr, state) in updates.iter().flat_map(|map| map.iter()) {
             if db.tracking_pool(addr) {
-                db.update_all_slots(*addr, state.clone()).unwrap();
-                updated_pools.insert(*addr);
+                let changed = db.update_all_slots(*addr, state.clone()).unwrap();
+                if !changed.is_empty() {
+                    db.pool_last_updated.insert(*addr, block_num);
+                }
+                updated_pools.entry(*addr).or_default().extend(changed);
             }
         }
-
         updated_pools
     }
 }
+
+impl<N, P> MarketState<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    /// Read lock on the state DB, recovering the guard if a writer panicked.
+    /// The DB is only ever appended to or overwritten slot by slot, so a poisoned lock still holds usable state
+    pub fn db_read(&self) -> std::sync::RwLockReadGuard<'_, BlockStateDB<N, P>> {
+        self.db.read().unwrap_or_else(|poisoned| {
+            warn!("DB lock poisoned, recovering read guard");
+            poisoned.into_inner()
+        })
+    }
+
+    /// Read lock on the block env, recovering the guard if the updater panicked mid-write.
+    /// Fields are assigned one at a time, so at worst a recovered env mixes two consecutive blocks
+    pub fn block_env_read(&self) -> std::sync::RwLockReadGuard<'_, BlockEnv> {
+        self.block_env.read().unwrap_or_else(|poisoned| {
+            warn!("BlockEnv lock poisoned, recovering read guard");
+            poisoned.into_inner()
+        })
+    }
+
+    /// Snapshot of one tracked pool, None if the pool isn't tracked or its state can't be read
+    pub fn pool_snapshot(&self, pool: Address) -> Option<PoolSnapshot> {
+        let db = self.db_read();
+        Self::snapshot_from_db(&db, pool)
+    }
+
+    /// Snapshots of every tracked pool, taken under a single read lock
+    pub fn all_pool_snapshots(&self) -> Vec<PoolSnapshot> {
+        let db = self.db_read();
+        db.pools
+            .iter()
+            .filter_map(|pool| Self::snapshot_from_db(&db, *pool))
+            .collect()
+    }
+
+    fn snapshot_from_db(db: &BlockStateDB<N, P>, pool: Address) -> Option<PoolSnapshot> {
+        let info = db.pool_info.get(&pool)?;
+        let pool_type = info.pool_type();
+
+        let state = if info.is_v3() {
+            let slot0 = db.slot0(pool).ok()?;
+            PoolStateSnapshot::Concentrated {
+                sqrt_price_x96: U256::from(slot0.sqrt_price_x96),
+                liquidity: db.liquidity(pool).ok()?,
+                tick: slot0.tick,
+            }
+        } else if info.is_v2() {
+            let (reserve0, reserve1) = db.get_reserves(&pool);
+            PoolStateSnapshot::Reserves { reserve0, reserve1 }
+        } else if pool_type == PoolType::BalancerV2 {
+            PoolStateSnapshot::Weighted {
+                balances: db.get_balancer_balances(&pool),
+                weights: db.get_balancer_weights(&pool),
+            }
+        } else {
+            PoolStateSnapshot::Opaque
+        };
+
+        Some(PoolSnapshot {
+            address: pool,
+            pool_type: pool_type_name(pool_type).unwrap_or("Unknown"),
+            token0: info.token0_address(),
+            token1: info.token1_address(),
+            fee: info.fee(),
+            state,
+            last_updated_block: db.pool_last_updated.get(&pool).copied(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::test_utils::{insert_v2_offline, insert_v3_offline, market_state, offline_db, v2_pool, v3_pool};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn snapshots_match_inserted_v2_and_v3_pools() {
+        let (token0, token1) = (Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let (v2, v3) = (Address::repeat_byte(0x21), Address::repeat_byte(0x31));
+        let sqrt_price = U256::from(1) << 96;
+        let mut db = offline_db();
+        insert_v2_offline(&mut db, v2_pool(v2, token0, token1, 5_000, 7_000));
+        insert_v3_offline(&mut db, v3_pool(v3, token0, token1, sqrt_price, 0, 1_000_000));
+        let state = market_state(db);
+
+        let snapshot = state.pool_snapshot(v2).unwrap();
+        assert_eq!((snapshot.token0, snapshot.token1), (token0, token1));
+        assert_eq!(snapshot.last_updated_block, None);
+        assert!(matches!(
+            snapshot.state,
+            PoolStateSnapshot::Reserves { reserve0, reserve1 }
+                if reserve0 == U256::from(5_000) && reserve1 == U256::from(7_000)
+        ));
+
+        let snapshot = state.pool_snapshot(v3).unwrap();
+        assert_eq!(snapshot.fee, 3000);
+        assert!(matches!(
+            snapshot.state,
+            PoolStateSnapshot::Concentrated { sqrt_price_x96, liquidity: 1_000_000, tick: 0 } if sqrt_price_x96 == sqrt_price
+        ));
+
+        assert_eq!(state.all_pool_snapshots().len(), 2);
+        assert!(state.pool_snapshot(Address::repeat_byte(0x41)).is_none());
+    }
+
+    // Rayon estimation and the state updater share the db lock, following the documented ordering
+    // neither side can wedge the other. A deadlock shows up as the timeout firing
+    #[tokio::test(flavor = "multi_thread")]
+    async fn estimation_and_state_updates_run_concurrently() {
+        use crate::calculation::Calculator;
+        use rayon::prelude::*;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let (pool, token0, token1) = (Address::repeat_byte(0x21), Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+        let mut db = offline_db();
+        insert_v2_offline(&mut db, v2_pool(pool, token0, token1, 1_000_000, 1_000_000));
+        let state = market_state(db);
+        let done = Arc::new(AtomicBool::new(false));
+
+        let updater = {
+            let (state, done) = (Arc::clone(&state), Arc::clone(&done));
+            std::thread::spawn(move || {
+                let mut block = 0u64;
+                while !done.load(Ordering::Relaxed) {
+                    block += 1;
+                    // Like the updater: block env and db are written one after the other, never together
+                    state.block_env.write().unwrap().number = U256::from(block);
+                    let reserve = U256::from(1_000_000 + block % 1_000);
+                    let mut db = state.db.write().unwrap();
+                    db.accounts.get_mut(&pool).unwrap().storage.insert(U256::from(8), BlockStateDBSlot {
+                        value: (reserve << 112) | reserve,
+                        insertion_type: InsertionType::Custom,
+                    });
+                    db.pool_last_updated.insert(pool, block);
+                }
+            })
+        };
+
+        let estimation = {
+            let state = Arc::clone(&state);
+            tokio::task::spawn_blocking(move || {
+                let calculator = Calculator::new(Arc::clone(&state));
+                (1..20_000u64).into_par_iter().for_each(|amount| {
+                    let out = calculator.compute_amount_out(U256::from(amount), pool, token0, PoolType::UniswapV2, 0);
+                    assert!(out < U256::from(amount));
+                    // Reading block_env under a db guard is allowed, the updater never holds both
+                    let db = state.db_read();
+                    let _ = (db.get_reserves(&pool), state.block_env.read().unwrap().number);
+                });
+            })
+        };
+
+        let finished = tokio::time::timeout(std::time::Duration::from_secs(60), estimation).await;
+        done.store(true, Ordering::Relaxed);
+        updater.join().unwrap();
+        finished.expect("estimation deadlocked against state updates").unwrap();
+    }
+
+    // PUSH0 only exists from Shanghai on, the same bytecode returns under Cancun and halts under London
+    #[tokio::test(flavor = "multi_thread")]
+    async fn simulations_follow_the_configured_spec() {
+        use crate::calculation::Calculator;
+        use crate::utile::test_utils::market_state_with;
+
+        // PUSH1 7 PUSH0 MSTORE PUSH1 32 PUSH0 RETURN
+        let code = alloy::primitives::Bytes::from_static(&[0x60, 0x07, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3]);
+        let contract = Address::repeat_byte(0x5f);
+        let quote = |spec_id: SpecId| {
+            let mut db = offline_db();
+            db.insert_account_info(
+                contract,
+                AccountInfo {
+                    nonce: 0,
+                    balance: U256::ZERO,
+                    code_hash: Keccak256::hash(&code),
+                    code: Some(Bytecode::new_raw(code.clone())),
+                },
+                InsertionType::Custom,
+            );
+            let state = market_state_with(db, SimConfig { spec_id, ..SimConfig::default() });
+            Calculator::new(state).curve_out(U256::ZERO, U256::from(1), U256::from(1), contract)
+        };
+
+        assert_eq!(quote(SpecId::CANCUN), U256::from(7));
+        assert_eq!(quote(SpecId::LONDON), U256::ZERO);
+        assert_eq!(SimConfig::for_chain(Chain::Base).spec_id, SpecId::CANCUN);
+    }
+}
@@ -3,9 +3,13 @@
 // Import tracing macros
 // Import lazy_static macro
 
+pub mod api;
+pub mod blacklist;
 pub mod bytecode;
 pub mod cache;
 pub mod constant;
+pub mod decimals;
+pub mod errors;
 pub mod estimator;
 pub mod events;
 pub mod filter;
@@ -13,21 +17,30 @@ pub mod gas_station;
 pub mod graph;
 pub mod history_db;
 pub mod ignition;
+pub mod ledger;
 pub mod market_state;
 pub mod node_db;
+pub mod opportunities;
 pub mod quoter;
+pub mod revert;
 pub mod rgen;
+pub mod sandwicher;
 pub mod searcher;
 pub mod simulator;
 pub mod stream;
 pub mod swap;
+#[cfg(test)]
+pub mod test_utils;
+pub mod tick_provider;
 pub mod tx_sender;
+pub mod watchdog;
 
 pub use cache::Cache;
-pub use constant::AMOUNT;
+pub use constant::{AMOUNTS, AmountConfig, amount_for};
 pub use market_state::MarketState;
 pub use rgen::FlashQuoter;
 pub use rgen::FlashSwap;
 pub use swap::SwapPath;
+pub use tick_provider::{DbTickDataProvider, TickCache};
 
 // Re-export Calculator for easier import
@@ -0,0 +1,88 @@
+use crate::utile::swap::SwapPath;
+use alloy::primitives::U256;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Profitable paths found by the searcher, shared with the API
+pub type RecentOpportunities = Arc<Mutex<RingBuffer<OpportunityRecord>>>;
+
+/// A profitable path as the searcher saw it
+#[derive(Debug, Clone, Serialize)]
+pub struct OpportunityRecord {
+    pub block_number: u64,
+    pub path: SwapPath,
+    /// Estimated output left after repaying the flash loan, before gas
+    pub estimated_profit: U256,
+    /// Same for the on-state re-quote, only set for the path that was forwarded
+    pub real_profit: Option<U256>,
+    /// Unix seconds when the path was found
+    pub timestamp: u64,
+}
+
+impl OpportunityRecord {
+    pub fn new(block_number: u64, path: SwapPath, estimated_profit: U256) -> Self {
+        Self {
+            block_number,
+            path,
+            estimated_profit,
+            real_profit: None,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Fixed capacity FIFO, pushing onto a full buffer evicts the oldest item
+#[derive(Debug)]
+pub struct RingBuffer<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+        }
+        self.items.push_back(item);
+    }
+
+    /// Up to `n` items, newest first
+    pub fn recent(&self, n: usize) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.items.iter().rev().take(n).cloned().collect()
+    }
+
+    /// Items newest first, for in-place updates of recent entries
+    pub fn iter_mut_recent(&mut self) -> impl Iterator<Item = &mut T> {
+        self.items.iter_mut().rev()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
@@ -1,36 +1,98 @@
-use crate::utile::constant::AMOUNT;
 use crate::utile::rgen::{FlashQuoter, FlashSwap};
 use crate::utile::MarketState;
+use crate::utile::swap::SwapPath;
+use crate::utile::constant::FlashLoanConfig;
+use crate::utile::revert::describe_revert;
+use crate::utile::decimals::decimals_of;
 use alloy::rlp::Decodable;
 use anyhow::Result;
+use rayon::prelude::*;
 use std::sync::Arc;
 use tracing::{info, warn};
-use alloy::network::Ethereum;
-use alloy::primitives::{U256, address};
-use alloy::providers::RootProvider;
+use alloy::network::Network;
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
 use alloy::sol_types::SolCall;
 use reth::revm::revm::ExecutionResult;
- use alloy_transport_http::Http;
 use reth::revm::revm::context::Evm;
 use reth::revm::revm::context::TransactTo;
+use revm::Database;
+use revm::db::{CacheDB, WrapDatabaseRef};
 
 /// Quoter – runs an EVM simulation to quote arbitrage profitability.
 pub struct Quoter;
 
 impl Quoter {
     /// Runs a simulated EVM call on the provided quote path.
-    pub fn quote_path(
+    pub fn quote_path<N: Network, P: Provider<N>>(
         quote_params: FlashQuoter::SwapParams,
-        market_state: Arc<MarketState<Ethereum, RootProvider<Http>>>,
+        market_state: Arc<MarketState<N, P>>,
     ) -> Result<Vec<U256>, anyhow::Error> {
-        let mut guard = market_state.db.write().unwrap();
+        Self::quote_path_with_gas(quote_params, market_state).map(|(amounts, _)| amounts)
+    }
 
-        let mut evm = Evm::new(&mut *guard, (), ());
+    /// Like `quote_path`, also returning the gas the quote used.
+    /// Gas grows with the V3 ticks crossed, so it tracks how execution cost scales with size.
+    pub fn quote_path_with_gas<N: Network, P: Provider<N>>(
+        quote_params: FlashQuoter::SwapParams,
+        market_state: Arc<MarketState<N, P>>,
+    ) -> Result<(Vec<U256>, u64), anyhow::Error> {
+        let guard = market_state.db_read();
+        let mut overlay = CacheDB::new(WrapDatabaseRef(&*guard));
+        let mut evm = Self::quote_evm(&mut overlay, &market_state);
+        Self::quote_on_evm(&mut evm, quote_params)
+    }
 
-        evm.tx_mut().caller = address!("d8da6bf26964af9d7eed9e03e53415d37aa96045");
-        evm.tx_mut().transact_to =
-            TransactTo::Call(address!("0000000000000000000000000000000000001000"));
+    /// Samples `steps` evenly spaced inputs from `min_in` to `max_in` along `path`, returning `(input, output)` pairs.
+    /// Every sample runs on the same EVM under one DB read lock. Empty if no sample's output clears its input
+    pub fn profit_curve<N: Network, P: Provider<N>>(
+        path: &SwapPath,
+        min_in: U256,
+        max_in: U256,
+        steps: usize,
+        market_state: Arc<MarketState<N, P>>,
+    ) -> Vec<(U256, U256)> {
+        if steps == 0 || max_in < min_in {
+            return Vec::new();
+        }
+        let guard = market_state.db_read();
+        let mut overlay = CacheDB::new(WrapDatabaseRef(&*guard));
+        let mut evm = Self::quote_evm(&mut overlay, &market_state);
 
+        let mut params: FlashQuoter::SwapParams = match path.clone().try_into() {
+            Ok(params) => params,
+            Err(e) => {
+                warn!("Skipping profit curve: {e}");
+                return Vec::new();
+            }
+        };
+        sample_profit_curve(min_in, max_in, steps, |input| {
+            params.amountIn = input;
+            Self::quote_on_evm(&mut evm, params.clone())
+                .ok()
+                .map(|(amounts, _)| amounts.last().copied().unwrap_or_default())
+        })
+    }
+
+    /// EVM over `db` under the state's `SimConfig`, calling the FlashQuoter the warm-up injected from its funded account
+    fn quote_evm<DB: Database, N: Network, P: Provider<N>>(
+        db: DB,
+        market_state: &MarketState<N, P>,
+    ) -> Evm<'static, (), DB> {
+        let sim_config = &market_state.sim_config;
+        let mut evm = Evm::new(db, (), ());
+        evm.modify_spec_id(sim_config.spec_id);
+        evm.modify_cfg_env(|cfg| sim_config.apply_cfg(cfg));
+        evm.tx_mut().caller = market_state.deployment.sim_account;
+        evm.tx_mut().transact_to = TransactTo::Call(market_state.deployment.quoter);
+        evm
+    }
+
+    /// Quotes `quote_params` on an EVM built by `quote_evm`, without committing state
+    fn quote_on_evm<DB: Database>(
+        evm: &mut Evm<'_, (), DB>,
+        quote_params: FlashQuoter::SwapParams,
+    ) -> Result<(Vec<U256>, u64), anyhow::Error> {
         let calldata = FlashQuoter::quoteArbitrageCall {
             params: quote_params,
         }
@@ -40,9 +102,9 @@ impl Quoter {
 
         // Run the transaction
         match evm.transact().map(|tx| tx.result) {
-            Ok(ExecutionResult::Success { output, .. }) => {
+            Ok(ExecutionResult::Success { output, gas_used, .. }) => {
                 match Vec::<U256>::decode(output.data()) {
-                    Ok(decoded) => Ok(decoded),
+                    Ok(decoded) => Ok((decoded, gas_used)),
                     Err(e) => {
                         warn!("❌ ABI decode failed: {e:?}");
                         Err(anyhow::anyhow!("Failed to decode EVM output"))
@@ -50,8 +112,9 @@ impl Quoter {
                 }
             }
             Ok(ExecutionResult::Revert { output, .. }) => {
-                warn!("🚫 Simulation reverted with output: {:?}", output);
-                Err(anyhow::anyhow!("Simulation reverted"))
+                let reason = describe_revert(&output);
+                warn!("🚫 Simulation reverted: {}", reason);
+                Err(anyhow::anyhow!("Simulation reverted: {}", reason))
             }
             Ok(_) => {
                 warn!("🤔 Unexpected simulation result");
@@ -64,42 +127,444 @@ impl Quoter {
         }
     }
 
-    /// Optimizes the input amount via binary search to maximize profitability.
-    /// Returns a `(best_input, best_output)` pair.
-    pub fn optimize_input(
+    /// Searches `bounds` for the input maximizing net profit:
+    /// `output - input - flash_loan.fee(input) - gas_in_token((gas_used + loan_gas) * gas_price)`,
+    /// `loan_gas` being the flash loan's own gas and `gas_in_token` converting the wei gas cost into the path's start token.
+    /// Starts from the clamped `amountIn` and probes both directions, halving the step whenever neither side improves,
+    /// so a peak below the starting amount is found as well as one above it.
+    /// Returns a `(best_input, net_profit)` pair, `best_input` always within `bounds`.
+    pub fn optimize_input<N: Network, P: Provider<N>>(
         mut quote_path: FlashQuoter::SwapParams,
-        initial_out: U256,
-        market_state: Arc<MarketState<Ethereum, RootProvider<Http>>>,
+        market_state: Arc<MarketState<N, P>>,
+        gas_price: U256,
+        loan_gas: u64,
+        flash_loan: &FlashLoanConfig,
+        bounds: InputBounds,
+        gas_in_token: impl Fn(U256) -> Option<U256>,
     ) -> (U256, U256) {
-        let mut best_input = *AMOUNT.read().unwrap();
-        let mut best_output = initial_out;
-        let mut curr_input = *AMOUNT.read().unwrap();
-
-        let step = U256::from(200000000000000u128); // ✅ precise 2e14 step
-
-        for _ in 0..50 {
-            curr_input += step;
-            quote_path.amountIn = curr_input;
-
-            match Self::quote_path(quote_path.clone(), market_state.clone()) {
-                Ok(amounts) => {
-                    if let Some(&output) = amounts.last() {
-                        if output > curr_input && output > best_output {
-                            best_output = output;
-                            best_input = curr_input;
-                            continue;
-                        }
-                    }
-                    // If output not better, stop early
-                    break;
+        // amountIn was sized for the path's start token via `amount_for`
+        let start = quote_path.amountIn;
+        search_input(start, bounds, |input| {
+            quote_path.amountIn = input;
+            match Self::quote_path_with_gas(quote_path.clone(), market_state.clone()) {
+                Ok((amounts, gas_used)) => {
+                    let output = *amounts.last()?;
+                    let gas_cost = gas_in_token(U256::from(gas_used + loan_gas) * gas_price)?;
+                    Some(output.saturating_sub(input + flash_loan.fee(input) + gas_cost))
                 }
                 Err(e) => {
-                    info!("Binary search early exit: {e}");
-                    break;
+                    info!("Input {} not quotable: {e}", input);
+                    None
                 }
             }
+        })
+    }
+}
+
+/// Hill climb behind `Quoter::optimize_input` over any `net_profit` curve, None marking unquotable inputs.
+/// Probes `step` above and below the best input so far, halving `step` once neither side improves,
+/// until it drops below `bounds.resolution` or `MAX_OPTIMIZE_PROBES` rounds ran
+fn search_input(start: U256, bounds: InputBounds, mut net_profit: impl FnMut(U256) -> Option<U256>) -> (U256, U256) {
+    let mut best_input = bounds.clamp(start);
+    let mut best_profit = net_profit(best_input).unwrap_or_default();
+    let mut step = (best_input / U256::from(2)).max(U256::from(1));
+
+    for _ in 0..MAX_OPTIMIZE_PROBES {
+        if step < bounds.resolution {
+            break;
+        }
+        let up = bounds.clamp(best_input.saturating_add(step));
+        let down = bounds.clamp(best_input.saturating_sub(step));
+
+        let improved = [up, down]
+            .into_iter()
+            .filter(|input| *input != best_input)
+            .filter_map(|input| Some((input, net_profit(input)?)))
+            .filter(|(_, profit)| *profit > best_profit)
+            .max_by_key(|(_, profit)| *profit);
+
+        match improved {
+            Some((input, profit)) => {
+                best_input = input;
+                best_profit = profit;
+            }
+            None => step /= U256::from(2),
+        }
+    }
+
+    (best_input, best_profit)
+}
+
+/// Sampling behind `Quoter::profit_curve` over any `quote`, None marking inputs that revert.
+/// Stops at the first unquotable input, empty if no sample's output clears its input
+fn sample_profit_curve(
+    min_in: U256,
+    max_in: U256,
+    steps: usize,
+    mut quote: impl FnMut(U256) -> Option<U256>,
+) -> Vec<(U256, U256)> {
+    if steps == 0 || max_in < min_in {
+        return Vec::new();
+    }
+    let interval = if steps > 1 {
+        (max_in - min_in) / U256::from(steps - 1)
+    } else {
+        U256::ZERO
+    };
+
+    let mut curve = Vec::with_capacity(steps);
+    for i in 0..steps {
+        let input = min_in + interval * U256::from(i);
+        // Past the point the path reverts, larger inputs only revert too
+        let Some(output) = quote(input) else {
+            break;
+        };
+        curve.push((input, output));
+    }
+
+    if curve.iter().all(|(input, output)| output <= input) {
+        return Vec::new();
+    }
+    curve
+}
+
+// Lower bound of the input search as a fraction of the starting amount: start / divisor.
+// Override with OPTIMIZE_MIN_DIVISOR
+const DEFAULT_MIN_INPUT_DIVISOR: u64 = 100;
+// Upper bound as a multiple of the starting amount for tokens without a flash loan cap.
+// Override with OPTIMIZE_MAX_MULTIPLIER
+const DEFAULT_MAX_INPUT_MULTIPLIER: u64 = 10;
+// Finest step the search probes, as decimals below a whole token (4: 0.0001 of a token).
+// Override with OPTIMIZE_RESOLUTION_DIGITS
+const DEFAULT_RESOLUTION_DIGITS: u8 = 4;
+// Step-halving rounds `optimize_input` runs at most, each quoting up to two inputs
+const MAX_OPTIMIZE_PROBES: usize = 50;
+
+/// Range `Quoter::optimize_input` searches, in raw units of the path's start token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputBounds {
+    pub min_in: U256,
+    pub max_in: U256,
+    /// Smallest step still worth quoting, the search stops once its step falls below it
+    pub resolution: U256,
+}
+
+impl InputBounds {
+    /// Bounds explicitly set, swapped if given in the wrong order, searched down to single units
+    pub fn new(min_in: U256, max_in: U256) -> Self {
+        Self {
+            min_in: min_in.min(max_in),
+            max_in: min_in.max(max_in),
+            resolution: U256::from(1),
+        }
+    }
+
+    /// Stops the search at steps below `resolution` raw units
+    pub fn with_resolution(mut self, resolution: U256) -> Self {
+        self.resolution = resolution.max(U256::from(1));
+        self
+    }
+
+    /// Bounds around `start` for a path borrowing `token`: `start / OPTIMIZE_MIN_DIVISOR` up to the token's
+    /// flash loan cap, or `start * OPTIMIZE_MAX_MULTIPLIER` when it has none, searched down to
+    /// `10^-OPTIMIZE_RESOLUTION_DIGITS` of a token. None while the token's decimals are unknown
+    pub fn around(token: Address, start: U256, flash_loan: &FlashLoanConfig) -> Option<Self> {
+        let decimals = decimals_of(token)?;
+        Some(Self::for_decimals(start, decimals, flash_loan.max_loan_for(token, decimals)))
+    }
+
+    fn for_decimals(start: U256, decimals: u8, max_loan: Option<U256>) -> Self {
+        let divisor = std::env::var("OPTIMIZE_MIN_DIVISOR")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|d: &u64| *d > 0)
+            .unwrap_or(DEFAULT_MIN_INPUT_DIVISOR);
+        let multiplier: u64 = std::env::var("OPTIMIZE_MAX_MULTIPLIER")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_INPUT_MULTIPLIER);
+        let digits: u8 = std::env::var("OPTIMIZE_RESOLUTION_DIGITS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RESOLUTION_DIGITS);
+        let max_in = max_loan.unwrap_or_else(|| start.saturating_mul(U256::from(multiplier)));
+        let resolution = U256::from(10u64).pow(U256::from(decimals.saturating_sub(digits)));
+        Self::new(start / U256::from(divisor), max_in).with_resolution(resolution)
+    }
+
+    #[inline]
+    pub fn clamp(&self, input: U256) -> U256 {
+        input.clamp(self.min_in, self.max_in)
+    }
+}
+
+// EVM instances a `QuoterPool` quotes on at once. Override with QUOTER_POOL_SIZE
+const DEFAULT_QUOTER_POOL_SIZE: usize = 4;
+
+/// Quotes many paths in parallel on a bounded number of EVMs.
+/// Every EVM runs over its own overlay of the same read-locked DB and never commits,
+/// so each path quotes exactly as `Quoter::quote_path` would, whichever instance it lands on
+pub struct QuoterPool<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    market_state: Arc<MarketState<N, P>>,
+    size: usize,
+}
+
+impl<N, P> QuoterPool<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    pub fn new(market_state: Arc<MarketState<N, P>>, size: usize) -> Self {
+        Self {
+            market_state,
+            size: size.max(1),
+        }
+    }
+
+    /// Pool sized from QUOTER_POOL_SIZE
+    pub fn from_env(market_state: Arc<MarketState<N, P>>) -> Self {
+        let size = std::env::var("QUOTER_POOL_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_QUOTER_POOL_SIZE);
+        Self::new(market_state, size)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Quotes every path, results in the order of `paths`.
+    /// Paths are split into at most `size` contiguous chunks, one EVM per chunk, all under a single DB read lock
+    pub fn quote_many(&self, paths: &[SwapPath]) -> Vec<Result<Vec<U256>>> {
+        if paths.is_empty() {
+            return Vec::new();
+        }
+        let guard = self.market_state.db_read();
+        let db = &*guard;
+        let chunk_size = paths.len().div_ceil(self.size);
+
+        paths
+            .par_chunks(chunk_size)
+            .map(|chunk| {
+                let mut overlay = CacheDB::new(WrapDatabaseRef(db));
+                let mut evm = Quoter::quote_evm(&mut overlay, &self.market_state);
+                chunk
+                    .iter()
+                    .map(|path| {
+                        let params = path.clone().try_into()?;
+                        Quoter::quote_on_evm(&mut evm, params).map(|(amounts, _)| amounts)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::market_state::inject_quoter;
+    use crate::utile::swap::SwapStep;
+    use crate::utile::test_utils::{full_node_provider, insert_v2_offline, market_state, offline_db, v2_pool};
+    use alloy::primitives::{Address, address};
+    use pool_sync::{Pool, PoolType};
+
+    alloy::sol! {
+        #[sol(rpc)]
+        contract V2Pair {
+            function token0() external view returns (address);
+            function token1() external view returns (address);
+        }
+    }
+
+    /// V2 pools listed comma separated in `var`, read from the node at FULL with their current reserves
+    async fn v2_pools_from_env(var: &str) -> (Vec<Address>, Vec<Pool>) {
+        use crate::utile::rgen::V2State;
+
+        let pools: Vec<Address> = std::env::var(var)
+            .unwrap_or_else(|_| panic!("{var} must list V2 pools"))
+            .split(',')
+            .map(|pool| pool.trim().parse().unwrap())
+            .collect();
+        let provider = full_node_provider();
+        let mut listed = Vec::new();
+        for pool in &pools {
+            let token0 = V2Pair::new(*pool, &provider).token0().call().await.unwrap();
+            let token1 = V2Pair::new(*pool, &provider).token1().call().await.unwrap();
+            let reserves = V2State::new(*pool, &provider).getReserves().call().await.unwrap();
+            listed.push(v2_pool(*pool, token0, token1, reserves.reserve0.to(), reserves.reserve1.to()));
+        }
+        (pools, listed)
+    }
+
+    fn v2_step(pool: Address, token_in: Address, token_out: Address) -> SwapStep {
+        SwapStep {
+            pool_address: pool,
+            token_in,
+            token_out,
+            protocol: PoolType::UniswapV2,
+            fee: 0,
+            split: Vec::new(),
+        }
+    }
+
+    // Every listed pool is round-tripped from both of its tokens, so each path quotes a different amount
+    // and a result landing in the wrong slot shows up. Run with FULL=<base rpc>
+    // QUOTE_MANY_POOLS=<v2 pool>,<v2 pool>,... cargo test -- --ignored, two pools or more
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn quote_many_matches_quote_path() {
+        let (pools, listed) = v2_pools_from_env("QUOTE_MANY_POOLS").await;
+        let paths: Vec<SwapPath> = pools
+            .iter()
+            .zip(&listed)
+            .flat_map(|(pool, listed)| {
+                let (token0, token1) = (listed.token0_address(), listed.token1_address());
+                [
+                    SwapPath::new(vec![v2_step(*pool, token0, token1), v2_step(*pool, token1, token0)]),
+                    SwapPath::new(vec![v2_step(*pool, token1, token0), v2_step(*pool, token0, token1)]),
+                ]
+            })
+            .collect();
+
+        let state = MarketState::standalone(listed, full_node_provider()).await.unwrap();
+        inject_quoter(&mut state.db.write().unwrap(), state.deployment.quoter);
+        let quoter_pool = QuoterPool::new(Arc::clone(&state), 3);
+        assert!(paths.len() > quoter_pool.size(), "list more pools to spread paths over every EVM");
+
+        let pooled = quoter_pool.quote_many(&paths);
+        assert_eq!(pooled.len(), paths.len());
+        assert!(pooled.iter().any(Result::is_ok), "no path quoted: {pooled:?}");
+        for (path, pooled) in paths.iter().zip(pooled) {
+            let single = Quoter::quote_path(path.clone().try_into().unwrap(), Arc::clone(&state));
+            match (pooled, single) {
+                (Ok(pooled), Ok(single)) => assert_eq!(pooled, single, "path {}", path.hash),
+                (Err(pooled), Err(single)) => assert_eq!(pooled.to_string(), single.to_string()),
+                (pooled, single) => panic!("path {} diverged: {:?} vs {:?}", path.hash, pooled, single),
+            }
+        }
+    }
+
+    /// Profit of every sample, asserting it climbs to a single peak then only falls as price impact takes over
+    fn assert_rises_then_falls(curve: &[(U256, U256)]) {
+        let profits: Vec<i128> = curve
+            .iter()
+            .map(|(input, output)| output.to::<u128>() as i128 - input.to::<u128>() as i128)
+            .collect();
+        let peak = (0..profits.len()).max_by_key(|i| profits[*i]).unwrap();
+        assert!(peak > 0 && peak < profits.len() - 1, "peak at the edge of {profits:?}");
+        assert!(profits[..=peak].windows(2).all(|w| w[0] <= w[1]), "{profits:?}");
+        assert!(profits[peak..].windows(2).all(|w| w[0] >= w[1]), "{profits:?}");
+    }
+
+    // WETH is 10% dearer in USDC on one pool than the other. Sold on the dear pool and bought back on the
+    // cheap one, small inputs profit, the peak sits near 20 WETH and from ~45 WETH on the cycle loses
+    #[tokio::test(flavor = "multi_thread")]
+    async fn two_pool_cycle_profit_rises_then_falls() {
+        use crate::calculation::Calculator;
+
+        let (weth, usdc) = (
+            address!("0000000000000000000000000000000000000a01"),
+            address!("0000000000000000000000000000000000000a02"),
+        );
+        let (cheap, dear) = (
+            address!("0000000000000000000000000000000000000b01"),
+            address!("0000000000000000000000000000000000000b02"),
+        );
+        let one = 10u128.pow(18);
+        let mut db = offline_db();
+        insert_v2_offline(&mut db, v2_pool(cheap, weth, usdc, 1_000 * one, 3_000_000 * one));
+        insert_v2_offline(&mut db, v2_pool(dear, weth, usdc, 1_000 * one, 3_300_000 * one));
+        let calculator = Calculator::new(market_state(db));
+        let path = SwapPath::new(vec![v2_step(dear, weth, usdc), v2_step(cheap, usdc, weth)]);
+
+        let one = U256::from(one);
+        let curve = sample_profit_curve(one / U256::from(100), one * U256::from(200), 40, |input| {
+            Some(calculator.compute_path_output(&path, input))
+        });
+        assert_eq!(curve.len(), 40);
+        assert_rises_then_falls(&curve);
+
+        // Every sample losing means no curve at all
+        let losing = SwapPath::new(vec![v2_step(cheap, weth, usdc), v2_step(dear, usdc, weth)]);
+        let curve = sample_profit_curve(one / U256::from(100), one * U256::from(200), 40, |input| {
+            Some(calculator.compute_path_output(&losing, input))
+        });
+        assert!(curve.is_empty());
+    }
+
+    // The same shape from the FlashQuoter bytecode, WETH -> token -> WETH through two V2 pools of one pair.
+    // Run with FULL=<base rpc> PROFIT_CURVE_POOLS=<v2 pool>,<v2 pool> cargo test -- --ignored,
+    // the first pool pricing WETH higher than the second by enough to peak between 0.001 and 10 WETH
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn profit_curve_rises_then_falls() {
+        let (pools, listed) = v2_pools_from_env("PROFIT_CURVE_POOLS").await;
+        let weth = crate::utile::constant::ChainDefaults::for_chain(pool_sync::Chain::Base).weth;
+        let other = if listed[0].token0_address() == weth { listed[0].token1_address() } else { listed[0].token0_address() };
+
+        let state = MarketState::standalone(listed, full_node_provider()).await.unwrap();
+        inject_quoter(&mut state.db.write().unwrap(), state.deployment.quoter);
+        let path = SwapPath::new(vec![v2_step(pools[0], weth, other), v2_step(pools[1], other, weth)]);
+
+        let one = U256::from(10u64).pow(U256::from(18));
+        let curve = Quoter::profit_curve(&path, one / U256::from(1_000), one * U256::from(10), 40, state);
+        assert!(!curve.is_empty(), "the pools in PROFIT_CURVE_POOLS never clear their input");
+        assert_rises_then_falls(&curve);
+    }
+
+    /// Net profit of a synthetic curve: `gain * x - x^2 / curvature - cost(x)`, None below zero
+    fn curve(gain: i128, curvature: i128, cost: impl Fn(i128) -> i128) -> impl FnMut(U256) -> Option<U256> {
+        move |input| {
+            let x = input.to::<u64>() as i128;
+            let net = gain * x - x * x / curvature - cost(x);
+            (net > 0).then(|| U256::from(net as u128))
         }
+    }
+
+    // Gross output peaks at 1000, but gas growing with the ticks crossed moves the net peak down to 750
+    #[test]
+    fn search_finds_the_net_optimum_not_the_gross_one() {
+        let bounds = InputBounds::new(U256::from(1), U256::from(10_000));
+        let (gross_best, _) = search_input(U256::from(500), bounds, curve(2, 1_000, |_| 0));
+        let (net_best, _) = search_input(U256::from(500), bounds, curve(2, 1_000, |x| x / 2));
+
+        assert!(gross_best.abs_diff(U256::from(1_000)) <= U256::from(1), "gross peak at {gross_best}");
+        assert!(net_best.abs_diff(U256::from(750)) <= U256::from(1), "net peak at {net_best}");
+    }
+
+    // The default input is profitable but past the peak, shrinking the trade earns more
+    #[test]
+    fn search_walks_below_the_starting_amount() {
+        let start = U256::from(5_000);
+        let bounds = InputBounds::new(U256::from(50), U256::from(50_000));
+        let mut net_profit = curve(6_000, 1, |_| 0);
+        let start_profit = net_profit(start).unwrap();
+        let (best, profit) = search_input(start, bounds, net_profit);
+
+        assert!(best.abs_diff(U256::from(3_000)) <= U256::from(1), "peak at {best}");
+        assert!(profit > start_profit);
+    }
+
+    #[test]
+    fn bounds_follow_the_token_decimals_and_loan_cap() {
+        let usdc_start = U256::from(1_000_000_000u64);
+        let usdc = InputBounds::for_decimals(usdc_start, 6, Some(U256::from(50_000_000_000u64)));
+        assert_eq!(usdc.resolution, U256::from(100));
+        assert_eq!(usdc.max_in, U256::from(50_000_000_000u64));
+        assert_eq!(usdc.min_in, usdc_start / U256::from(DEFAULT_MIN_INPUT_DIVISOR));
 
-        (best_input, best_output)
+        let weth_start = U256::from(10u64).pow(U256::from(18));
+        let weth = InputBounds::for_decimals(weth_start, 18, None);
+        assert_eq!(weth.resolution, U256::from(10u64).pow(U256::from(14)));
+        assert_eq!(weth.max_in, weth_start * U256::from(DEFAULT_MAX_INPUT_MULTIPLIER));
     }
 }
@@ -0,0 +1,93 @@
+use alloy::primitives::{Bytes, U256, hex};
+use alloy::sol_types::SolValue;
+
+/// Selector of `Error(string)`, emitted by `require` and `revert` with a message
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of `Panic(uint256)`, emitted by failed asserts, checked arithmetic and bad indexing
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Human readable reason of a revert, None for empty output and custom errors.
+/// `Error(string)` yields its message, `Panic(uint256)` the panic code and what raised it
+pub fn decode_revert_reason(output: &Bytes) -> Option<String> {
+    let (selector, data) = output.split_at_checked(4)?;
+    if selector == ERROR_SELECTOR {
+        return String::abi_decode(data).ok();
+    }
+    if selector == PANIC_SELECTOR {
+        let code = U256::abi_decode(data).ok()?;
+        return Some(format!("panic 0x{:02x}: {}", code, panic_reason(code)));
+    }
+    None
+}
+
+/// Decoded reason for log lines, the raw hex when the output can't be decoded
+pub fn describe_revert(output: &Bytes) -> String {
+    match decode_revert_reason(output) {
+        Some(reason) => reason,
+        None if output.is_empty() => "no revert data".to_string(),
+        None => format!("0x{}", hex::encode(output)),
+    }
+}
+
+/// Meaning of the Solidity panic codes
+fn panic_reason(code: U256) -> &'static str {
+    // Codes past u64 come from hand-rolled reverts, not the compiler
+    match u64::try_from(code).unwrap_or(u64::MAX) {
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array encoding",
+        0x31 => "pop on empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to zero-initialized function",
+        _ => "unknown panic",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::bytes;
+
+    #[test]
+    fn error_strings_decode_to_their_message() {
+        // revert("UniswapV2Router: INSUFFICIENT_OUTPUT_AMOUNT") as emitted by the router
+        let output = bytes!(
+            "08c379a0"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "000000000000000000000000000000000000000000000000000000000000002b"
+            "556e69737761705632526f757465723a20494e53554646494349454e545f4f55"
+            "545055545f414d4f554e54000000000000000000000000000000000000000000"
+        );
+        assert_eq!(
+            decode_revert_reason(&output).as_deref(),
+            Some("UniswapV2Router: INSUFFICIENT_OUTPUT_AMOUNT")
+        );
+    }
+
+    #[test]
+    fn panics_decode_to_their_code_and_cause() {
+        let panic = |code: U256| Bytes::from([PANIC_SELECTOR.as_slice(), &code.to_be_bytes::<32>()].concat());
+
+        assert_eq!(
+            decode_revert_reason(&panic(U256::from(0x11))).as_deref(),
+            Some("panic 0x11: arithmetic overflow or underflow")
+        );
+        assert!(decode_revert_reason(&panic(U256::MAX)).unwrap().ends_with("unknown panic"));
+    }
+
+    #[test]
+    fn empty_and_custom_errors_have_no_reason() {
+        assert_eq!(decode_revert_reason(&Bytes::new()), None);
+        assert_eq!(describe_revert(&Bytes::new()), "no revert data");
+
+        // InsufficientLiquidity(), a custom error with no arguments
+        let custom = bytes!("bb55fd27");
+        assert_eq!(decode_revert_reason(&custom), None);
+        assert_eq!(describe_revert(&custom), "0xbb55fd27");
+        // Error selector with a truncated payload
+        assert_eq!(decode_revert_reason(&Bytes::from(ERROR_SELECTOR.to_vec())), None);
+    }
+}
@@ -14,6 +14,40 @@ sol!(
     "./abi/FlashQuoter.json"
 );
 
+// Split-route entry point of the FlashSwap executor, called on the same address as `executeArbitrage`.
+// Each hop divides its running input across `pools` by `sharesBps` (summing to 10_000) and sums the outputs.
+// FlashSwap.sol has to implement this before split paths can leave dry-run
+sol!(
+    #[sol(rpc)]
+    contract FlashSwapSplit {
+        struct SplitHop {
+            address[] pools;
+            uint8[] poolVersions;
+            uint16[] sharesBps;
+        }
+        struct SplitSwapParams {
+            SplitHop[] hops;
+            uint256 amountIn;
+        }
+        function executeSplitArbitrage(SplitSwapParams arb) external;
+    }
+);
+
+// `executeArbitrage` of a FlashSwap whose SwapParams carries `amountOutMin`, the executor reverts when the
+// last hop returns less. The struct change gives it a different selector than the deployed ABI's
+sol!(
+    #[sol(rpc)]
+    contract FlashSwapMinOut {
+        struct SwapParams {
+            address[] pools;
+            uint8[] poolVersions;
+            uint256 amountIn;
+            uint256 amountOutMin;
+        }
+        function executeArbitrage(SwapParams arb) external;
+    }
+);
+
 pub use FlashSwap::FlashSwapInstance;
 pub use FlashQuoter::FlashQuoterInstance;
 
@@ -23,6 +57,7 @@ sol!(
     contract ERC20Token {
         function approve(address spender, uint256 amount) external returns (bool success);
         function balanceOf(address account) external view returns (uint256);
+        function decimals() external view returns (uint8);
     }
 );
 
@@ -0,0 +1,253 @@
+use crate::calculation::Calculator;
+use crate::calculation::uniswap::{v2_amount_out, v2_fee};
+use crate::utile::events::Event;
+use crate::utile::market_state::MarketState;
+use crate::utile::swap::{SwapPath, SwapStep};
+use alloy::network::Network;
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use pool_sync::PoolType;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, info, warn};
+
+// Search iterations for the victim's slippage bound and the profit maximum
+const BOUND_SEARCH_ITERATIONS: usize = 128;
+const PROFIT_SEARCH_ITERATIONS: usize = 128;
+
+/// A pending swap decoded from the mempool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingSwap {
+    pub pool: Address,
+    pub pool_type: PoolType,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub amount_in: U256,
+    /// Victim's slippage bound, the front-run can't push their output below this
+    pub amount_out_min: U256,
+}
+
+/// Sizes front-run / back-run pairs around pending V2 swaps
+pub struct Sandwicher<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    calculator: Calculator<N, P>,
+}
+
+impl<N, P> Sandwicher<N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    pub fn new(market_state: Arc<MarketState<N, P>>) -> Self {
+        Self {
+            calculator: Calculator::new(market_state),
+        }
+    }
+
+    /// Finds the front-run size that maximizes back-run profit, returns (front_run, back_run, profit)
+    pub fn find_sandwich(&self, victim: &PendingSwap) -> Option<(SwapPath, SwapPath, U256)> {
+        let fee = v2_fee(victim.pool_type)?;
+        let (reserve_in, reserve_out) = {
+            let db = self.calculator.db_read();
+            let zero_to_one = db.zero_to_one(&victim.pool, victim.token_in)?;
+            let (reserve0, reserve1) = db.get_reserves(&victim.pool);
+            if zero_to_one {
+                (reserve0, reserve1)
+            } else {
+                (reserve1, reserve0)
+            }
+        };
+
+        let (front_run_amount, profit) = size_sandwich(victim, reserve_in, reserve_out, fee)?;
+        debug!(
+            "Sandwich on {}: front-run {} for profit {}",
+            victim.pool, front_run_amount, profit
+        );
+
+        let front_run = SwapPath::new(vec![SwapStep {
+            pool_address: victim.pool,
+            token_in: victim.token_in,
+            token_out: victim.token_out,
+            protocol: victim.pool_type,
+            fee: 0,
+            split: Vec::new(),
+        }]);
+        let back_run = SwapPath::new(vec![SwapStep {
+            pool_address: victim.pool,
+            token_in: victim.token_out,
+            token_out: victim.token_in,
+            protocol: victim.pool_type,
+            fee: 0,
+            split: Vec::new(),
+        }]);
+        Some((front_run, back_run, profit))
+    }
+
+    /// Sizes a sandwich around `victim` and forwards it down the pipeline if profitable
+    pub async fn process(&self, victim: &PendingSwap, block_number: u64, bundle_tx: &Sender<Event>) {
+        let Some((front_run, back_run, profit)) = self.find_sandwich(victim) else {
+            return;
+        };
+
+        info!("🥪 Sandwich found on {} with profit {}", victim.pool, profit);
+        if let Err(e) = bundle_tx
+            .send(Event::SandwichBundle((front_run, back_run, profit, block_number)))
+            .await
+        {
+            debug!("⚠️ Failed to send sandwich bundle: {:?}", e);
+        }
+    }
+
+    /// Sizes sandwiches for every pending swap on the event stream until it closes or `shutdown` fires
+    pub async fn run(
+        self,
+        mut event_rx: broadcast::Receiver<Event>,
+        bundle_tx: Sender<Event>,
+        mut shutdown: broadcast::Receiver<()>,
+    ) {
+        loop {
+            let event = tokio::select! {
+                event = event_rx.recv() => event,
+                _ = shutdown.recv() => break,
+            };
+            match event {
+                Ok(Event::PendingSwap(victim)) => {
+                    // The victim lands in the block after the latest one we processed
+                    let block_number = {
+                        let block_env = self.calculator.market_state.block_env_read();
+                        block_env.number.saturating_to::<u64>() + 1
+                    };
+                    self.process(&victim, block_number, &bundle_tx).await;
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => warn!("Sandwicher lagged, skipped {} events", skipped),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+/// Front-run size maximizing the back-run profit on a V2 pool with reserves `reserve_in`/`reserve_out`,
+/// returns (front_run_amount, profit), None when no size the victim tolerates is profitable
+pub fn size_sandwich(
+    victim: &PendingSwap,
+    reserve_in: U256,
+    reserve_out: U256,
+    fee: U256,
+) -> Option<(U256, U256)> {
+    // The victim's output only shrinks as the front-run grows, find the largest size they still tolerate
+    let mut lo = U256::ZERO;
+    let mut hi = reserve_in;
+    for _ in 0..BOUND_SEARCH_ITERATIONS {
+        if hi - lo <= U256::from(1) {
+            break;
+        }
+        let mid = lo + (hi - lo) / U256::from(2);
+        match sandwich_profit(victim, mid, reserve_in, reserve_out, fee) {
+            Some(_) => lo = mid,
+            None => hi = mid,
+        }
+    }
+    let max_front_run = lo;
+    if max_front_run.is_zero() {
+        return None;
+    }
+
+    // Profit is unimodal in the front-run size, ternary search for its peak
+    let mut lo = U256::ZERO;
+    let mut hi = max_front_run;
+    let profit_at = |amount: U256| {
+        sandwich_profit(victim, amount, reserve_in, reserve_out, fee).unwrap_or_default()
+    };
+    for _ in 0..PROFIT_SEARCH_ITERATIONS {
+        if hi - lo <= U256::from(2) {
+            break;
+        }
+        let third = (hi - lo) / U256::from(3);
+        let (m1, m2) = (lo + third, hi - third);
+        if profit_at(m1) < profit_at(m2) {
+            lo = m1;
+        } else {
+            hi = m2;
+        }
+    }
+
+    let front_run_amount = lo + (hi - lo) / U256::from(2);
+    let profit = profit_at(front_run_amount);
+    if profit.is_zero() {
+        return None;
+    }
+    Some((front_run_amount, profit))
+}
+
+/// Profit of front-running with `amount`, None if the victim's swap would revert.
+/// All three legs are quoted with the same constant-product math, each on the reserves the previous leg leaves
+fn sandwich_profit(
+    victim: &PendingSwap,
+    amount: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    fee: U256,
+) -> Option<U256> {
+    let front_out = v2_amount_out(amount, reserve_in, reserve_out, fee);
+    let (reserve_in, reserve_out) = (reserve_in + amount, reserve_out.checked_sub(front_out)?);
+
+    let victim_out = v2_amount_out(victim.amount_in, reserve_in, reserve_out, fee);
+    if victim_out < victim.amount_out_min {
+        return None;
+    }
+    let (reserve_in, reserve_out) = (reserve_in + victim.amount_in, reserve_out.checked_sub(victim_out)?);
+
+    let back_out = v2_amount_out(front_out, reserve_out, reserve_in, fee);
+    Some(back_out.saturating_sub(amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETHER: u128 = 1_000_000_000_000_000_000;
+
+    fn victim(amount_in: U256, amount_out_min: U256) -> PendingSwap {
+        PendingSwap {
+            pool: Address::repeat_byte(0x01),
+            pool_type: PoolType::UniswapV2,
+            token_in: Address::repeat_byte(0x02),
+            token_out: Address::repeat_byte(0x03),
+            amount_in,
+            amount_out_min,
+        }
+    }
+
+    #[test]
+    fn sandwiches_v2_victim_within_their_slippage() {
+        let fee = U256::from(9970);
+        let (reserve_in, reserve_out) = (U256::from(1_000 * ETHER), U256::from(1_000 * ETHER));
+        let amount_in = U256::from(50 * ETHER);
+        let quoted = v2_amount_out(amount_in, reserve_in, reserve_out, fee);
+        // Victim accepts 5% slippage
+        let victim = victim(amount_in, quoted * U256::from(95) / U256::from(100));
+
+        let (front_run, profit) = size_sandwich(&victim, reserve_in, reserve_out, fee).unwrap();
+        assert!(!front_run.is_zero());
+        assert!(!profit.is_zero());
+
+        // The chosen size still lets the victim's swap through, anything far past it doesn't
+        assert_eq!(sandwich_profit(&victim, front_run, reserve_in, reserve_out, fee), Some(profit));
+        assert!(sandwich_profit(&victim, reserve_in, reserve_in, reserve_out, fee).is_none());
+    }
+
+    #[test]
+    fn no_sandwich_without_slippage_room() {
+        let fee = U256::from(9970);
+        let (reserve_in, reserve_out) = (U256::from(1_000 * ETHER), U256::from(1_000 * ETHER));
+        let amount_in = U256::from(50 * ETHER);
+        let quoted = v2_amount_out(amount_in, reserve_in, reserve_out, fee);
+
+        assert!(size_sandwich(&victim(amount_in, quoted), reserve_in, reserve_out, fee).is_none());
+    }
+}
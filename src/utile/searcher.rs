@@ -1,22 +1,52 @@
 use crate::calculation::calculator;
-use crate::utile::constant::AMOUNT;
+use crate::utile::blacklist::path_is_blacklisted;
+use crate::utile::constant::{FlashLoanConfig, amount_for};
+use crate::utile::errors::WorkerError;
 use crate::utile::estimator::Estimator;
 use crate::utile::events::Event;
+use crate::utile::gas_station::ChainGasModel;
 use crate::utile::market_state::MarketState;
+use crate::utile::quoter::{InputBounds, Quoter};
+use crate::utile::rgen::FlashQuoter;
 use crate::utile::swap::SwapPath;
+use crate::utile::watchdog::Heartbeat;
 use rayon::prelude::*;
+use crate::utile::Cache;
+use crate::utile::opportunities::{OpportunityRecord, RecentOpportunities, RingBuffer};
 use std::collections::{HashMap, HashSet};
-use std::str::FromStr;
-use std::sync::Arc;
-use std::time::Instant;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::{Receiver, Sender};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use alloy::network::Network;
 use alloy::primitives::{Address, U256};
 use alloy::providers::Provider;
-use anyhow::Context;
 //use super::utills::calculation::calculator;
 
+// Estimates returning this many times the path's input or more are treated as pricing errors rather than opportunities
+const MAX_PLAUSIBLE_RETURN_MULTIPLE: u64 = 2;
+
+// Profitable paths kept for the API and post-hoc analysis. Override with RECENT_OPPORTUNITIES
+const DEFAULT_RECENT_OPPORTUNITIES: usize = 100;
+
+// Time budget for one block's search, under Base's 2s block time. Override with SEARCH_DEADLINE_MS
+const DEFAULT_SEARCH_DEADLINE: Duration = Duration::from_millis(1500);
+
+/// Smallest output worth sending for a path borrowing `input`: the loan and its premium, `gas_cost`
+/// and a 1% buffer on the input, all in the start token
+fn required_output(input: U256, flash_loan: &FlashLoanConfig, gas_cost: U256) -> U256 {
+    let repayment = input + flash_loan.fee(input);
+    let buffer = input / U256::from(100);
+    repayment + gas_cost + buffer
+}
+
+/// Whether `output` is a believable return on `input`, both in the start token so the bound holds for any decimals
+fn plausible_output(input: U256, output: U256) -> bool {
+    output < input.saturating_mul(U256::from(MAX_PLAUSIBLE_RETURN_MULTIPLE))
+}
+
 /// Top-level search engine for arbitrage cycles
 pub struct Searchoor<N, P>
 where
@@ -27,7 +57,11 @@ where
     estimator: Estimator<N, P>,
     path_index: HashMap<Address, Vec<usize>>,
     cycles: Vec<SwapPath>,
-    min_profit: U256,
+    recent: RecentOpportunities,
+    heartbeat: Arc<Heartbeat>,
+    search_deadline: Duration,
+    flash_loan: FlashLoanConfig,
+    gas_model: Arc<ChainGasModel>,
 }
 
 impl<N, P> Searchoor<N, P>
@@ -39,6 +73,8 @@ where
         cycles: Vec<SwapPath>,
         market_state: Arc<MarketState<N, P>>,
         estimator: Estimator<N, P>,
+        flash_loan: FlashLoanConfig,
+        gas_model: Arc<ChainGasModel>,
     ) -> Self {
         let calculator = calculator::Calculator::new(market_state);
 
@@ -50,37 +86,160 @@ where
             }
         }
 
-        // 💰 Minimum profit is loan repayment + 1% buffer
-        let initial_amount = *AMOUNT.read().unwrap();
-        let flash_loan_fee = (initial_amount * U256::from(9)) / U256::from(10000);
-        let repayment_amount = initial_amount + flash_loan_fee;
-        let min_profit_percentage = (initial_amount * U256::from(1)) / U256::from(100);
-        let min_profit = repayment_amount + min_profit_percentage;
-
         Self {
             calculator,
             estimator,
             cycles,
             path_index: index,
-            min_profit,
+            recent: Arc::new(Mutex::new(RingBuffer::new(
+                std::env::var("RECENT_OPPORTUNITIES")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_RECENT_OPPORTUNITIES),
+            ))),
+            heartbeat: Arc::new(Heartbeat::new()),
+            search_deadline: std::env::var("SEARCH_DEADLINE_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_SEARCH_DEADLINE),
+            flash_loan,
+            gas_model,
         }
     }
 
+    /// Overrides the per-block search budget
+    pub fn with_search_deadline(mut self, deadline: Duration) -> Self {
+        self.search_deadline = deadline;
+        self
+    }
+
+    /// Overrides how many profitable paths are retained, dropping what was recorded so far
+    pub fn with_opportunity_capacity(mut self, capacity: usize) -> Self {
+        self.recent = Arc::new(Mutex::new(RingBuffer::new(capacity)));
+        self
+    }
+
+    /// Profitable paths found in recent blocks, shared with the API
+    pub fn opportunities(&self) -> RecentOpportunities {
+        Arc::clone(&self.recent)
+    }
+
+    /// Up to `n` recorded opportunities, newest first
+    pub fn recent_opportunities(&self, n: usize) -> Vec<OpportunityRecord> {
+        self.recent.lock().unwrap().recent(n)
+    }
+
+    /// Bumped for every searched block
+    pub fn heartbeat(&self) -> Arc<Heartbeat> {
+        Arc::clone(&self.heartbeat)
+    }
+
+    /// Quote cache of the searcher's calculator
+    pub fn cache(&self) -> Arc<Cache> {
+        Arc::clone(&self.calculator.cache)
+    }
+
+    /// 💰 Minimum output for a path borrowing `input`: loan repayment + expected gas at `gas_price` + 1% buffer on the input.
+    /// Gas is converted from wei into the start token at the estimator's WETH rate, None while it has none
+    fn min_profit(&self, path: &SwapPath, input: U256, gas_price: U256) -> Option<U256> {
+        let start_token = path.steps.first()?.token_in;
+        let gas_wei = U256::from(self.gas_model.estimate_path(path)) * gas_price;
+        let gas_cost = self.estimator.wei_to_token(gas_wei, start_token)?;
+        Some(required_output(input, &self.flash_loan, gas_cost))
+    }
+
+    /// Input maximizing the path's net profit on the FlashQuoter, searched around its default input size.
+    /// None for paths the quoter can't run, whose start token has no known decimals or no WETH rate yet
+    fn optimal_input(&self, path: &SwapPath, gas_price: U256) -> Option<U256> {
+        let start_token = path.steps.first()?.token_in;
+        let params = match FlashQuoter::SwapParams::try_from(path.clone()) {
+            Ok(params) => params,
+            Err(e) => {
+                debug!("Not optimizing path {}: {e}", path.hash);
+                return None;
+            }
+        };
+        let bounds = InputBounds::around(start_token, params.amountIn, &self.flash_loan)?;
+        let (input, profit) = Quoter::optimize_input(
+            params,
+            Arc::clone(&self.calculator.market_state),
+            gas_price,
+            // The quoter runs the hops without borrowing, the loan's own gas comes from the path gas model
+            self.gas_model.flash_loan_overhead(),
+            &self.flash_loan,
+            bounds,
+            |wei| self.estimator.wei_to_token(wei, start_token),
+        );
+        (profit > U256::ZERO).then_some(input)
+    }
+
+    /// Input size of a path, set per start token
+    fn input_amount(path: &SwapPath) -> U256 {
+        path.steps
+            .first()
+            .map(|step| amount_for(step.token_in))
+            .unwrap_or_default()
+    }
+
+    /// Flash loan principal plus premium a path borrowing `input` has to cover
+    fn repayment(&self, input: U256) -> U256 {
+        input + self.flash_loan.fee(input)
+    }
+
     /// Search for profitable paths whenever a new block update is received
 
     pub async fn search_paths(
         &mut self,
         mut paths_tx: Sender<Event>,
         mut address_rx: Receiver<Event>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        mut shutdown: broadcast::Receiver<()>,
+    ) -> Result<(), WorkerError> {
         let _sim: bool = std::env::var("SIM")
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(false);
+        // VALIDATE cross-checks the forwarded path's off-chain quote against the FlashQuoter bytecode
+        let validate: bool = std::env::var("VALIDATE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(false);
+
+        // Slot changes arrive ahead of the PoolsTouched event of the same block
+        let mut changed_slots: HashMap<Address, Vec<U256>> = HashMap::new();
 
-        while let Some(Event::PoolsTouched(pools, block_number)) = address_rx.recv().await {
+        loop {
+            // A block already being searched is finished before shutdown is noticed
+            let event = tokio::select! {
+                event = address_rx.recv() => event,
+                _ = shutdown.recv() => {
+                    info!("Searcher shutting down");
+                    return Ok(());
+                }
+            };
+            let Some(event) = event else {
+                break;
+            };
+            let (touched, block_number) = match event {
+                Event::SlotsChanged(pool, slots) => {
+                    changed_slots.entry(pool).or_default().extend(slots);
+                    continue;
+                }
+                Event::PoolsTouched(pools, block_number) => (pools, block_number),
+                _ => continue,
+            };
             info!("🧠 Searching block {}...", block_number);
+            self.heartbeat.beat();
             let res = Instant::now();
+            let deadline = res + self.search_deadline;
+            let gas_price = self.calculator.market_state.block_env_read().basefee;
+
+            // Pools whose traced slots all kept their value would quote exactly as before
+            let pools: HashSet<Address> = touched
+                .into_iter()
+                .filter(|pool| changed_slots.contains_key(pool))
+                .collect();
+            changed_slots.clear();
 
             self.calculator.invalidate_cache(&pools);
             self.estimator.update_rates(&pools);
@@ -92,16 +251,26 @@ where
                 .filter_map(|pool| self.path_index.get(pool))
                 .flatten()
                 .map(|&idx| &self.cycles[idx])
+                .filter(|path| !path_is_blacklisted(path))
                 .collect();
 
             info!("🔍 {} paths touched", affected_paths.len());
 
+            // Paths not reached before the deadline are dropped, the state they'd be quoted on is stale by then
+            let skipped = AtomicUsize::new(0);
             let profitable_paths: Vec<(SwapPath, U256)> = affected_paths
                 .par_iter()
                 .filter_map(|path| {
+                    if Instant::now() >= deadline {
+                        skipped.fetch_add(1, Ordering::Relaxed);
+                        return None;
+                    }
+                    let input = Self::input_amount(path);
                     let output_est = self.estimator.estimate_output_amount(path);
-                    if output_est >= self.min_profit
-                        && output_est < U256::from_str("1000000000000000000").unwrap()
+                    if self
+                        .min_profit(path, input, gas_price)
+                        .is_some_and(|min| output_est >= min)
+                        && plausible_output(input, output_est)
                     {
                         Some(((*path).clone(), output_est))
                     } else {
@@ -111,43 +280,107 @@ where
                 .collect();
 
             info!("⏱️ Estimation took {:?}", res.elapsed());
+
+            let skipped = skipped.into_inner();
+            if skipped > 0 {
+                warn!(
+                    "⌛ Search deadline of {:?} hit for block {}, skipped {} of {} paths",
+                    self.search_deadline,
+                    block_number,
+                    skipped,
+                    affected_paths.len()
+                );
+                continue;
+            }
             info!("💎 {} profitable paths found", profitable_paths.len());
 
+            {
+                let mut recent = self.recent.lock().unwrap();
+                for (path, output) in &profitable_paths {
+                    let profit = output.saturating_sub(self.repayment(Self::input_amount(path)));
+                    recent.push(OpportunityRecord::new(block_number, path.clone(), profit));
+                }
+            }
+
             if let Some(best_path) = profitable_paths.iter().max_by_key(|(_, amt)| amt) {
                 let swap_path: &SwapPath = &best_path.0;
-                let first_step = swap_path.steps.first().context("Empty path")?;
-                let input_amount = swap_path.input_amount;
-                let pool_address = first_step.pool_address;
-                let token_in = first_step.token_in;
-                let pool_type = first_step.pool_type;
-                let fee = first_step.fee;
-                
-                let calculated_out = self.calculator.compute_amount_out(
-                     input_amount,
-                     pool_address,
-                     token_in,
-                     pool_type,
-                     fee
-                );
+                if swap_path.steps.is_empty() {
+                    warn!("Skipping empty path {}", swap_path.hash);
+                    continue;
+                }
+                // The estimate only priced the default input size, the quoter sizes the trade for real
+                let input = self
+                    .optimal_input(swap_path, gas_price)
+                    .unwrap_or_else(|| Self::input_amount(swap_path));
+                let calculated_out = self.calculator.compute_path_output(swap_path, input);
 
-                if calculated_out >= self.min_profit {
-                    info!("✅ Best estimated {}, real {}", best_path.1, calculated_out);
+                if let Some(record) = self
+                    .recent
+                    .lock()
+                    .unwrap()
+                    .iter_mut_recent()
+                    .find(|record| record.block_number == block_number && record.path == *swap_path)
+                {
+                    record.real_profit = Some(calculated_out.saturating_sub(self.repayment(input)));
+                }
 
-                    if let Err(e) = paths_tx
+                if validate {
+                    self.calculator.validate_path(swap_path);
+                }
+
+                if self.min_profit(swap_path, input, gas_price).is_some_and(|min| calculated_out >= min) {
+                    info!("✅ Best estimated {}, real {} for input {}", best_path.1, calculated_out, input);
+
+                    paths_tx
                         .send(Event::ArbPath((
                             best_path.0.clone(),
+                            input,
                             calculated_out,
                             block_number,
                         )))
-                        .await
-                    {
-                        debug!("⚠️ Failed to send path: {:?}", e);
-                    } else {
-                        debug!("📤 Sent profitable path");
-                    }
+                        .await?;
+                    debug!("📤 Sent profitable path");
                 }
             }
         }
-        Ok(())
+
+        Err(WorkerError::ChannelClosed("pool update stream ended".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utile::constant::FlashLoanProvider;
+
+    #[test]
+    fn fee_free_loans_only_need_repayment_gas_and_buffer() {
+        let input = U256::from(10u64).pow(U256::from(18));
+        let gas_cost = U256::from(3_000_000_000_000u64);
+        let free = FlashLoanConfig {
+            fee_bps: 0,
+            provider: FlashLoanProvider::Balancer,
+            max_loans: HashMap::new(),
+        };
+        assert_eq!(required_output(input, &free, gas_cost), input + gas_cost + input / U256::from(100));
+
+        let aave = FlashLoanConfig::default();
+        assert_eq!(
+            required_output(input, &aave, gas_cost),
+            input + input * U256::from(9) / U256::from(10_000) + gas_cost + input / U256::from(100)
+        );
+    }
+
+    #[test]
+    fn plausibility_scales_with_the_input() {
+        // 1 WETH returning 1.05 WETH, the default size that an absolute 1e18 cap threw away
+        let weth = U256::from(10u64).pow(U256::from(18));
+        assert!(plausible_output(weth, weth * U256::from(105) / U256::from(100)));
+        assert!(!plausible_output(weth, weth * U256::from(MAX_PLAUSIBLE_RETURN_MULTIPLE)));
+
+        // 1000 USDC at 6 decimals is held to the same multiple
+        let usdc = U256::from(1_000_000_000u64);
+        assert!(plausible_output(usdc, usdc + U256::from(5_000_000u64)));
+        assert!(!plausible_output(usdc, usdc * U256::from(1_000)));
     }
 }
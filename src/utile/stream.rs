@@ -4,21 +4,27 @@ use alloy_provider::ProviderBuilder;
 use alloy_transport_ipc::IpcConnect; // Add impor
 use futures::StreamExt;
 use log::{debug, warn};
+use crate::utile::rgen::V2Swap;
+use crate::utile::sandwicher::PendingSwap;
+use alloy::consensus::Transaction;
+use alloy::primitives::Address;
+use alloy::providers::Provider;
+use alloy::sol_types::SolCall;
+use pool_sync::PoolType;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::broadcast::Sender;
-
-/// Subscribes to new block headers over IPC and broadcasts them via a channel.
-pub async fn stream_new_blocks(block_sender: Sender<Event>) {
-        
-        // ...
-let ipc_conn: String = ...;
-let ipc_builder = IpcConnect::new(ipc_conn.clone()); // Create builder specific to IPC
-let ipc_transport = ipc_builder.connect().await.context("Failed to connect IPC")?;
-let ipc_provider = ProviderBuilder::new().provider(ipc_transport);
-let ipc = Arc::new(ipc_provider);
-// ...
-    // 👇 Attempt to load IPC path from environment
-    let ipc_path = std::env::var("IPC").expect("IPC path not set in environment");
+use tokio::sync::broadcast::{self, Sender};
+
+/// Subscribes to new block headers over the IPC socket at `ipc_path` and broadcasts them via a channel.
+/// Stops once `shutdown` fires.
+pub async fn stream_new_blocks(block_sender: Sender<Event>, ipc_path: String, mut shutdown: broadcast::Receiver<()>) {
+    let ipc = match ProviderBuilder::new().on_ipc(IpcConnect::new(ipc_path)).await {
+        Ok(provider) => provider,
+        Err(e) => {
+            warn!("Failed to connect IPC for block stream: {:?}", e);
+            return;
+        }
+    };
 
     // 👇 Subscribe to new block headers
     let sub = match ipc.subscribe_blocks().await {
@@ -32,10 +38,95 @@ let ipc = Arc::new(ipc_provider);
     let mut stream = sub.into_stream();
 
     // 👇 Stream and broadcast each new block as an Event
-    while let Some(block) = stream.next().await {
+    loop {
+        let block = tokio::select! {
+            block = stream.next() => block,
+            _ = shutdown.recv() => {
+                debug!("Block stream shutting down");
+                return;
+            }
+        };
+        let Some(block) = block else {
+            break;
+        };
         match block_sender.send(Event::NewBlock(block)) {
             Ok(_) => debug!("New block event sent"),
             Err(e) => warn!("Failed to broadcast new block: {:?}", e),
         }
     }
 }
+
+/// Subscribes to pending transactions over IPC and broadcasts single-hop swaps through `router` on tracked pools.
+/// `pools` maps a sorted (token0, token1) pair to the UniswapV2 pool the router would trade on.
+pub async fn stream_pending_txs(
+    sender: Sender<Event>,
+    pools: Arc<HashMap<(Address, Address), Address>>,
+    router: Address,
+    ipc_path: String,
+    mut shutdown: broadcast::Receiver<()>,
+) {
+    let ipc = match ProviderBuilder::new().on_ipc(IpcConnect::new(ipc_path)).await {
+        Ok(provider) => provider,
+        Err(e) => {
+            warn!("Failed to connect IPC for mempool stream: {:?}", e);
+            return;
+        }
+    };
+
+    let sub = match ipc.subscribe_full_pending_transactions().await {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("Failed to subscribe to pending transactions: {:?}", e);
+            return;
+        }
+    };
+
+    let mut stream = sub.into_stream();
+    loop {
+        let tx = tokio::select! {
+            tx = stream.next() => tx,
+            _ = shutdown.recv() => {
+                debug!("Pending tx stream shutting down");
+                return;
+            }
+        };
+        let Some(tx) = tx else {
+            break;
+        };
+        if tx.to() != Some(router) {
+            continue;
+        }
+        let Some(swap) = decode_pending_swap(tx.input(), &pools) else {
+            continue;
+        };
+
+        match sender.send(Event::PendingSwap(swap)) {
+            Ok(_) => debug!("Pending swap event sent"),
+            Err(e) => warn!("Failed to broadcast pending swap: {:?}", e),
+        }
+    }
+}
+
+/// Decodes a single-hop `swapExactTokensForTokens`, None if it doesn't hit a tracked pool
+fn decode_pending_swap(input: &[u8], pools: &HashMap<(Address, Address), Address>) -> Option<PendingSwap> {
+    let call = V2Swap::swapExactTokensForTokensCall::abi_decode(input, true).ok()?;
+    let [token_in, token_out] = call.path[..] else {
+        return None;
+    };
+
+    let pair = if token_in < token_out {
+        (token_in, token_out)
+    } else {
+        (token_out, token_in)
+    };
+    let pool = *pools.get(&pair)?;
+
+    Some(PendingSwap {
+        pool,
+        pool_type: PoolType::UniswapV2,
+        token_in,
+        token_out,
+        amount_in: call.amountIn,
+        amount_out_min: call.amountOutMin,
+    })
+}
@@ -1,10 +1,12 @@
-use crate::utile::constant::AMOUNT;
-use crate::utile::rgen::{FlashQuoter, FlashSwap};
-use alloy::primitives::Address;
+use crate::utile::constant::{Deployment, amount_for};
+use crate::utile::rgen::{FlashQuoter, FlashSwap, FlashSwapMinOut, FlashSwapSplit};
+use alloy::primitives::{Address, U256};
+use alloy::sol_types::SolCall;
+use anyhow::{Result, bail};
 use pool_sync::PoolType;
 use serde::{Deserialize, Serialize};
 use std::convert::From;
-use std::hash::Hash;
+use std::hash::{DefaultHasher, Hash, Hasher};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Point {
@@ -21,18 +23,135 @@ pub struct SwapStep {
     #[serde(with = "pool_type_serde")]
     pub protocol: PoolType,
     pub fee: u32,
+    /// Parallel pools the step's input is divided across, empty for a single-pool step.
+    /// When set, `pool_address`/`protocol`/`fee` mirror the first leg
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub split: Vec<SplitLeg>,
+}
+
+/// One branch of a split step
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub struct SplitLeg {
+    pub pool_address: Address,
+    #[serde(with = "pool_type_serde")]
+    pub protocol: PoolType,
+    pub fee: u32,
+    /// Portion of the step's input routed through this pool, in basis points
+    pub share_bps: u16,
+}
+
+/// Full share of a step's input, in basis points
+pub const SPLIT_TOTAL_BPS: u16 = 10_000;
+
+impl SwapStep {
+    /// Step dividing its input across `legs`, None without legs or when the shares don't add up to `SPLIT_TOTAL_BPS`
+    pub fn split(token_in: Address, token_out: Address, legs: Vec<SplitLeg>) -> Option<Self> {
+        let first = legs.first()?.clone();
+        let total: u32 = legs.iter().map(|leg| leg.share_bps as u32).sum();
+        if total != SPLIT_TOTAL_BPS as u32 {
+            return None;
+        }
+        Some(Self {
+            pool_address: first.pool_address,
+            token_in,
+            token_out,
+            protocol: first.protocol,
+            fee: first.fee,
+            split: legs,
+        })
+    }
+
+    #[inline]
+    pub fn is_split(&self) -> bool {
+        !self.split.is_empty()
+    }
+
+    /// Pools the step swaps through with their shares, a single full-share leg for an unsplit step
+    pub fn legs(&self) -> Vec<SplitLeg> {
+        if self.is_split() {
+            return self.split.clone();
+        }
+        vec![SplitLeg {
+            pool_address: self.pool_address,
+            protocol: self.protocol,
+            fee: self.fee,
+            share_bps: SPLIT_TOTAL_BPS,
+        }]
+    }
+}
+
+/// Converts per-pool amounts (e.g. from `Calculator::split_route`) into basis point shares summing to
+/// `SPLIT_TOTAL_BPS`, rounding dust goes to the largest amount
+pub fn split_shares_bps(amounts: &[U256]) -> Vec<u16> {
+    let total = amounts.iter().fold(U256::ZERO, |acc, amount| acc + *amount);
+    if total.is_zero() {
+        return vec![0; amounts.len()];
+    }
+    let mut shares: Vec<u16> = amounts
+        .iter()
+        .map(|amount| (*amount * U256::from(SPLIT_TOTAL_BPS) / total).to::<u16>())
+        .collect();
+    let assigned: u16 = shares.iter().sum();
+    if let Some(largest) = (0..amounts.len()).max_by_key(|i| amounts[*i]) {
+        shares[largest] += SPLIT_TOTAL_BPS - assigned;
+    }
+    shares
+}
+
+/// Stable string names for every `PoolType` the crate handles, shared by serialization and parsing
+pub const POOL_TYPE_NAMES: &[(PoolType, &str)] = &[
+    (PoolType::UniswapV2, "UniswapV2"),
+    (PoolType::SushiSwapV2, "SushiSwapV2"),
+    (PoolType::PancakeSwapV2, "PancakeSwapV2"),
+    (PoolType::BaseSwapV2, "BaseSwapV2"),
+    (PoolType::SwapBasedV2, "SwapBasedV2"),
+    (PoolType::AlienBaseV2, "AlienBaseV2"),
+    (PoolType::DackieSwapV2, "DackieSwapV2"),
+    (PoolType::UniswapV3, "UniswapV3"),
+    (PoolType::SushiSwapV3, "SushiSwapV3"),
+    (PoolType::PancakeSwapV3, "PancakeSwapV3"),
+    (PoolType::BaseSwapV3, "BaseSwapV3"),
+    (PoolType::SwapBasedV3, "SwapBasedV3"),
+    (PoolType::AlienBaseV3, "AlienBaseV3"),
+    (PoolType::DackieSwapV3, "DackieSwapV3"),
+    (PoolType::Slipstream, "Slipstream"),
+    (PoolType::Aerodrome, "Aerodrome"),
+    (PoolType::MaverickV1, "MaverickV1"),
+    (PoolType::MaverickV2, "MaverickV2"),
+    (PoolType::CurveTwoCrypto, "CurveTwoCrypto"),
+    (PoolType::CurveTriCrypto, "CurveTriCrypto"),
+    (PoolType::BalancerV2, "BalancerV2"),
+];
+
+/// Stable name for a `PoolType`
+pub fn pool_type_name(pool_type: PoolType) -> Option<&'static str> {
+    POOL_TYPE_NAMES
+        .iter()
+        .find(|(pt, _)| *pt == pool_type)
+        .map(|(_, name)| *name)
+}
+
+/// Parses a name produced by `pool_type_name`
+pub fn pool_type_from_name(name: &str) -> Option<PoolType> {
+    POOL_TYPE_NAMES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(pt, _)| *pt)
 }
 
 // Custom serde module for PoolType
 mod pool_type_serde {
-    use crate::utile::PoolType;
+    use super::{pool_type_from_name, pool_type_name};
+    use pool_sync::PoolType;
     use serde::{Deserialize, Deserializer, Serializer};
 
     pub fn serialize<S>(pt: &PoolType, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(&format!("{:?}", pt))
+        let name = pool_type_name(*pt)
+            .ok_or_else(|| serde::ser::Error::custom(format!("Unsupported PoolType: {:?}", pt)))?;
+        serializer.serialize_str(name)
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<PoolType, D::Error>
@@ -40,16 +159,29 @@ mod pool_type_serde {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        match s.as_str() {
-            "UniswapV2" => Ok(PoolType::UniswapV2),
-            "SushiSwapV2" => Ok(PoolType::SushiSwapV2),
-            "PancakeSwapV2" => Ok(PoolType::PancakeSwapV2),
-            "UniswapV3" => Ok(PoolType::UniswapV3),
-            "SushiSwapV3" => Ok(PoolType::SushiSwapV3),
-            "Aerodrome" => Ok(PoolType::Aerodrome),
-            "Slipstream" => Ok(PoolType::Slipstream),
-            _ => Err(serde::de::Error::custom(format!("Unknown PoolType: {}", s))),
-        }
+        pool_type_from_name(&s)
+            .ok_or_else(|| serde::de::Error::custom(format!("Unknown PoolType: {}", s)))
+    }
+}
+
+// Pool version codes understood by the FlashQuoter / FlashSwap contracts.
+// These must stay in sync with the dispatch in the Solidity `_swap` implementation.
+pub const POOL_VERSION_V2: u8 = 0;
+pub const POOL_VERSION_V3: u8 = 1;
+pub const POOL_VERSION_AERODROME: u8 = 2;
+pub const POOL_VERSION_CURVE: u8 = 3;
+pub const POOL_VERSION_MAVERICK: u8 = 4;
+pub const POOL_VERSION_BALANCER: u8 = 5;
+
+/// Maps a protocol to the `poolVersions` code the on-chain contracts dispatch on.
+pub fn pool_version_code(pool_type: PoolType) -> u8 {
+    match pool_type {
+        PoolType::Aerodrome => POOL_VERSION_AERODROME,
+        PoolType::CurveTwoCrypto | PoolType::CurveTriCrypto => POOL_VERSION_CURVE,
+        PoolType::MaverickV1 | PoolType::MaverickV2 => POOL_VERSION_MAVERICK,
+        PoolType::BalancerV2 => POOL_VERSION_BALANCER,
+        pt if pt.is_v3() => POOL_VERSION_V3,
+        _ => POOL_VERSION_V2,
     }
 }
 
@@ -60,6 +192,20 @@ pub struct SwapPath {
     pub hash: u64,
 }
 
+impl SwapPath {
+    /// Builds a path, hashing its steps
+    pub fn new(steps: Vec<SwapStep>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        for step in &steps {
+            step.hash(&mut hasher);
+        }
+        Self {
+            steps,
+            hash: hasher.finish(),
+        }
+    }
+}
+
 /// This conversion is useful after estimating quotes from a flash quoter and preparing a swap call.
 impl From<FlashQuoter::SwapParams> for FlashSwap::SwapParams {
     fn from(params: FlashQuoter::SwapParams) -> Self {
@@ -71,21 +217,222 @@ impl From<FlashQuoter::SwapParams> for FlashSwap::SwapParams {
     }
 }
 
-/// Converts a [`SwapPath`] into a [`FlashQuoter::SwapParams`] for quote estimation.
-impl From<SwapPath> for FlashQuoter::SwapParams {
+/// `executeArbitrage` calldata for linear `params`. When `deployment` declares `amountOutMin` support the
+/// executor reverts below `amount_out_min`, otherwise the floor can't be encoded and the output is unbounded on-chain
+pub fn arbitrage_calldata(params: FlashQuoter::SwapParams, amount_out_min: U256, deployment: &Deployment) -> Vec<u8> {
+    if deployment.min_out_arbitrage {
+        let arb = FlashSwapMinOut::SwapParams {
+            pools: params.pools,
+            poolVersions: params.poolVersions,
+            amountIn: params.amountIn,
+            amountOutMin: amount_out_min,
+        };
+        return FlashSwapMinOut::executeArbitrageCall { arb }.abi_encode();
+    }
+    FlashSwap::executeArbitrageCall { arb: params.into() }.abi_encode()
+}
+
+/// Converts a [`SwapPath`] into [`FlashSwapSplit::SplitSwapParams`], unsplit steps become single full-share hops
+impl From<SwapPath> for FlashSwapSplit::SplitSwapParams {
     fn from(path: SwapPath) -> Self {
+        let amount_in = path
+            .steps
+            .first()
+            .map(|step| amount_for(step.token_in))
+            .unwrap_or_default();
+        let hops = path
+            .steps
+            .iter()
+            .map(|step| {
+                let legs = step.legs();
+                FlashSwapSplit::SplitHop {
+                    pools: legs.iter().map(|leg| leg.pool_address).collect(),
+                    poolVersions: legs.iter().map(|leg| pool_version_code(leg.protocol)).collect(),
+                    sharesBps: legs.iter().map(|leg| leg.share_bps).collect(),
+                }
+            })
+            .collect();
+
+        FlashSwapSplit::SplitSwapParams {
+            hops,
+            amountIn: amount_in,
+        }
+    }
+}
+
+impl SwapPath {
+    /// Whether any step divides its input across parallel pools
+    pub fn is_split(&self) -> bool {
+        self.steps.iter().any(SwapStep::is_split)
+    }
+
+    /// FlashSwap calldata executing this path from `amount_in`: `executeArbitrage` for a linear path,
+    /// `executeSplitArbitrage` once any step is split. Split paths are refused unless `deployment`
+    /// declares its FlashSwap implements `executeSplitArbitrage`, and carry no `amount_out_min`
+    pub fn execution_calldata(&self, amount_in: U256, amount_out_min: U256, deployment: &Deployment) -> Result<Vec<u8>> {
+        if self.is_split() {
+            if !deployment.split_arbitrage {
+                bail!("Path {} is split but FlashSwap {} has no executeSplitArbitrage", self.hash, deployment.flash_swap);
+            }
+            let mut arb: FlashSwapSplit::SplitSwapParams = self.clone().into();
+            arb.amountIn = amount_in;
+            return Ok(FlashSwapSplit::executeSplitArbitrageCall { arb }.abi_encode());
+        }
+        let mut params: FlashQuoter::SwapParams = self.clone().try_into()?;
+        params.amountIn = amount_in;
+        Ok(arbitrage_calldata(params, amount_out_min, deployment))
+    }
+}
+
+/// Converts a [`SwapPath`] into a [`FlashQuoter::SwapParams`] for quote estimation.
+/// The quoter only walks linear paths, a path with a split step is rejected rather than quoted as something else
+impl TryFrom<SwapPath> for FlashQuoter::SwapParams {
+    type Error = anyhow::Error;
+
+    fn try_from(path: SwapPath) -> Result<Self> {
+        if path.is_split() {
+            bail!("Path {} has split steps, the FlashQuoter only quotes linear paths", path.hash);
+        }
+        let amount_in = path
+            .steps
+            .first()
+            .map(|step| amount_for(step.token_in))
+            .unwrap_or_default();
         let mut pools: Vec<Address> = Vec::with_capacity(path.steps.len());
         let mut protocols: Vec<u8> = Vec::with_capacity(path.steps.len());
 
         for step in path.steps {
             pools.push(step.pool_address);
-            protocols.push(if step.protocol.is_v3() { 1 } else { 0 });
+            protocols.push(pool_version_code(step.protocol));
         }
 
-        FlashQuoter::SwapParams {
+        Ok(FlashQuoter::SwapParams {
             pools,
             poolVersions: protocols,
-            amountIn: AMOUNT,
+            amountIn: amount_in,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(protocol: PoolType) -> SwapStep {
+        SwapStep {
+            pool_address: Address::repeat_byte(0x11),
+            token_in: Address::repeat_byte(0x22),
+            token_out: Address::repeat_byte(0x33),
+            protocol,
+            fee: 3000,
+            split: Vec::new(),
         }
     }
+
+    #[test]
+    fn pool_type_serde_round_trips_every_variant() {
+        for (pool_type, name) in POOL_TYPE_NAMES {
+            let json = serde_json::to_string(&step(*pool_type)).unwrap();
+            assert!(json.contains(&format!("\"protocol\":\"{}\"", name)));
+
+            let decoded: SwapStep = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded.protocol, *pool_type);
+            assert_eq!(decoded, step(*pool_type));
+        }
+    }
+
+    #[test]
+    fn pool_type_names_are_unique() {
+        for (i, (pool_type, name)) in POOL_TYPE_NAMES.iter().enumerate() {
+            for (other_type, other_name) in &POOL_TYPE_NAMES[i + 1..] {
+                assert_ne!(pool_type, other_type);
+                assert_ne!(name, other_name);
+            }
+        }
+    }
+
+    fn split_path() -> SwapPath {
+        let legs = vec![
+            SplitLeg {
+                pool_address: Address::repeat_byte(0x44),
+                protocol: PoolType::UniswapV2,
+                fee: 3000,
+                share_bps: 7_000,
+            },
+            SplitLeg {
+                pool_address: Address::repeat_byte(0x55),
+                protocol: PoolType::UniswapV3,
+                fee: 500,
+                share_bps: 3_000,
+            },
+        ];
+        let back = SwapStep {
+            token_in: Address::repeat_byte(0x33),
+            token_out: Address::repeat_byte(0x22),
+            ..step(PoolType::Aerodrome)
+        };
+        let split = SwapStep::split(Address::repeat_byte(0x22), Address::repeat_byte(0x33), legs).unwrap();
+        SwapPath::new(vec![split, back])
+    }
+
+    #[test]
+    fn split_paths_encode_for_execute_split_arbitrage() {
+        let path = split_path();
+        let deployment = Deployment {
+            split_arbitrage: true,
+            ..Deployment::simulation_only(pool_sync::Chain::Base)
+        };
+        let calldata = path.execution_calldata(U256::from(1_000u64), U256::ZERO, &deployment).unwrap();
+        let call = FlashSwapSplit::executeSplitArbitrageCall::abi_decode(&calldata).unwrap();
+
+        assert_eq!(call.arb.amountIn, U256::from(1_000u64));
+        assert_eq!(call.arb.hops.len(), 2);
+        let split = &call.arb.hops[0];
+        assert_eq!(split.pools, vec![Address::repeat_byte(0x44), Address::repeat_byte(0x55)]);
+        assert_eq!(split.poolVersions, vec![POOL_VERSION_V2, POOL_VERSION_V3]);
+        assert_eq!(split.sharesBps, vec![7_000, 3_000]);
+        let single = &call.arb.hops[1];
+        assert_eq!(single.pools, vec![Address::repeat_byte(0x11)]);
+        assert_eq!(single.sharesBps, vec![SPLIT_TOTAL_BPS]);
+    }
+
+    #[test]
+    fn split_paths_need_the_split_entry_point_and_skip_the_quoter() {
+        let path = split_path();
+        let deployment = Deployment::simulation_only(pool_sync::Chain::Base);
+        assert!(path.execution_calldata(U256::from(1_000u64), U256::ZERO, &deployment).is_err());
+        assert!(FlashQuoter::SwapParams::try_from(path).is_err());
+
+        let linear = SwapPath::new(vec![step(PoolType::UniswapV2)]);
+        assert!(linear.execution_calldata(U256::from(1_000u64), U256::ZERO, &deployment).is_ok());
+    }
+
+    #[test]
+    fn min_out_is_encoded_only_when_the_executor_supports_it() {
+        let path = SwapPath::new(vec![step(PoolType::UniswapV2)]);
+        let (amount_in, amount_out_min) = (U256::from(1_000u64), U256::from(1_010u64));
+
+        let legacy = Deployment::simulation_only(pool_sync::Chain::Base);
+        let calldata = path.execution_calldata(amount_in, amount_out_min, &legacy).unwrap();
+        assert_eq!(FlashSwap::executeArbitrageCall::abi_decode(&calldata).unwrap().arb.amountIn, amount_in);
+        assert!(FlashSwapMinOut::executeArbitrageCall::abi_decode(&calldata).is_err());
+
+        let guarded = Deployment {
+            min_out_arbitrage: true,
+            ..legacy
+        };
+        let calldata = path.execution_calldata(amount_in, amount_out_min, &guarded).unwrap();
+        let call = FlashSwapMinOut::executeArbitrageCall::abi_decode(&calldata).unwrap();
+        assert_eq!(call.arb.amountIn, amount_in);
+        assert_eq!(call.arb.amountOutMin, amount_out_min);
+        assert_eq!(call.arb.pools, vec![Address::repeat_byte(0x11)]);
+    }
+
+    #[test]
+    fn unknown_pool_type_name_is_rejected() {
+        let json = serde_json::to_string(&step(PoolType::UniswapV2))
+            .unwrap()
+            .replace("UniswapV2", "NotAPool");
+        assert!(serde_json::from_str::<SwapStep>(&json).is_err());
+    }
 }
@@ -0,0 +1,113 @@
+//! Helpers for tests. Offline helpers build state by hand against an unreachable node,
+//! tests needing a live node are `#[ignore]`d and run with `--ignored`
+
+use crate::state_db::{BlockStateDB, BlockStateDBAccount};
+use crate::utile::constant::Deployment;
+use crate::utile::market_state::{MarketState, SimConfig};
+use crate::utile::watchdog::Heartbeat;
+use alloy::network::Ethereum;
+use alloy::primitives::{Address, U128, U256};
+use alloy::providers::RootProvider;
+use pool_sync::{Chain, Pool, UniswapV2Pool, UniswapV3Pool};
+use revm::primitives::BlockEnv;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Provider for the node at FULL, the same variable the bot reads its RPC from
+pub fn full_node_provider() -> RootProvider {
+    let url = std::env::var("FULL").expect("FULL must point at a Base node to run network tests");
+    RootProvider::new_http(url.parse().expect("Invalid FULL url"))
+}
+
+/// Db whose provider points nowhere, any state a test reads has to be inserted first.
+/// Needs a multi-threaded runtime like every `BlockStateDB`
+pub fn offline_db() -> BlockStateDB<Ethereum, RootProvider> {
+    let provider = RootProvider::new_http("http://127.0.0.1:1".parse().unwrap());
+    BlockStateDB::new(provider, Duration::from_millis(100)).expect("offline_db needs a multi-threaded runtime")
+}
+
+/// Inserts a V2-style pool into an offline db, its account is created locally instead of fetched
+pub fn insert_v2_offline(db: &mut BlockStateDB<Ethereum, RootProvider>, pool: Pool) {
+    db.accounts.insert(pool.address(), BlockStateDBAccount::default());
+    db.insert_v2(pool);
+}
+
+/// Uniswap V2 pool with the given reserves
+pub fn v2_pool(address: Address, token0: Address, token1: Address, reserve0: u128, reserve1: u128) -> Pool {
+    Pool::UniswapV2(UniswapV2Pool {
+        address,
+        token0,
+        token1,
+        token0_decimals: 18,
+        token1_decimals: 18,
+        token0_reserves: U128::from(reserve0),
+        token1_reserves: U128::from(reserve1),
+        ..Default::default()
+    })
+}
+
+/// Uniswap V3 pool at `sqrt_price`/`tick` with in-range `liquidity` and no initialized ticks
+pub fn v3_pool(address: Address, token0: Address, token1: Address, sqrt_price: U256, tick: i32, liquidity: u128) -> Pool {
+    Pool::UniswapV3(UniswapV3Pool {
+        address,
+        token0,
+        token1,
+        token0_decimals: 18,
+        token1_decimals: 18,
+        sqrt_price,
+        tick,
+        liquidity,
+        fee: 3000,
+        tick_spacing: 60,
+        ..Default::default()
+    })
+}
+
+/// Inserts a V3 pool into an offline db, its account is created locally instead of fetched
+pub fn insert_v3_offline(db: &mut BlockStateDB<Ethereum, RootProvider>, pool: Pool) {
+    db.accounts.insert(pool.address(), BlockStateDBAccount::default());
+    db.insert_v3(pool).expect("V3 pool insertion");
+}
+
+/// Aerodrome pool with the given reserves, 18 decimals on both sides
+pub fn aerodrome_pool(address: Address, token0: Address, token1: Address, reserve0: u128, reserve1: u128, stable: bool) -> Pool {
+    Pool::Aerodrome(UniswapV2Pool {
+        address,
+        token0,
+        token1,
+        token0_decimals: 18,
+        token1_decimals: 18,
+        token0_reserves: U128::from(reserve0),
+        token1_reserves: U128::from(reserve1),
+        stable: Some(stable),
+        ..Default::default()
+    })
+}
+
+/// Inserts an Aerodrome pool into an offline db with `fee_bps`, the factory lookup failing offline
+pub fn insert_aerodrome_offline(db: &mut BlockStateDB<Ethereum, RootProvider>, pool: Pool, fee_bps: u32) {
+    let address = pool.address();
+    insert_v2_offline(db, pool);
+    db.aerodrome_fees.insert(address, fee_bps);
+}
+
+/// Market state over `db` with no updater behind it
+pub fn market_state(db: BlockStateDB<Ethereum, RootProvider>) -> Arc<MarketState<Ethereum, RootProvider>> {
+    market_state_with(db, SimConfig::default())
+}
+
+/// `market_state` simulating under `sim_config`
+pub fn market_state_with(
+    db: BlockStateDB<Ethereum, RootProvider>,
+    sim_config: SimConfig,
+) -> Arc<MarketState<Ethereum, RootProvider>> {
+    Arc::new(MarketState {
+        db: RwLock::new(db),
+        block_env: RwLock::new(BlockEnv::default()),
+        sim_config,
+        deployment: Deployment::simulation_only(Chain::Base),
+        heartbeat: Arc::new(Heartbeat::new()),
+        skipped_blocks: Mutex::new(BTreeSet::new()),
+    })
+}
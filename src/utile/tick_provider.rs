@@ -0,0 +1,151 @@
+use crate::state_db::BlockStateDB;
+use alloy::network::Network;
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use anyhow::{Result, anyhow};
+use dashmap::DashMap;
+use fxhash::FxBuildHasher;
+use uniswap_v3_math::bit_math;
+
+/// Memoized tick bitmaps and liquidity_net values, keyed by pool.
+/// Entries are only valid for the block they were read in, so touched pools must be invalidated.
+#[derive(Default)]
+pub struct TickCache {
+    bitmaps: DashMap<(Address, i16), U256, FxBuildHasher>,
+    liquidity_net: DashMap<(Address, i32), i128, FxBuildHasher>,
+}
+
+impl TickCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop all cached tick data for a pool
+    #[inline]
+    pub fn invalidate(&self, pool_address: Address) {
+        self.bitmaps.retain(|(pool, _), _| *pool != pool_address);
+        self.liquidity_net.retain(|(pool, _), _| *pool != pool_address);
+    }
+
+    /// Clears all entries in the cache
+    #[inline]
+    pub fn clear(&self) {
+        self.bitmaps.clear();
+        self.liquidity_net.clear();
+    }
+}
+
+/// Reads V3 tick data for a single pool straight from the state DB
+pub struct DbTickDataProvider<'a, N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    db: &'a BlockStateDB<N, P>,
+    pool: Address,
+    tick_spacing: i32,
+    cache: Option<&'a TickCache>,
+}
+
+impl<'a, N, P> DbTickDataProvider<'a, N, P>
+where
+    N: Network,
+    P: Provider<N>,
+{
+    pub fn new(db: &'a BlockStateDB<N, P>, pool: Address, tick_spacing: i32) -> Self {
+        Self {
+            db,
+            pool,
+            tick_spacing,
+            cache: None,
+        }
+    }
+
+    /// Serve repeated bitmap / liquidity_net reads from `cache`
+    pub fn with_cache(mut self, cache: &'a TickCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Returns the bitmap word at `word_pos`
+    pub fn tick_bitmap(&self, word_pos: i16) -> Result<U256> {
+        let Some(cache) = self.cache else {
+            return self.read_tick_bitmap(word_pos);
+        };
+
+        if let Some(bitmap) = cache.bitmaps.get(&(self.pool, word_pos)) {
+            return Ok(*bitmap);
+        }
+
+        let bitmap = self.read_tick_bitmap(word_pos)?;
+        cache.bitmaps.insert((self.pool, word_pos), bitmap);
+        Ok(bitmap)
+    }
+
+    /// Returns the liquidity_net stored for an initialized tick
+    pub fn get_liquidity_net(&self, tick: i32) -> Result<i128> {
+        let Some(cache) = self.cache else {
+            return self.read_liquidity_net(tick);
+        };
+
+        if let Some(liquidity_net) = cache.liquidity_net.get(&(self.pool, tick)) {
+            return Ok(*liquidity_net);
+        }
+
+        let liquidity_net = self.read_liquidity_net(tick)?;
+        cache.liquidity_net.insert((self.pool, tick), liquidity_net);
+        Ok(liquidity_net)
+    }
+
+    /// Port of `TickBitmap.nextInitializedTickWithinOneWord`
+    pub fn next_initialized_tick_within_one_word(&self, tick: i32, lte: bool) -> Result<(i32, bool)> {
+        let mut compressed = tick / self.tick_spacing;
+        if tick < 0 && tick % self.tick_spacing != 0 {
+            compressed -= 1;
+        }
+
+        if lte {
+            let (word_pos, bit_pos) = position(compressed);
+            let mask = (U256::from(1) << bit_pos) - U256::from(1) + (U256::from(1) << bit_pos);
+            let masked = self.tick_bitmap(word_pos)? & mask;
+
+            let initialized = !masked.is_zero();
+            let next = if initialized {
+                let msb = bit_math::most_significant_bit(masked)
+                    .map_err(|e| anyhow!("most_significant_bit failed: {e:?}"))?;
+                (compressed - (bit_pos as i32 - msb as i32)) * self.tick_spacing
+            } else {
+                (compressed - bit_pos as i32) * self.tick_spacing
+            };
+            Ok((next, initialized))
+        } else {
+            let (word_pos, bit_pos) = position(compressed + 1);
+            let mask = !((U256::from(1) << bit_pos) - U256::from(1));
+            let masked = self.tick_bitmap(word_pos)? & mask;
+
+            let initialized = !masked.is_zero();
+            let next = if initialized {
+                let lsb = bit_math::least_significant_bit(masked)
+                    .map_err(|e| anyhow!("least_significant_bit failed: {e:?}"))?;
+                (compressed + 1 + (lsb as i32 - bit_pos as i32)) * self.tick_spacing
+            } else {
+                (compressed + 1 + (u8::MAX - bit_pos) as i32) * self.tick_spacing
+            };
+            Ok((next, initialized))
+        }
+    }
+
+    fn read_tick_bitmap(&self, word_pos: i16) -> Result<U256> {
+        self.db.get_tick_bitmap_word(self.pool, word_pos)
+    }
+
+    fn read_liquidity_net(&self, tick: i32) -> Result<i128> {
+        self.db.get_liquidity_net(self.pool, tick)
+    }
+}
+
+// Computes the position in the mapping where the initialized bit for a tick lives
+#[inline]
+fn position(tick: i32) -> (i16, u8) {
+    ((tick >> 8) as i16, (tick & 0xff) as u8)
+}
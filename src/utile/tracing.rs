@@ -1,8 +1,11 @@
 use alloy::sol_types::sol;
-use alloy::primitives::Address;
-use alloy::rpc::types::trace::geth::GethDebugTracingOptions;
+use alloy::primitives::{Address, B256};
+use alloy::rpc::types::trace::geth::{
+    CallConfig, CallFrame, GethDebugTracingOptions, GethTrace, PreStateConfig, PreStateFrame,
+};
 use alloy::rpc::types::trace::common::TraceResult;
 use alloy::eips::BlockNumberOrTag;
+use anyhow::{Context, Result, anyhow};
 use std::collections::BTreeMap;
 use std::sync::Arc;
 
@@ -23,7 +26,7 @@ pub async fn debug_trace_block<N>(
     client: Arc<impl DebugApi<N> + Send + Sync>,
     block_tag: BlockNumberOrTag,
     diff_mode: bool,
-) -> Vec<BTreeMap<Address, AccountState>>
+) -> Result<Vec<BTreeMap<Address, AccountState>>>
 where
     N: Network,
 {
@@ -34,7 +37,7 @@ where
     let results = client
         .debug_trace_block(block_tag, tracer, PruneStageConfig::default())
         .await
-        .expect("Failed to trace block");
+        .with_context(|| format!("Failed to trace block {}", block_tag))?;
 
     // Process results to extract post-trace changes
     let mut post: Vec<BTreeMap<Address, AccountState>> = Vec::new();
@@ -43,5 +46,75 @@ where
         post.push(trace_result);
     }
 
-    post
+    Ok(post)
+}
+
+/// Runs the `prestateTracer` over a block, returning per transaction the accounts and slots it touched
+/// with the values they held before it ran. Transactions that failed to trace are skipped
+pub async fn debug_trace_block_prestate<N>(
+    client: Arc<impl DebugApi<N> + Send + Sync>,
+    block_tag: BlockNumberOrTag,
+) -> Result<Vec<BTreeMap<Address, alloy::rpc::types::trace::geth::AccountState>>>
+where
+    N: Network,
+{
+    let options = GethDebugTracingOptions::prestate_tracer(PreStateConfig {
+        diff_mode: Some(false),
+        ..Default::default()
+    });
+
+    let results = client
+        .debug_trace_block_by_number(block_tag, options)
+        .await
+        .with_context(|| format!("Failed to prestate-trace block {}", block_tag))?;
+
+    let mut pre = Vec::with_capacity(results.len());
+    for result in results {
+        match result {
+            TraceResult::Success { result: GethTrace::PreStateTracer(PreStateFrame::Default(mode)), .. } => {
+                pre.push(mode.0)
+            }
+            TraceResult::Success { .. } => warn!("Unexpected trace type in prestate trace of block {}", block_tag),
+            TraceResult::Error { error, .. } => warn!("Prestate trace failed in block {}: {}", block_tag, error),
+        }
+    }
+    Ok(pre)
+}
+
+/// Runs the `callTracer` on a single transaction and returns its call tree,
+/// including gas used and revert reason for every nested call.
+pub async fn debug_trace_tx_calls<N>(
+    client: Arc<impl DebugApi<N> + Send + Sync>,
+    tx_hash: B256,
+    only_top_call: bool,
+) -> Result<CallFrame>
+where
+    N: Network,
+{
+    let options = GethDebugTracingOptions::call_tracer(CallConfig {
+        only_top_call: Some(only_top_call),
+        with_log: Some(false),
+    });
+
+    let trace = client
+        .debug_trace_transaction(tx_hash, options)
+        .await
+        .with_context(|| format!("Failed to call-trace tx {}", tx_hash))?;
+
+    trace
+        .try_into_call_frame()
+        .map_err(|e| anyhow!("Unexpected trace type for tx {}: {:?}", tx_hash, e))
+}
+
+/// Returns the deepest call in the tree that reverted, i.e. the hop that actually failed
+pub fn deepest_revert(frame: &CallFrame) -> Option<&CallFrame> {
+    if frame.error.is_none() && frame.revert_reason.is_none() {
+        return None;
+    }
+
+    frame
+        .calls
+        .iter()
+        .find_map(deepest_revert)
+        .or(Some(frame))
 }
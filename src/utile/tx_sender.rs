@@ -1,6 +1,12 @@
+use crate::utile::constant::{Deployment, FlashLoanConfig};
 use crate::utile::events::Event;
-use crate::utile::gas_station::GasStation;
-use crate::utile::rgen::FlashSwap;
+use crate::utile::gas_station::{ChainGasModel, GasStation, PathGasLimits};
+use crate::utile::rgen::FlashQuoter::SwapParams;
+use crate::utile::rgen::ERC20Token;
+use crate::utile::ledger::Ledger;
+use crate::utile::swap::arbitrage_calldata;
+use alloy::eips::BlockId;
+use alloy::primitives::I256;
 use alloy::hex;
 use alloy::network::{Ethereum, Network, TransactionBuilder};
 use alloy::primitives::{Address, B256, Bytes, U256};
@@ -13,14 +19,27 @@ use alloy::transports::Transport;
 use reqwest::{Client, Url};
 use serde::{Serialize, Deserialize};
 use serde_json::{json, Value};
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::Receiver;
-use tracing::{info, error};
-use anyhow::{Context, Result};
+use tracing::{info, error, warn};
+use anyhow::{Context, Result, anyhow};
+use futures::stream::{FuturesUnordered, StreamExt};
 use std::convert::TryInto;
 
+// Tolerated drop of the final output between simulation and inclusion, override with SLIPPAGE_BPS
+const DEFAULT_SLIPPAGE_BPS: u32 = 50;
+
+// Gas limit for transactions sent without a path estimate
+const DEFAULT_GAS_LIMIT: u64 = 500_000;
+
+// Receipt polling for submitted transactions, roughly 30 Base blocks
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const RECEIPT_POLL_ATTEMPTS: u32 = 60;
+
 
 pub struct TxSender<T> // Transport generic
 where
@@ -31,6 +50,8 @@ where
     wallet: LocalWallet,
     contract_address: Address,
     chain_id: u64,
+    http_client: Client,
+    relays: Vec<Url>,
 }
 
 
@@ -43,6 +64,7 @@ where
         http_url: String,
         pk_hex: String,
         contract_address: Address,
+        relays: Vec<Url>,
     ) -> Result<Self> {
         // Setup Provider
         let url = Url::parse(&http_url).context("Invalid HTTP URL")?;
@@ -52,7 +74,7 @@ where
             .build()
             .context("Failed to create HTTP client")?;
             
-        let http = Http::new_with_client(url, client);
+        let http = Http::new_with_client(url, client.clone());
         let provider = ProviderBuilder::new()
             .provider(http);
             
@@ -72,9 +94,15 @@ where
             wallet,
             contract_address,
             chain_id,
+            http_client: client,
+            relays,
         })
     }
     
+    pub fn provider(&self) -> Arc<RootProvider<T>> {
+        Arc::clone(&self.provider)
+    }
+
     // Gets current nonce for the wallet address
     pub async fn get_nonce(&self) -> Result<u64> {
         self.provider
@@ -91,8 +119,14 @@ where
     T: Transport + Clone + Send + Sync + 'static,
     <T as Transport>::Error: Send + Sync + 'static,
 {
-    // Builds and signs a transaction
-    pub async fn build_and_sign_tx(&self, calldata: Vec<u8>) -> Result<(TransactionRequest, Signature)> {
+    // Builds and signs an EIP-1559 transaction paying `max_fee` / `priority_fee` wei per gas
+    pub async fn build_and_sign_tx(
+        &self,
+        calldata: Vec<u8>,
+        gas_limit: u64,
+        max_fee: u128,
+        priority_fee: u128,
+    ) -> Result<(TransactionRequest, Signature)> {
         let nonce = self.provider
             .get_transaction_count(self.wallet.address())
             .await
@@ -103,9 +137,9 @@ where
             .with_to(self.contract_address)
             .with_nonce(nonce)
             .with_chain_id(self.chain_id)
-            .with_gas_limit(500_000)
-            .with_max_fee_per_gas(U256::from(20_000_000_000u128)) // 20 gwei
-            .with_max_priority_fee_per_gas(U256::from(1_000_000_000u128)) // 1 gwei
+            .with_gas_limit(gas_limit)
+            .with_max_fee_per_gas(max_fee)
+            .with_max_priority_fee_per_gas(priority_fee)
             .with_input(Bytes::from(calldata));
 
         // Calculate transaction hash and sign it
@@ -147,10 +181,12 @@ where
             .context("Failed to send raw transaction")
     }
 
-    // Main method to send a transaction
-    pub async fn send_tx(&self, calldata: Vec<u8>) -> Result<B256> {
+    // Main method to send a transaction, fees come from the caller's gas station
+    pub async fn send_tx(&self, calldata: Vec<u8>, max_fee: u128, priority_fee: u128) -> Result<B256> {
         // Build and sign the transaction
-        let (tx, signature) = self.build_and_sign_tx(calldata).await?;
+        let (tx, signature) = self
+            .build_and_sign_tx(calldata, DEFAULT_GAS_LIMIT, max_fee, priority_fee)
+            .await?;
         
         // Get RLP encoded bytes
         let rlp_bytes = self.get_signed_rlp(&tx, &signature)?;
@@ -179,3 +215,330 @@ where
         Ok(receipt)
     }
 }
+
+impl<T> TxSender<T>
+where
+    T: Transport + Clone + Send + Sync + 'static,
+    <T as Transport>::Error: Send + Sync + 'static,
+{
+    /// Sends the same signed transaction to every relay concurrently.
+    /// Returns the tx hash as soon as one endpoint accepts it, the remaining responses are logged in the background.
+    pub async fn submit_to_all(&self, rlp_hex: String, block_number: u64) -> Result<B256> {
+        let mut pending: FuturesUnordered<_> = self
+            .relays
+            .iter()
+            .cloned()
+            .map(|relay| {
+                let client = self.http_client.clone();
+                let rlp_hex = rlp_hex.clone();
+                async move {
+                    let res = send_raw_to_relay(&client, &relay, &rlp_hex).await;
+                    (relay, res)
+                }
+            })
+            .collect();
+
+        let mut last_err = anyhow!("No relays configured");
+        while let Some((relay, res)) = pending.next().await {
+            match res {
+                Ok(tx_hash) => {
+                    info!("Relay {} accepted tx {} for block {}", relay, tx_hash, block_number);
+
+                    // Drain the rest so every endpoint's response still gets logged
+                    tokio::spawn(async move {
+                        while let Some((relay, res)) = pending.next().await {
+                            match res {
+                                Ok(hash) if hash == tx_hash => info!("Relay {} accepted tx {}", relay, hash),
+                                Ok(hash) => warn!("Relay {} returned unexpected hash {} (expected {})", relay, hash, tx_hash),
+                                Err(e) => warn!("Relay {} rejected tx {}: {:?}", relay, tx_hash, e),
+                            }
+                        }
+                    });
+
+                    return Ok(tx_hash);
+                }
+                Err(e) => {
+                    warn!("Relay {} rejected tx for block {}: {:?}", relay, block_number, e);
+                    last_err = e;
+                }
+            }
+        }
+
+        error!("All {} relays rejected tx for block {}", self.relays.len(), block_number);
+        Err(last_err)
+    }
+}
+
+/// Drains validated paths and submits them through `TxSender`.
+/// With `dry_run` set, every transaction is still built and signed but never broadcast.
+pub struct TransactionSender {
+    sender: TxSender<Http<Client>>,
+    gas_station: Arc<GasStation>,
+    dry_run: bool,
+    // Running tally of what dry-run submissions would have made
+    paper_profit: U256,
+    paper_trades: u64,
+    slippage_bps: u32,
+    gas_limits: PathGasLimits,
+    gas_model: Arc<ChainGasModel>,
+    ledger: Arc<Ledger>,
+    flash_loan: FlashLoanConfig,
+    deployment: Deployment,
+}
+
+impl TransactionSender {
+    /// Sends through the deployment's FlashSwap on the chain behind `http_url`, PRIVATE_KEY is shared by every chain
+    pub async fn new(
+        gas_station: Arc<GasStation>,
+        dry_run: bool,
+        http_url: String,
+        deployment: &Deployment,
+        ledger_path: impl AsRef<Path>,
+        flash_loan: FlashLoanConfig,
+        gas_model: Arc<ChainGasModel>,
+    ) -> Result<Self> {
+        let pk_hex = std::env::var("PRIVATE_KEY").context("PRIVATE_KEY env var not set")?;
+
+        flash_loan.ensure_executable(dry_run)?;
+
+        let slippage_bps: u32 = std::env::var("SLIPPAGE_BPS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SLIPPAGE_BPS);
+
+        let sender = TxSender::new(http_url, pk_hex, deployment.flash_swap, deployment.relays.clone()).await?;
+        if dry_run {
+            warn!("DRY_RUN enabled, transactions will be signed and logged but never sent");
+        }
+        if !deployment.min_out_arbitrage {
+            warn!(
+                "FlashSwap {} takes no amountOutMin, trades aren't protected on-chain against price moves before inclusion",
+                deployment.flash_swap
+            );
+        }
+
+        Ok(Self {
+            sender,
+            gas_station,
+            dry_run,
+            paper_profit: U256::ZERO,
+            paper_trades: 0,
+            slippage_bps,
+            gas_limits: PathGasLimits::from_env(),
+            gas_model,
+            ledger: Arc::new(Ledger::load(ledger_path)),
+            flash_loan,
+            deployment: deployment.clone(),
+        })
+    }
+
+    pub fn ledger(&self) -> Arc<Ledger> {
+        Arc::clone(&self.ledger)
+    }
+
+    /// Submits validated paths until the channel closes or `shutdown` fires.
+    /// A submission already underway is finished first, so no signed transaction is left half sent
+    pub async fn send_transactions(&mut self, mut tx_rx: Receiver<Event>, mut shutdown: broadcast::Receiver<()>) {
+        loop {
+            let event = tokio::select! {
+                event = tx_rx.recv() => event,
+                _ = shutdown.recv() => {
+                    info!("Transaction sender shutting down");
+                    return;
+                }
+            };
+            let Some(event) = event else {
+                break;
+            };
+            let Event::ValidPath((params, start_token, expected_out, gas_limit, block_number)) = event else {
+                continue;
+            };
+
+            if !self.gas_limits.within_budget(gas_limit) {
+                warn!(
+                    "Skipping {}-hop path for block {}: estimated gas {} exceeds cap {}",
+                    params.pools.len(), block_number, gas_limit, self.gas_limits.max
+                );
+                continue;
+            }
+
+            // Paths that can't repay their input after the tolerated slippage aren't worth sending.
+            // This only filters, the output floor is enforced on-chain only when FlashSwap takes `amountOutMin`
+            let min_out = min_amount_out(expected_out, self.slippage_bps);
+            if min_out <= params.amountIn {
+                warn!(
+                    "Skipping path for block {}: min output {} after {} bps slippage doesn't cover input {}",
+                    block_number, min_out, self.slippage_bps, params.amountIn
+                );
+                continue;
+            }
+
+            let profit = expected_out.saturating_sub(params.amountIn);
+            if let Err(e) = self.submit(params, start_token, profit, min_out, gas_limit, block_number).await {
+                error!("Failed to submit path for block {}: {:?}", block_number, e);
+            }
+        }
+    }
+
+    async fn submit(
+        &mut self,
+        params: SwapParams,
+        start_token: Address,
+        profit: U256,
+        min_out: U256,
+        gas_limit: u64,
+        block_number: u64,
+    ) -> Result<()> {
+        // executeArbitrage always borrows from the Aave pool, a provider selector joins SwapParams
+        // once FlashSwap implements another provider's callback
+        if !self.dry_run && !self.flash_loan.provider.supported_by_executor() {
+            return Err(anyhow!("Refusing to send, FlashSwap can't borrow from {:?}", self.flash_loan.provider));
+        }
+        let pool_versions = params.poolVersions.clone();
+        let calldata = arbitrage_calldata(params, min_out, &self.deployment);
+
+        // Half the expected profit goes to gas, spread over the path's gas limit
+        let (max_fee, priority_fee) = self.gas_station.get_gas_fees_for_limit(profit, gas_limit);
+        let (tx, signature) = self
+            .sender
+            .build_and_sign_tx(calldata.clone(), gas_limit, max_fee, priority_fee)
+            .await?;
+        let rlp_bytes = self.sender.get_signed_rlp(&tx, &signature)?;
+
+        if self.dry_run {
+            self.paper_profit += profit;
+            self.paper_trades += 1;
+            info!(
+                "[DRY_RUN] block {}: to={} gas_limit={:?} max_fee={} priority_fee={} expected_profit={} calldata=0x{}",
+                block_number,
+                self.sender.contract_address,
+                tx.gas,
+                max_fee,
+                priority_fee,
+                profit,
+                hex::encode(&calldata)
+            );
+            info!(
+                "[DRY_RUN] {} paper trades, {} total hypothetical profit",
+                self.paper_trades, self.paper_profit
+            );
+            return Ok(());
+        }
+
+        self.send_and_monitor(rlp_bytes, start_token, profit, block_number, pool_versions).await?;
+        Ok(())
+    }
+
+    /// Broadcasts the transaction, records it in the ledger and reconciles it once the receipt lands.
+    /// Profit is booked in `token`, the path's start token.
+    /// The receipt's gas also calibrates the path gas model for the path's hop `pool_versions`
+    async fn send_and_monitor(
+        &self,
+        rlp_bytes: Bytes,
+        token: Address,
+        expected_profit: U256,
+        block_number: u64,
+        pool_versions: Vec<u8>,
+    ) -> Result<B256> {
+        let tx_hash = self
+            .sender
+            .submit_to_all(format!("0x{}", hex::encode(&rlp_bytes)), block_number)
+            .await?;
+        info!("Submitted {} for block {} (expected profit {})", tx_hash, block_number, expected_profit);
+        self.ledger
+            .record_submission(tx_hash, block_number, token, expected_profit);
+
+        let provider = self.sender.provider();
+        let ledger = Arc::clone(&self.ledger);
+        let contract = self.sender.contract_address;
+        let gas_model = Arc::clone(&self.gas_model);
+        tokio::spawn(async move {
+            if let Err(e) = reconcile_receipt(provider, ledger, gas_model, tx_hash, token, contract, pool_versions).await {
+                warn!("Failed to reconcile {}: {:?}", tx_hash, e);
+            }
+        });
+
+        Ok(tx_hash)
+    }
+}
+
+/// Smallest acceptable output: `estimated_output * (1 - slippage_bps / 10_000)`
+pub fn min_amount_out(estimated_output: U256, slippage_bps: u32) -> U256 {
+    let slippage_bps = U256::from(slippage_bps.min(10_000));
+    estimated_output * (U256::from(10_000) - slippage_bps) / U256::from(10_000)
+}
+
+/// Waits for the receipt and books the contract's balance change over the inclusion block, net of gas.
+/// Successful receipts feed their gas used back into the chain's path gas model
+async fn reconcile_receipt<T>(
+    provider: Arc<RootProvider<T>>,
+    ledger: Arc<Ledger>,
+    gas_model: Arc<ChainGasModel>,
+    tx_hash: B256,
+    token: Address,
+    contract: Address,
+    pool_versions: Vec<u8>,
+) -> Result<()>
+where
+    T: Transport + Clone + Send + Sync + 'static,
+    <T as Transport>::Error: Send + Sync + 'static,
+{
+    let mut receipt = None;
+    for _ in 0..RECEIPT_POLL_ATTEMPTS {
+        receipt = provider.get_transaction_receipt(tx_hash).await?;
+        if receipt.is_some() {
+            break;
+        }
+        tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+    }
+    let receipt = receipt.ok_or_else(|| anyhow!("No receipt for {} after {} polls", tx_hash, RECEIPT_POLL_ATTEMPTS))?;
+    let block = receipt.block_number.context("Receipt missing block number")?;
+
+    let erc20 = ERC20Token::new(token, provider.as_ref());
+    let before = erc20.balanceOf(contract).block(BlockId::number(block - 1)).call().await?;
+    let after = erc20.balanceOf(contract).block(BlockId::number(block)).call().await?;
+    let balance_delta = I256::from_raw(after) - I256::from_raw(before);
+
+    let gas_cost = U256::from(receipt.gas_used) * U256::from(receipt.effective_gas_price);
+    let success = receipt.status();
+    info!(
+        "Tx {} landed in block {} (success={}): balance delta {}, gas cost {}",
+        tx_hash, block, success, balance_delta, gas_cost
+    );
+    ledger.record_receipt(tx_hash, success, balance_delta, gas_cost);
+    // Reverts stop early and would drag the hop costs down
+    if success {
+        gas_model.record(&pool_versions, receipt.gas_used as u64);
+    }
+    Ok(())
+}
+
+/// Posts `eth_sendRawTransaction` to a single endpoint
+async fn send_raw_to_relay(client: &Client, relay: &Url, rlp_hex: &str) -> Result<B256> {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_sendRawTransaction",
+        "params": [rlp_hex],
+    });
+
+    let response: Value = client
+        .post(relay.clone())
+        .json(&body)
+        .send()
+        .await
+        .context("Relay request failed")?
+        .json()
+        .await
+        .context("Invalid relay response")?;
+
+    if let Some(err) = response.get("error") {
+        return Err(anyhow!("Relay error: {}", err));
+    }
+
+    let hash = response
+        .get("result")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("Relay response missing result: {}", response))?;
+    B256::from_str(hash).context("Relay returned invalid tx hash")
+}
@@ -0,0 +1,108 @@
+use log::{error, info};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Last-activity timestamp of a worker task, bumped once per unit of work
+#[derive(Debug)]
+pub struct Heartbeat {
+    last_beat: AtomicU64,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self {
+            last_beat: AtomicU64::new(unix_now()),
+        }
+    }
+
+    #[inline]
+    pub fn beat(&self) {
+        self.last_beat.store(unix_now(), Ordering::Relaxed);
+    }
+
+    /// Seconds since the last beat
+    pub fn silent_for(&self) -> u64 {
+        unix_now().saturating_sub(self.last_beat.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Watches worker heartbeats and reports tasks that went silent.
+/// With `exit_on_stall` the process exits so an external supervisor can restart it.
+pub struct Watchdog {
+    tasks: Mutex<HashMap<String, Arc<Heartbeat>>>,
+    timeout: Duration,
+    exit_on_stall: bool,
+}
+
+impl Watchdog {
+    pub fn new(timeout: Duration, exit_on_stall: bool) -> Self {
+        Self {
+            tasks: Mutex::new(HashMap::new()),
+            timeout,
+            exit_on_stall,
+        }
+    }
+
+    /// Reads WATCHDOG_TIMEOUT_SECS (default 60) and WATCHDOG_EXIT (default false)
+    pub fn from_env() -> Self {
+        let timeout = std::env::var("WATCHDOG_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+        let exit_on_stall = std::env::var("WATCHDOG_EXIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(false);
+        Self::new(Duration::from_secs(timeout), exit_on_stall)
+    }
+
+    /// Start watching `heartbeat` under `name`
+    pub fn track(&self, name: impl Into<String>, heartbeat: Arc<Heartbeat>) {
+        self.tasks.lock().unwrap().insert(name.into(), heartbeat);
+    }
+
+    /// Checks every tracked task at half the timeout, forever
+    pub async fn run(self: Arc<Self>) {
+        info!(
+            "Watchdog started (timeout {:?}, exit on stall: {})",
+            self.timeout, self.exit_on_stall
+        );
+        let timeout_secs = self.timeout.as_secs();
+        loop {
+            tokio::time::sleep(self.timeout / 2).await;
+
+            let stalled: Vec<(String, u64)> = self
+                .tasks
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, heartbeat)| (name.clone(), heartbeat.silent_for()))
+                .filter(|(_, silent)| *silent > timeout_secs)
+                .collect();
+
+            for (name, silent) in &stalled {
+                error!("🚨 Task {} has been silent for {}s (timeout {}s)", name, silent, timeout_secs);
+            }
+
+            if !stalled.is_empty() && self.exit_on_stall {
+                error!("🚨 Exiting so the supervisor can restart the bot");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}